@@ -27,6 +27,14 @@ struct Config {
 }
 
 fn main() {
+    // `eauth config ...` runs the libelp configuration toolkit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [command, rest @ ..] = args.as_slice()
+        && command == "config"
+    {
+        std::process::exit(libelp::config::cli::run::<Config>("eauth config", rest).into());
+    }
+
     // 测试默认值
     let config = Config::new();
     println!("{}", config.to_toml());
@@ -0,0 +1,101 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{Expr, Field, Visibility};
+
+/// Generate `builder()`, the `<Name>Builder` type and the `Assign` impl editing through it
+///
+/// The builder records the key paths its setters were called for, so that
+/// `build()` can tell an unset required field from one set to an empty value.
+pub fn generate_builder_impl(
+    name: &syn::Ident,
+    vis: &Visibility,
+    field_configs: &[(Field, Option<Expr>, Option<String>)],
+) -> TokenStream {
+    let builder_name = format_ident!("{}Builder", name);
+
+    let setters: Vec<TokenStream> = field_configs
+        .iter()
+        .filter_map(|(field, _, note)| {
+            let ident = field.ident.as_ref()?;
+            let ty = &field.ty;
+            let key = ident.unraw().to_string();
+            let doc = note.as_ref().map(|note| quote! { #[doc = #note] });
+            // Paths set in a flattened section are keys of this table
+            let nested_path = if super::flattened(field) {
+                quote! { __path }
+            } else {
+                quote! { format!("{}.{}", #key, __path) }
+            };
+            // `#ty: Assign<__V>` picks the argument by the field type: values are
+            // set as they are and nested sections through their builder
+            Some(quote! {
+                #doc
+                pub fn #ident<__V>(mut self, value: __V) -> Self
+                where
+                    #ty: libelp::config::builder::Assign<__V>,
+                {
+                    let __set = libelp::config::builder::Assign::__elp_assign(&mut self.inner.#ident, value);
+                    self.set.extend(__set.into_iter().map(|__path| match __path.as_str() {
+                        "" => #key.to_string(),
+                        _ => #nested_path,
+                    }));
+                    self
+                }
+            })
+        })
+        .collect();
+
+    let builder_doc = format!("Builder for [`{0}`], created by [`{0}::builder`]", name);
+
+    quote! {
+        #[doc = #builder_doc]
+        #vis struct #builder_name {
+            inner: #name,
+            // Key paths whose setters were called
+            set: ::std::vec::Vec<::std::string::String>,
+        }
+
+        #[allow(dead_code)]
+        impl #name {
+            /// Start building from the default values
+            pub fn builder() -> #builder_name {
+                #builder_name { inner: Self::__elp_new(), set: ::std::vec::Vec::new() }
+            }
+        }
+
+        #[allow(dead_code)]
+        impl #builder_name {
+            #(#setters)*
+
+            /// Change the configuration directly
+            ///
+            /// Required fields without a default only count as set through their setters.
+            pub fn with(mut self, edit: impl FnOnce(&mut #name)) -> Self {
+                edit(&mut self.inner);
+                self
+            }
+
+            /// Validate the configuration and return it
+            ///
+            /// Fails naming the required fields without a default whose setters were not called.
+            pub fn build(self) -> libelp::ConfigurationResult<#name> {
+                libelp::config::meta::check_set::<#name>(&self.set)?;
+                libelp::Configuration::validate(&self.inner)?;
+                Ok(self.inner)
+            }
+        }
+
+        impl<__F> libelp::config::builder::Assign<__F> for #name
+        where
+            __F: FnOnce(#builder_name) -> #builder_name,
+        {
+            fn __elp_assign(&mut self, edit: __F) -> ::std::vec::Vec<::std::string::String> {
+                let builder = #builder_name { inner: ::std::mem::replace(self, Self::__elp_new()), set: ::std::vec::Vec::new() };
+                let builder = edit(builder);
+                *self = builder.inner;
+                builder.set
+            }
+        }
+    }
+}
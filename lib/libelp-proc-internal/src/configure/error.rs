@@ -66,6 +66,20 @@ pub enum ConfigError {
         span: Span,
     },
 
+    #[error("Field '{field_name}' sets both `default` and `default_fn`; use only one")]
+    ConflictingDefaults {
+        field_name: String,
+        span: Span,
+    },
+
+    #[error("Field '{field_name}' is `{mode}`, which does not take `{attribute}`")]
+    UnusedAttribute {
+        field_name: String,
+        mode: &'static str,
+        attribute: &'static str,
+        span: Span,
+    },
+
     #[error("Configuration struct '{struct_name}' nesting level exceeds allowed two levels (top level + one level of nested structs)")]
     NestingLevelExceeded {
         struct_name: String,
@@ -113,6 +127,12 @@ impl ConfigError {
             ConfigError::NestingLevelExceeded { span, .. } => {
                 syn::Error::new(*span, self)
             }
+            ConfigError::ConflictingDefaults { span, .. } => {
+                syn::Error::new(*span, self)
+            }
+            ConfigError::UnusedAttribute { span, .. } => {
+                syn::Error::new(*span, self)
+            }
         }
     }
 
@@ -191,6 +211,24 @@ impl ConfigError {
         Self::OnlySupportsStructs { span }
     }
 
+    /// Create ConflictingDefaults error
+    pub fn conflicting_defaults(field_name: &str, span: Span) -> Self {
+        Self::ConflictingDefaults {
+            field_name: field_name.to_string(),
+            span,
+        }
+    }
+
+    /// Create UnusedAttribute error
+    pub fn unused_attribute(field_name: &str, mode: &'static str, attribute: &'static str, span: Span) -> Self {
+        Self::UnusedAttribute {
+            field_name: field_name.to_string(),
+            mode,
+            attribute,
+            span,
+        }
+    }
+
     /// Create NestingLevelExceeded error
     pub fn nesting_level_exceeded(struct_name: &str, span: Span) -> Self {
         Self::NestingLevelExceeded {
@@ -482,6 +520,12 @@ mod tests {
         assert!(message.contains("Configuration struct 'MyConfig' nesting level exceeds allowed two levels"));
     }
 
+    #[test]
+    fn test_unused_attribute_message() {
+        let error = ConfigError::unused_attribute("pool", "flatten", "default", test_span());
+        assert_eq!(error.to_string(), "Field 'pool' is `flatten`, which does not take `default`");
+    }
+
     // Test to_syn_error conversion
     #[test]
     fn test_unsupported_literal_type_to_syn_error() {
@@ -612,6 +656,7 @@ mod tests {
 
     // Test ConfigResult type alias
     #[test]
+    #[allow(clippy::unnecessary_literal_unwrap)]
     fn test_config_result_ok() {
         let result: ConfigResult<i32> = Ok(42);
         assert_eq!(result.unwrap(), 42);
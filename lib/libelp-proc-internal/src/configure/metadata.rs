@@ -0,0 +1,102 @@
+use proc_macro2::TokenStream;
+use quote::{ToTokens, quote};
+use syn::ext::IdentExt;
+use syn::{Expr, Type};
+
+use super::process_default_value::process_default_value;
+use super::{ConfigResult, ConfigurationField};
+
+/// Render tokens the way they are usually written, e.g. `Option<String>` or `limits::MAX`
+///
+/// Literals are kept verbatim.
+pub(crate) fn written(tokens: &impl ToTokens) -> String {
+    let tokens = tokens.to_token_stream();
+    if let Ok(Expr::Lit(_)) = syn::parse2::<Expr>(tokens.clone()) {
+        return tokens.to_string();
+    }
+    let mut text = tokens.to_string();
+    for (spaced, tight) in [(" :: ", "::"), (":: ", "::"), (" < ", "<"), (" <", "<"), (" >", ">"), (" ,", ","), (" (", "("), ("( ", "("), (" )", ")"), (" . ", "."), ("& ", "&")] {
+        text = text.replace(spaced, tight);
+    }
+    text
+}
+
+/// `libelp::FieldMeta` entry of one field for `Name::FIELDS`
+pub fn field_meta(ident: &syn::Ident, ty: &Type, opts: &ConfigurationField, default: Option<&Expr>) -> TokenStream {
+    // The key serde reads, e.g. `type` for `r#type`
+    let name = ident.unraw().to_string();
+    let type_name = written(ty);
+    let optional = |text: Option<String>| match text {
+        Some(text) => quote! { Some(#text) },
+        None => quote! { None },
+    };
+    let note = optional(opts.note.clone());
+    let default = optional(default.map(written));
+    let example = optional(opts.example.as_ref().map(written));
+    let unit = optional(opts.unit.clone());
+    let deprecated = optional(opts.deprecated.clone());
+    let secret = opts.secret;
+    let required = opts.required;
+    let flatten = opts.flatten;
+    let constraints = constraints(opts).into_iter().map(|(variant, expr)| {
+        let literal = bound_text(expr);
+        quote! { libelp::config::meta::Constraint::#variant(#literal) }
+    });
+    quote! {
+        libelp::config::meta::FieldMeta {
+            name: #name,
+            path: ::std::borrow::Cow::Borrowed(#name),
+            type_name: #type_name,
+            note: #note,
+            default: #default,
+            example: #example,
+            unit: #unit,
+            allowed: <#ty as libelp::config::value::ConfigField>::__ELP_ALLOWED,
+            secret: #secret,
+            required: #required,
+            deprecated: #deprecated,
+            constraints: &[#(#constraints),*],
+            nested: <#ty as libelp::config::value::ConfigField>::__ELP_FIELDS,
+            flatten: #flatten,
+        }
+    }
+}
+
+/// Statements of the derived `validate()` for one field
+///
+/// Bounds are converted like defaults, so `min = "1s"` works for `Duration`.
+pub fn validate_field(ident: &syn::Ident, ty: &Type, opts: &ConfigurationField) -> ConfigResult<TokenStream> {
+    let key = ident.unraw().to_string();
+    let mut checks = Vec::new();
+    for (variant, expr) in constraints(opts) {
+        let bound = process_default_value(expr, ty, &key)?;
+        let (fails, word) = if variant == "Min" { (quote! { < }, "least") } else { (quote! { > }, "most") };
+        let message = format!("must be at {} {}, got {{}}", word, bound_text(expr).replace('{', "{{").replace('}', "}}"));
+        checks.push(quote! {
+            if self.#ident #fails #bound {
+                return Err(libelp::ConfigurationError::validation_error(
+                    format!(#message, libelp::config::value::inline(&libelp::config::value::ConfigField::__elp_to_value(&self.#ident))),
+                    Some(#key.to_string()),
+                ));
+            }
+        });
+    }
+    Ok(quote! {
+        #(#checks)*
+        libelp::config::value::ConfigField::__elp_validate(&self.#ident, #key)?;
+    })
+}
+
+// String bounds such as `"1s"` are shown without their quotes
+fn bound_text(expr: &Expr) -> String {
+    match expr {
+        Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) => s.value(),
+        _ => written(expr),
+    }
+}
+
+fn constraints(opts: &ConfigurationField) -> Vec<(syn::Ident, &Expr)> {
+    let min = opts.min.as_ref().map(|expr| (syn::Ident::new("Min", proc_macro2::Span::call_site()), expr));
+    let max = opts.max.as_ref().map(|expr| (syn::Ident::new("Max", proc_macro2::Span::call_site()), expr));
+    min.into_iter().chain(max).collect()
+}
@@ -1,22 +1,136 @@
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{DeriveInput, Expr, Type};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{DeriveInput, Expr};
 
 use darling::FromField;
 
+mod builder;
 mod error;
+mod metadata;
 mod process_default_value;
 mod toml_utils;
+mod units;
 
 pub use error::{ConfigError, ConfigResult, ToSynError};
 
 #[derive(FromField, Default, Debug)]
 #[darling(attributes(config))]
 struct ConfigurationField {
-    #[darling(default)]
+    // Keep string literals as written so `"5m"` reaches the type-specific parsers
+    #[darling(default, with = darling::util::parse_expr::preserve_str_literal, map = Some)]
     default: Option<Expr>,
+    // Function called for the default, e.g. `default_fn = "num_cpus::get"`
+    #[darling(default)]
+    default_fn: Option<syn::Path>,
     #[darling(default)]
     note: Option<String>,
+    // Sample value shown in verbose templates, written like a default
+    #[darling(default, with = darling::util::parse_expr::preserve_str_literal, map = Some)]
+    example: Option<Expr>,
+    // Unit of a plain number, e.g. `unit = "ms"`
+    #[darling(default)]
+    unit: Option<String>,
+    // Passwords, tokens and keys; the file holding them must stay private
+    #[darling(default)]
+    secret: bool,
+    // Absence from a loaded file is an error instead of taking the default
+    #[darling(default)]
+    required: bool,
+    // Shown to tooling through `FIELDS`
+    #[darling(default)]
+    deprecated: Option<String>,
+    // Bounds checked by the derived `validate()`, converted like defaults
+    #[darling(default, with = darling::util::parse_expr::preserve_str_literal, map = Some)]
+    min: Option<Expr>,
+    #[darling(default, with = darling::util::parse_expr::preserve_str_literal, map = Some)]
+    max: Option<Expr>,
+    // A nested configuration whose keys sit in the parent's table
+    #[darling(default)]
+    flatten: bool,
+    // Runtime-only; never read, written or listed, set from `default_fn` or `Default`
+    #[darling(default)]
+    skip: bool,
+}
+
+/// Text of the `///` doc comments in `attrs`, one line per comment line
+///
+/// The single space rustdoc expects after `///` is dropped; blank leading and
+/// trailing lines are too.
+fn doc_text(attrs: &[syn::Attribute]) -> Option<String> {
+    let docs: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value: Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }),
+                ..
+            }) => Some(s.value()),
+            _ => None,
+        })
+        .collect();
+    let lines: Vec<&str> = docs
+        .iter()
+        .flat_map(|text| text.split('\n'))
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+        .collect();
+    let text = lines.join("\n");
+    let text = text.trim_matches('\n');
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+impl ConfigurationField {
+    /// Options of `field`, once `handler` has rejected those darling cannot read
+    fn of(field: &syn::Field) -> Self {
+        Self::from_field(field).unwrap_or_default()
+    }
+
+    /// Reject options that `skip` and `flatten` fields have no use for
+    fn check_mode(&self, ident: &syn::Ident) -> ConfigResult<()> {
+        let mode = match (self.skip, self.flatten) {
+            (false, false) => return Ok(()),
+            (true, _) => "skip",
+            (false, true) => "flatten",
+        };
+        let unused = [
+            ("flatten", self.skip && self.flatten),
+            ("default", self.flatten && self.default.is_some()),
+            ("default_fn", self.flatten && self.default_fn.is_some()),
+            ("note", self.note.is_some()),
+            ("example", self.example.is_some()),
+            ("unit", self.unit.is_some()),
+            ("secret", self.secret),
+            ("required", self.required),
+            ("deprecated", self.deprecated.is_some()),
+            ("min", self.min.is_some()),
+            ("max", self.max.is_some()),
+        ];
+        match unused.into_iter().find(|(_, set)| *set) {
+            Some((attribute, _)) => Err(ConfigError::unused_attribute(&ident.to_string(), mode, attribute, ident.span())),
+            None => Ok(()),
+        }
+    }
+
+    /// The default expression, with `default_fn` turned into a call
+    fn default_expr(&self, ident: &syn::Ident) -> ConfigResult<Option<Expr>> {
+        match (&self.default, &self.default_fn) {
+            (Some(_), Some(path)) => Err(ConfigError::conflicting_defaults(&ident.to_string(), path.span())),
+            (Some(expr), None) => Ok(Some(expr.clone())),
+            (None, Some(path)) => Ok(Some(syn::parse_quote! { #path() })),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Marked `#[config(skip)]`
+pub(crate) fn skipped(field: &syn::Field) -> bool {
+    ConfigurationField::of(field).skip
+}
+
+/// Marked `#[config(flatten)]`
+pub(crate) fn flattened(field: &syn::Field) -> bool {
+    ConfigurationField::of(field).flatten
 }
 
 pub fn handler(ast: DeriveInput) -> TokenStream {
@@ -29,11 +143,19 @@ pub fn handler(ast: DeriveInput) -> TokenStream {
         _ => {
             return ConfigError::only_supports_structs(name.span())
                 .to_syn_error()
-                .to_compile_error()
-                .into();
+                .to_compile_error();
         }
     };
 
+    // A misspelled or malformed option would otherwise drop the whole attribute
+    let mut errors = darling::Error::accumulator();
+    for field in &data.fields {
+        errors.handle(ConfigurationField::from_field(field));
+    }
+    if let Err(e) = errors.finish() {
+        return e.write_errors();
+    }
+
     // Collect field configuration information
     let mut field_configs = Vec::new();
     let mut field_assignments = Vec::new();
@@ -41,68 +163,179 @@ pub fn handler(ast: DeriveInput) -> TokenStream {
     // Depth expression for each field to calculate maximum nesting depth
     let mut depth_exprs: Vec<proc_macro2::TokenStream> = Vec::new();
 
+    // Match arms resolving a field path to its note (own fields / fields of nested sections)
+    let mut note_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut nested_note_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    // Statements expanding `PathBuf` fields and recursing into nested sections
+    let mut path_resolutions: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    // Whether each field is, or contains, a `#[config(secret)]` field
+    let mut secret_exprs: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    // Match arms reading and replacing a field by dotted path
+    let mut get_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut set_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    // `FieldMeta` entries for `FIELDS` and the checks of `validate()`
+    let mut field_metas: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut validations: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    // Flattened fields answer paths that none of the own fields match
+    let mut flat_note_fallbacks: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut flat_get_fallbacks: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut flat_set_fallbacks: Vec<proc_macro2::TokenStream> = Vec::new();
+
+    // Fields that are `required` without a default
+    let mut mandatory_fields: Vec<&syn::Ident> = Vec::new();
+
     for field in &data.fields {
         let ident = field.ident.as_ref().expect("named fields only");
-        let opts = ConfigurationField::from_field(field).unwrap_or_default();
+        // The key serde reads, e.g. `type` for `r#type`
+        let key = ident.unraw().to_string();
+        let mut opts = ConfigurationField::of(field);
+        if let Err(e) = opts.check_mode(ident) {
+            return e.to_syn_error().to_compile_error();
+        }
+        // `note` wins over the doc comment
+        if !opts.skip && !opts.flatten {
+            opts.note = opts.note.take().or_else(|| doc_text(&field.attrs));
+        }
+        let default_expr = match opts.default_expr(ident) {
+            Ok(expr) => expr,
+            Err(e) => return e.to_syn_error().to_compile_error(),
+        };
 
-        let default_value = match opts.default {
+        let default_value = match default_expr {
             Some(ref default_expr) => {
                 // Use independent default value processing module
-                match process_default_value::process_default_value(default_expr, &field.ty) {
+                match process_default_value::process_default_value(default_expr, &field.ty, &key) {
                     Ok(parsed_token) => parsed_token,
                     Err(e) => {
                         // If processing fails, return compile error
-                        return e.to_syn_error().to_compile_error().into();
+                        return e.to_syn_error().to_compile_error();
                     }
                 }
             }
+            // Required fields without a default hold a placeholder until set
+            None if opts.required => {
+                let ty = &field.ty;
+                quote! { <#ty as libelp::config::value::Placeholder>::__elp_placeholder() }
+            }
             None => {
                 // No default value, use Default::default()
                 quote! { Default::default() }
             }
         };
 
-        // Collect field configuration information for TOML generation
-        field_configs.push((field.clone(), opts.default.clone(), opts.note.clone()));
+        if opts.required && default_expr.is_none() {
+            mandatory_fields.push(ident);
+        }
 
         // Generate field assignment
         field_assignments.push(quote! {
             #ident: #default_value,
         });
 
+        // Skipped fields only get their initial value
+        if opts.skip {
+            continue;
+        }
+
+        // Collect field configuration information for TOML generation
+        field_configs.push((field.clone(), default_expr.clone(), opts.note.clone()));
 
-        // Generate depth expression: basic types -> 0, other types -> <T>::__ELP_DEPTH
         let field_ty = &field.ty;
-        let is_primitive = match get_type_last_ident(field_ty).as_deref() {
-            Some("String") | Some("str") | Some("i8") | Some("i16") | Some("i32") | Some("i64")
-            | Some("i128") | Some("isize") | Some("u8") | Some("u16") | Some("u32")
-            | Some("u64") | Some("u128") | Some("usize") | Some("f32") | Some("f64")
-            | Some("bool") => true,
-            _ => false,
-        };
-        if is_primitive {
-            depth_exprs.push(quote! { 0usize });
+        field_metas.push(metadata::field_meta(ident, field_ty, &opts, default_expr.as_ref()));
+        secret_exprs.push(if opts.secret {
+            quote! { true }
         } else {
-            depth_exprs.push(quote! { 1usize + <#field_ty>::__ELP_CHILD_DEPTH });
+            quote! { <#field_ty as libelp::config::value::ConfigField>::__elp_has_secrets() }
+        });
+
+        // The keys of a flattened struct are looked up in this table, and it
+        // adds no level of nesting
+        if opts.flatten {
+            depth_exprs.push(quote! { <#field_ty>::__ELP_CHILD_DEPTH });
+            flat_note_fallbacks.push(quote! {
+                .or_else(|| <#field_ty as libelp::config::value::ConfigField>::__elp_field_note(path))
+            });
+            flat_get_fallbacks.push(quote! {
+                .or_else(|| libelp::config::value::ConfigField::__elp_get_path(&self.#ident, path))
+            });
+            flat_set_fallbacks.push(quote! {
+                if libelp::config::value::ConfigField::__elp_get_path(&self.#ident, path).is_some() {
+                    return libelp::config::value::ConfigField::__elp_set_path(&mut self.#ident, path, value);
+                }
+            });
+            path_resolutions.push(quote! {
+                libelp::Configuration::resolve_paths(&mut self.#ident, __base_dir)?;
+            });
+            validations.push(quote! {
+                libelp::Configuration::validate(&self.#ident)?;
+            });
+            continue;
+        }
+
+
+        // Leaf values report depth 0 and no notes or paths of their own; nested
+        // structs answer through their generated `ConfigField` impl
+        depth_exprs.push(quote! { <#field_ty as libelp::config::value::ConfigField>::__ELP_DEPTH });
+        if let Some(note) = &opts.note {
+            note_arms.push(quote! { #key => Some(#note), });
+        }
+        nested_note_arms.push(quote! {
+            #key => <#field_ty as libelp::config::value::ConfigField>::__elp_field_note(__rest),
+        });
+        path_resolutions.push(quote! {
+            libelp::config::value::ConfigField::__elp_resolve_paths(&mut self.#ident, #key, __base_dir)?;
+        });
+        get_arms.push(quote! {
+            #key => match __rest {
+                None => Some(libelp::config::value::ConfigField::__elp_to_value(&self.#ident)),
+                Some(__rest) => libelp::config::value::ConfigField::__elp_get_path(&self.#ident, __rest),
+            },
+        });
+        set_arms.push(quote! {
+            #key => match __rest {
+                None => libelp::config::path::set_field(&mut self.#ident, #key, value),
+                Some(__rest) => libelp::config::value::ConfigField::__elp_set_path(&mut self.#ident, __rest, value)
+                    .map_err(|e| e.in_section(#key)),
+            },
+        });
+        match metadata::validate_field(ident, field_ty, &opts) {
+            Ok(checks) => validations.push(checks),
+            Err(e) => return e.to_syn_error().to_compile_error(),
         }
     }
 
+    let mandatory = !mandatory_fields.is_empty();
+
     // Collect field references
     let fields: Vec<&syn::Field> = data.fields.iter().collect();
+    // Fields the serde helpers carry; flattened ones are (de)serialized by serde's `flatten`
+    let io_fields: Vec<&syn::Field> = fields.iter().copied().filter(|f| !skipped(f)).collect();
+    let serde_attr = |f: &syn::Field, plain: proc_macro2::TokenStream| {
+        if flattened(f) { quote! { #[serde(flatten)] } } else { plain }
+    };
 
     // --- Auto-impl serde Serialize/Deserialize ---
     // Build helper struct fields for Serialize (borrowed fields)
-    let ser_helper_fields: Vec<proc_macro2::TokenStream> = fields
+    let ser_helper_fields: Vec<proc_macro2::TokenStream> = io_fields
         .iter()
         .map(|f| {
             let ident = f.ident.as_ref().expect("named fields only");
             let ty = &f.ty;
-            quote! { #ident: &'__elp_a #ty, }
+            let attr = serde_attr(f, quote! { #[serde(serialize_with = "libelp::config::value::serialize_field")] });
+            quote! {
+                #attr
+                #ident: &'__elp_a #ty,
+            }
         })
         .collect();
 
     // Initialize helper for Serialize
-    let ser_helper_inits: Vec<proc_macro2::TokenStream> = fields
+    let ser_helper_inits: Vec<proc_macro2::TokenStream> = io_fields
         .iter()
         .map(|f| {
             let ident = f.ident.as_ref().expect("named fields only");
@@ -125,24 +358,79 @@ pub fn handler(ast: DeriveInput) -> TokenStream {
     };
 
     // Build helper struct fields for Deserialize (owned fields)
-    let de_helper_fields: Vec<proc_macro2::TokenStream> = fields
+    let de_helper_fields: Vec<proc_macro2::TokenStream> = io_fields
         .iter()
         .map(|f| {
             let ident = f.ident.as_ref().expect("named fields only");
             let ty = &f.ty;
-            quote! { #ident: #ty, }
+            let attr = serde_attr(f, quote! { #[serde(deserialize_with = "libelp::config::value::deserialize_field")] });
+            // Without a helper-wide `default`, see `de_defaults` below
+            let default = if mandatory && !flattened(f) && !mandatory_fields.contains(&ident) {
+                let default_fn = quote::format_ident!("__elp_default_{}", ident).to_string();
+                quote! { #[serde(default = #default_fn)] }
+            } else {
+                quote! {}
+            };
+            quote! {
+                #attr
+                #default
+                #ident: #ty,
+            }
         })
         .collect();
 
-    // Reconstruct Self from helper
+    // Reconstruct Self from helper; skipped fields start from their default
     let de_self_inits: Vec<proc_macro2::TokenStream> = fields
         .iter()
         .map(|f| {
             let ident = f.ident.as_ref().expect("named fields only");
-            quote! { #ident: helper.#ident, }
+            if skipped(f) {
+                quote! { #ident: <#name>::__elp_new().#ident, }
+            } else {
+                quote! { #ident: helper.#ident, }
+            }
         })
         .collect();
 
+    // Missing keys take the value `new()` gives them, so commented-out
+    // defaults in `to_toml()` output read back unchanged
+    let de_defaults = if mandatory {
+        // Required keys without a default stay missing; the others are filled
+        // one by one
+        let default_fns: Vec<proc_macro2::TokenStream> = io_fields
+            .iter()
+            .filter(|f| !flattened(f) && !mandatory_fields.contains(&f.ident.as_ref().expect("named fields only")))
+            .map(|f| {
+                let ident = f.ident.as_ref().expect("named fields only");
+                let ty = &f.ty;
+                let default_fn = quote::format_ident!("__elp_default_{}", ident);
+                quote! {
+                    fn #default_fn() -> #ty {
+                        <#name>::__elp_new().#ident
+                    }
+                }
+            })
+            .collect();
+        quote! { #( #default_fns )* }
+    } else {
+        let de_default_inits: Vec<proc_macro2::TokenStream> = io_fields
+            .iter()
+            .map(|f| {
+                let ident = f.ident.as_ref().expect("named fields only");
+                quote! { #ident: __defaults.#ident, }
+            })
+            .collect();
+        quote! {
+            impl Default for __ElpSerdeHelper {
+                fn default() -> Self {
+                    let __defaults = <#name>::__elp_new();
+                    Self { #( #de_default_inits )* }
+                }
+            }
+        }
+    };
+    let de_helper_attr = if mandatory { quote! {} } else { quote! { #[serde(default)] } };
+
     let deserialize_impl = quote! {
         impl<'de> serde::Deserialize<'de> for #name {
             fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -150,7 +438,9 @@ pub fn handler(ast: DeriveInput) -> TokenStream {
                 D: serde::Deserializer<'de>,
             {
                 #[derive(serde::Deserialize)]
+                #de_helper_attr
                 struct __ElpSerdeHelper { #( #de_helper_fields )* }
+                #de_defaults
                 let helper = __ElpSerdeHelper::deserialize(deserializer)?;
                 Ok(Self { #( #de_self_inits )* })
             }
@@ -160,32 +450,84 @@ pub fn handler(ast: DeriveInput) -> TokenStream {
     // Generate from_toml method
     let from_toml_impl = toml_utils::generate_from_toml_impl(name, &fields);
 
-    // Generate to_toml method
-    let to_toml_impl = match toml_utils::generate_to_toml_impl(name, &fields, &field_configs) {
+    // Generate to_toml method, headed by the struct's doc comment
+    let struct_doc = doc_text(&ast.attrs);
+    let to_toml_impl = match toml_utils::generate_to_toml_impl(name, struct_doc.as_deref(), &field_configs) {
         Ok(impl_code) => impl_code,
-        Err(e) => return e.to_syn_error().to_compile_error().into(),
+        Err(e) => return e.to_syn_error().to_compile_error(),
     };
 
+    let struct_doc_tokens = match &struct_doc {
+        Some(doc) => quote! { Some(#doc) },
+        None => quote! { None },
+    };
+
+    // Generate builder() and the builder type
+    let builder_impl = builder::generate_builder_impl(name, &ast.vis, &field_configs);
+
     // Fold to find maximum expression: (((0 max d1) max d2) ...)
     let mut max_fold: proc_macro2::TokenStream = quote! { 0usize };
     for de in depth_exprs {
         max_fold = quote! { Self::__elp_max(#max_fold, #de) };
     }
 
+    // Required fields without a default leave `new()` nothing sensible to put
+    // there; such structs are built with `builder()` or loaded instead
+    let (new_impl, trait_new_impl, default_impl) = if mandatory {
+        (
+            quote! {},
+            quote! {},
+            quote! {
+                impl libelp::config::value::Placeholder for #name {
+                    fn __elp_placeholder() -> Self {
+                        Self::__elp_new()
+                    }
+                }
+            },
+        )
+    } else {
+        (
+            quote! {
+                pub fn new() -> Self {
+                    Self::__elp_new()
+                }
+            },
+            quote! {
+                fn new() -> Self {
+                    Self::new()
+                }
+            },
+            quote! {
+                impl Default for #name {
+                    fn default() -> Self {
+                        Self::__elp_new()
+                    }
+                }
+            },
+        )
+    };
+
     let expanded = quote! {
         impl #name {
-            pub fn new() -> Self {
+            #new_impl
+
+            // Defaults, with the empty value of the type for required fields without one
+            #[doc(hidden)]
+            pub fn __elp_new() -> Self {
                 Self {
                     #(#field_assignments)*
                 }
             }
 
+            /// Static description of each field, see `libelp::FieldMeta`
+            pub const FIELDS: &'static [libelp::config::meta::FieldMeta] = &[#(#field_metas),*];
+
             #from_toml_impl
 
             #to_toml_impl
 
             const fn __elp_max(a: usize, b: usize) -> usize { if a > b { a } else { b } }
-            // Maximum depth of child structs: basic types=0, structs=1+child depth
+            // Maximum depth of child structs: leaf values=0, structs=1+child depth
             pub const __ELP_CHILD_DEPTH: usize = { #max_fold };
             // Limit: maximum two levels (top level + one level of nested structs); provide friendly error message if exceeded
             pub const __ELP_ASSERT_MSG: () = {
@@ -196,38 +538,181 @@ pub fn handler(ast: DeriveInput) -> TokenStream {
                         "' nesting level exceeds allowed two levels (top level + one level of nested structs)"
                     ));
                 }
-                ()
             };
             // Force reference to the above constant at type level to ensure compile-time evaluation and error reporting
         pub const __ELP_ENFORCER: [(); { let _ = Self::__ELP_ASSERT_MSG; 1 }] = [(); { let _ = Self::__ELP_ASSERT_MSG; 1 }];
         }
 
-        impl Default for #name {
-            fn default() -> Self {
-                Self::new()
-            }
-        }
+        #default_impl
+
+        #builder_impl
 
         #serialize_impl
         #deserialize_impl
 
+        // Lets the struct be used as a field of another configuration struct
+        impl libelp::config::value::ConfigField for #name {
+            fn __elp_type_name() -> &'static str {
+                stringify!(#name)
+            }
+
+            fn __elp_from_value(value: &toml::Value) -> Result<Self, String> {
+                <Self as serde::Deserialize>::deserialize(value.clone()).map_err(|e| e.to_string())
+            }
+
+            // Every field writes a TOML value, so a failure is a bug in a field's
+            // `ConfigValue` impl; an empty table in its place would hide it
+            fn __elp_to_value(&self) -> toml::Value {
+                toml::Value::try_from(self).unwrap_or_else(|e| {
+                    panic!("{} cannot be written as TOML: {}", stringify!(#name), e)
+                })
+            }
+
+            const __ELP_DEPTH: usize = 1 + Self::__ELP_CHILD_DEPTH;
+
+            const __ELP_FIELDS: Option<&'static [libelp::config::meta::FieldMeta]> = Some(Self::FIELDS);
+
+            const __ELP_DOC: Option<&'static str> = #struct_doc_tokens;
+
+            fn __elp_deserialize<'de, __D>(deserializer: __D) -> Result<Self, __D::Error>
+            where
+                __D: serde::Deserializer<'de>,
+            {
+                <Self as serde::Deserialize>::deserialize(deserializer)
+            }
+
+            fn __elp_serialize<__S>(&self, serializer: __S) -> Result<__S::Ok, __S::Error>
+            where
+                __S: serde::Serializer,
+            {
+                serde::Serialize::serialize(self, serializer)
+            }
+
+            fn __elp_from_toml_lenient(value: &toml::Value) -> Option<Self> {
+                value.as_table().map(|_| Self::__elp_from_toml_value(value))
+            }
+
+            fn __elp_section(
+                &self,
+                __depth: usize,
+                __path: &str,
+                __options: &libelp::config::template::TemplateOptions,
+            ) -> Option<String> {
+                Some(self.__elp_to_toml_depth(__depth, __path, __options))
+            }
+
+            fn __elp_field_note(path: &str) -> Option<&'static str> {
+                <Self as libelp::Configuration>::field_note(path)
+            }
+
+            fn __elp_has_secrets() -> bool {
+                <Self as libelp::Configuration>::has_secrets()
+            }
+
+            fn __elp_get_path(&self, path: &str) -> Option<toml::Value> {
+                <Self as libelp::Configuration>::get_path(self, path)
+            }
+
+            fn __elp_set_path(&mut self, path: &str, value: &toml::Value) -> libelp::ConfigurationResult<()> {
+                let (__head, __rest) = match path.split_once('.') {
+                    Some((__head, __rest)) => (__head, Some(__rest)),
+                    None => (path, None),
+                };
+                match __head {
+                    #(#set_arms)*
+                    _ => {
+                        #(#flat_set_fallbacks)*
+                        Err(libelp::ConfigurationError::unknown_key(path, Vec::new()))
+                    }
+                }
+            }
+
+            fn __elp_validate(&self, key: &str) -> libelp::ConfigurationResult<()> {
+                <Self as libelp::Configuration>::validate(self).map_err(|e| e.in_section(key))
+            }
+
+            fn __elp_resolve_paths(
+                &mut self,
+                key: &str,
+                base_dir: &dyn Fn(&str) -> Option<::std::path::PathBuf>,
+            ) -> libelp::ConfigurationResult<()> {
+                <Self as libelp::Configuration>::resolve_paths(self, &|__path: &str| {
+                    base_dir(&format!("{}.{}", key, __path))
+                })
+            }
+        }
+
         impl libelp::Configuration for #name {
-            fn new() -> Self {
-                Self::new()
+            #trait_new_impl
+
+            // The inherent `__elp_new`, which takes precedence over this one
+            fn __elp_new() -> Self {
+                Self::__elp_new()
+            }
+
+            fn field_note(path: &str) -> Option<&'static str> {
+                let __note = match path.split_once('.') {
+                    None => match path {
+                        #(#note_arms)*
+                        _ => None,
+                    },
+                    Some((__head, __rest)) => {
+                        let _ = __rest;
+                        match __head {
+                            #(#nested_note_arms)*
+                            _ => None,
+                        }
+                    }
+                };
+                __note #(#flat_note_fallbacks)*
+            }
+
+            fn has_secrets() -> bool {
+                false #(|| #secret_exprs)*
+            }
+
+            fn get_path(&self, path: &str) -> Option<toml::Value> {
+                let (__head, __rest) = match path.split_once('.') {
+                    Some((__head, __rest)) => (__head, Some(__rest)),
+                    None => (path, None),
+                };
+                let _ = __rest;
+                let __value = match __head {
+                    #(#get_arms)*
+                    _ => None,
+                };
+                __value #(#flat_get_fallbacks)*
+            }
+
+            fn set_path(&mut self, path: &str, value: impl Into<toml::Value>) -> libelp::ConfigurationResult<()> {
+                if <Self as libelp::Configuration>::get_path(self, path).is_none() {
+                    let __known = <Self as libelp::Configuration>::metadata();
+                    return Err(libelp::config::path::unknown_key(path, __known.iter().map(|f| &f.path)));
+                }
+                libelp::config::value::ConfigField::__elp_set_path(self, path, &value.into())
+            }
+
+            fn metadata() -> Vec<libelp::config::meta::FieldMeta> {
+                libelp::config::meta::flatten(Self::FIELDS)
+            }
+
+            fn validate(&self) -> libelp::ConfigurationResult<()> {
+                #(#validations)*
+                Ok(())
+            }
+
+            fn resolve_paths(
+                &mut self,
+                __base_dir: &dyn Fn(&str) -> Option<::std::path::PathBuf>,
+            ) -> libelp::ConfigurationResult<()> {
+                let _ = __base_dir;
+                #(#path_resolutions)*
+                Ok(())
             }
         }
     };
 
-    return TokenStream::from(expanded);
-}
-
-// Get the last identifier of a type (type name)
-fn get_type_last_ident(ty: &Type) -> Option<String> {
-    if let Type::Path(tp) = ty {
-        tp.path.segments.last().map(|s| s.ident.to_string())
-    } else {
-        None
-    }
+    expanded
 }
 
 #[cfg(test)]
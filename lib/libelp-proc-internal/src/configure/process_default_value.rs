@@ -1,20 +1,34 @@
-use proc_macro2::{Span, TokenStream};
-use quote::quote;
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::{quote, quote_spanned};
 use syn::{Expr, Lit, LitStr, Type};
 use syn::spanned::Spanned;
 
 use super::{ConfigError, ConfigResult};
 
+/// Primitive types, whose literal defaults are emitted as written
+///
+/// Every other field type, aliases of these included, receives its default
+/// through `libelp::ConfigValue`, checked at compile time against the type's
+/// `DEFAULT_LITERALS` and `DEFAULT_RANGE`.
+const PRIMITIVE_TYPES: &[&str] = &[
+    "String", "str", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    "f32", "f64", "bool",
+];
+
 /// Process default value expression, check type matching and perform necessary conversions
 pub fn process_default_value(
     default_expr: &Expr,
     field_ty: &Type,
+    field_name: &str,
 ) -> ConfigResult<TokenStream> {
     // Get field type name
     let type_name = get_type_name(field_ty)?;
 
     // Process literal expressions
     if let Expr::Lit(lit) = default_expr {
+        if !PRIMITIVE_TYPES.contains(&type_name.as_str()) {
+            return handle_config_value_literal(&lit.lit, field_ty, field_name);
+        }
         match &lit.lit {
             Lit::Str(str_lit) => handle_string_literal(str_lit, &type_name),
             Lit::Int(int_lit) => handle_int_literal(int_lit, &type_name, field_ty),
@@ -26,26 +40,28 @@ pub fn process_default_value(
             )),
         }
     } else if let Expr::Path(path_expr) = default_expr {
-        // Process path expressions (such as identifiers)
-        if let Some(segment) = path_expr.path.segments.last() {
-            let ident = &segment.ident;
-            match type_name.as_str() {
-                // Treat identifier content as string literal
-                "String" => {
-                    let lit = LitStr::new(&ident.to_string(), Span::call_site());
-                    Ok(quote! { #lit.to_string() })
-                }
-                "str" => {
-                    let lit = LitStr::new(&ident.to_string(), Span::call_site());
-                    Ok(quote! { #lit })
-                }
-                _ => Ok(quote! { #ident }),
+        // Process path expressions: constants, or bare words used as strings
+        if path_expr.path.segments.is_empty() {
+            return Err(ConfigError::cannot_parse_path_expression(default_expr.span()));
+        }
+        let bare_word = path_expr.path.get_ident().map(|ident| ident.to_string());
+        match (type_name.as_str(), bare_word) {
+            // Treat identifier content as string literal, e.g. `default = localhost`
+            // or `default = INFO`; constants are named by their path
+            ("String", Some(word)) => {
+                let lit = LitStr::new(&word, Span::call_site());
+                Ok(quote! { #lit.to_string() })
+            }
+            ("str", Some(word)) => {
+                let lit = LitStr::new(&word, Span::call_site());
+                Ok(quote! { #lit })
             }
-        } else {
-            Err(ConfigError::cannot_parse_path_expression(default_expr.span()))
+            // Constants of type `&str` or `String` both become a `String`
+            ("String", None) => Ok(quote! { ::std::string::ToString::to_string(&#path_expr) }),
+            _ => Ok(quote! { #path_expr }),
         }
     } else {
-        // For other non-literal expressions, use directly
+        // Function calls and const expressions are evaluated when `new()` runs
         Ok(quote! { #default_expr })
     }
 }
@@ -64,6 +80,81 @@ fn get_type_name(ty: &Type) -> ConfigResult<String> {
     }
 }
 
+/// Hand a literal default to the field type's `ConfigValue` impl
+///
+/// The literal is described by every kind of value it parses as, and a
+/// `const` item fails compilation unless the type accepts one of them.
+fn handle_config_value_literal(lit: &Lit, field_ty: &Type, field_name: &str) -> ConfigResult<TokenStream> {
+    let (kinds, integer, value) = match lit {
+        Lit::Str(str_lit) => {
+            let kinds = string_kinds(&str_lit.value());
+            let check = check_default(field_ty, &kinds, quote! { None }, lit.span());
+            return Ok(quote! {
+                {
+                    #check
+                    libelp::config::value::default_from_literal::<#field_ty>(#field_name, #str_lit)
+                }
+            });
+        }
+        Lit::Int(int_lit) => {
+            let digits = int_lit.base10_digits();
+            let value = digits
+                .parse::<i64>()
+                .map_err(|e| ConfigError::parse_error(digits, "i64", &e.to_string(), int_lit.span()))?;
+            let integer = Literal::i128_unsuffixed(i128::from(value));
+            (vec!["INTEGER"], quote! { Some(#integer) }, quote! { toml::Value::Integer(#value) })
+        }
+        Lit::Float(float_lit) => {
+            let digits = float_lit.base10_digits();
+            let value = digits
+                .parse::<f64>()
+                .map_err(|e| ConfigError::parse_error(digits, "f64", &e.to_string(), float_lit.span()))?;
+            (vec!["FLOAT"], quote! { None }, quote! { toml::Value::Float(#value) })
+        }
+        Lit::Bool(bool_lit) => (vec!["BOOL"], quote! { None }, quote! { toml::Value::Boolean(#bool_lit) }),
+        other => {
+            return Err(ConfigError::unsupported_literal_type(&format!("{:?}", other), field_ty.span()));
+        }
+    };
+    let check = check_default(field_ty, &kinds, integer, lit.span());
+    Ok(quote! {
+        {
+            #check
+            libelp::config::value::default_from_value::<#field_ty>(#field_name, #value)
+        }
+    })
+}
+
+/// The `libelp::config::value::Literals` a string default parses as
+fn string_kinds(text: &str) -> Vec<&'static str> {
+    let mut kinds = vec!["STRING"];
+    let parses = [
+        ("DURATION", super::units::parse_duration(text).is_ok()),
+        ("BYTE_SIZE", super::units::parse_byte_size(text).is_ok()),
+        ("IP_ADDR", text.parse::<std::net::IpAddr>().is_ok()),
+        ("IPV4_ADDR", text.parse::<std::net::Ipv4Addr>().is_ok()),
+        ("IPV6_ADDR", text.parse::<std::net::Ipv6Addr>().is_ok()),
+        ("SOCKET_ADDR", text.parse::<std::net::SocketAddr>().is_ok()),
+        ("URL", url::Url::parse(text).is_ok()),
+    ];
+    kinds.extend(parses.into_iter().filter(|(_, ok)| *ok).map(|(kind, _)| kind));
+    kinds
+}
+
+/// A `const` item checking the default against the field type, reported at the literal
+fn check_default(field_ty: &Type, kinds: &[&str], integer: TokenStream, span: Span) -> TokenStream {
+    let kinds = kinds.iter().map(|kind| {
+        let kind = syn::Ident::new(kind, span);
+        quote! { libelp::config::value::Literals::#kind }
+    });
+    quote_spanned! {span=>
+        const _: () = libelp::config::value::check_default::<#field_ty>(
+            libelp::config::value::Literals::NONE #(.or(#kinds))*,
+            #integer,
+        );
+    }
+}
+
 /// Handle string literals
 fn handle_string_literal(
     str_lit: &syn::LitStr,
@@ -149,7 +240,7 @@ where
         Ok(parsed) => Ok(quote! { #parsed }),
         Err(e) => Err(ConfigError::parse_error(
             value_str,
-            &std::any::type_name::<T>(),
+            std::any::type_name::<T>(),
             &e.to_string(),
             field_ty.span(),
         )),
@@ -230,7 +321,7 @@ mod tests {
         let expr = create_string_expr("hello");
         let ty = create_type("String");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -243,7 +334,7 @@ mod tests {
         let expr = create_string_expr("world");
         let ty = create_type("str");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -256,7 +347,7 @@ mod tests {
         let expr = create_string_expr("hello");
         let ty = create_type("i32");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_err());
 
         let error = result.unwrap_err();
@@ -268,7 +359,7 @@ mod tests {
         let expr = create_int_expr("42");
         let ty = create_type("i32");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -282,7 +373,7 @@ mod tests {
         let expr = create_int_expr("100");
         let ty = create_type("u16");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -295,7 +386,7 @@ mod tests {
         let expr = create_int_expr("42");
         let ty = create_type("String");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_err());
 
         let error = result.unwrap_err();
@@ -307,7 +398,7 @@ mod tests {
         let expr = create_int_expr("1000");
         let ty = create_type("i8");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_err());
 
         let error = result.unwrap_err();
@@ -319,7 +410,7 @@ mod tests {
         let expr = create_float_expr("3.14");
         let ty = create_type("f32");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -332,7 +423,7 @@ mod tests {
         let expr = create_float_expr("2.718");
         let ty = create_type("f64");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -345,7 +436,7 @@ mod tests {
         let expr = create_float_expr("3.14");
         let ty = create_type("i32");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_err());
 
         let error = result.unwrap_err();
@@ -357,7 +448,7 @@ mod tests {
         let expr = create_bool_expr(true);
         let ty = create_type("bool");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -370,7 +461,7 @@ mod tests {
         let expr = create_bool_expr(false);
         let ty = create_type("bool");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -383,7 +474,7 @@ mod tests {
         let expr = create_bool_expr(true);
         let ty = create_type("String");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_err());
 
         let error = result.unwrap_err();
@@ -395,7 +486,7 @@ mod tests {
         let expr = create_non_literal_expr();
         let ty = create_type("MyType");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -598,7 +689,7 @@ mod tests {
         });
         let ty = create_type("String");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_err());
         assert!(
             result
@@ -613,7 +704,7 @@ mod tests {
         let expr = create_string_expr("");
         let ty = create_type("String");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -626,7 +717,7 @@ mod tests {
         let expr = create_int_expr("0");
         let ty = create_type("i32");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -639,7 +730,7 @@ mod tests {
         let expr = create_int_expr("-42");
         let ty = create_type("i32");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
@@ -652,11 +743,99 @@ mod tests {
         let expr = create_float_expr("-3.14");
         let ty = create_type("f32");
 
-        let result = process_default_value(&expr, &ty);
+        let result = process_default_value(&expr, &ty, "field");
         assert!(result.is_ok());
 
         let tokens = result.unwrap();
         let expected = quote! { -3.14f32 };
         assert_eq!(tokens.to_string(), expected.to_string());
     }
+
+    // The kinds a default is described by, as emitted for the compile-time check
+    fn literal_kinds(tokens: &TokenStream) -> Vec<String> {
+        let s = tokens.to_string();
+        let check = &s[s.find("check_default").expect("no compile-time check")..];
+        let check = &check[..check.find(';').unwrap()];
+        check.split("Literals :: ").skip(2).map(|kind| kind.split(')').next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_non_primitive_defaults_go_through_config_value() {
+        let ty: Type = parse_quote! { std::time::Duration };
+        let tokens = process_default_value(&create_string_expr("1m30s"), &ty, "field").unwrap();
+        assert!(
+            tokens.to_string().contains(
+                &quote! { libelp::config::value::default_from_literal::<std::time::Duration>("field", "1m30s") }.to_string()
+            ),
+            "{}",
+            tokens
+        );
+        assert_eq!(literal_kinds(&tokens), ["STRING", "DURATION"]);
+
+        let ty: Type = parse_quote! { ByteSize };
+        let tokens = process_default_value(&create_int_expr("512"), &ty, "field").unwrap();
+        assert!(tokens.to_string().contains("toml :: Value :: Integer (512i64)"), "{}", tokens);
+        assert!(tokens.to_string().contains("Literals :: INTEGER) , Some (512) ,"), "{}", tokens);
+    }
+
+    #[test]
+    fn test_string_defaults_are_described_by_what_they_parse_as() {
+        let kinds = |text: &str| literal_kinds(&process_default_value(&create_string_expr(text), &parse_quote! { T }, "f").unwrap());
+        assert_eq!(kinds("10MiB"), ["STRING", "BYTE_SIZE"]);
+        assert_eq!(kinds("5 minutes"), ["STRING"]);
+        assert_eq!(kinds("0.0.0.0:80"), ["STRING", "SOCKET_ADDR"]);
+        assert_eq!(kinds("10.0.0.1"), ["STRING", "IP_ADDR", "IPV4_ADDR"]);
+        assert_eq!(kinds("300.1.1.1"), ["STRING"]);
+        assert_eq!(kinds("https://example.com/auth"), ["STRING", "URL"]);
+        assert_eq!(kinds("/var/lib/eauth"), ["STRING"]);
+    }
+
+    #[test]
+    fn test_aliases_are_checked_through_config_value() {
+        // `type Port = u16` is not spelled as a primitive, so the range comes from the trait
+        let ty: Type = parse_quote! { Port };
+        let tokens = process_default_value(&create_int_expr("70000"), &ty, "port").unwrap();
+        assert!(tokens.to_string().contains("check_default :: < Port >"), "{}", tokens);
+        assert!(tokens.to_string().contains("Some (70000)"), "{}", tokens);
+        assert_eq!(literal_kinds(&tokens), ["INTEGER"]);
+    }
+
+    #[test]
+    fn test_user_defined_type_defaults_go_through_config_value() {
+        let ty: Type = parse_quote! { Currency };
+        let tokens = process_default_value(&create_string_expr("EUR"), &ty, "currency").unwrap();
+        assert!(
+            tokens.to_string().contains(
+                &quote! { libelp::config::value::default_from_literal::<Currency>("currency", "EUR") }.to_string()
+            ),
+            "{}",
+            tokens
+        );
+
+        let tokens = process_default_value(&create_float_expr("19.5"), &ty, "vat").unwrap();
+        assert!(tokens.to_string().contains("toml :: Value :: Float (19.5f64)"), "{}", tokens);
+        assert_eq!(literal_kinds(&tokens), ["FLOAT"]);
+        let tokens = process_default_value(&create_bool_expr(true), &ty, "flag").unwrap();
+        assert!(tokens.to_string().contains("default_from_value :: < Currency >"), "{}", tokens);
+    }
+
+    #[test]
+    fn test_constant_paths_and_expressions() {
+        let ty = create_type("u16");
+        let tokens = process_default_value(&parse_quote! { ports::HTTPS }, &ty, "field").unwrap();
+        assert_eq!(tokens.to_string(), quote! { ports::HTTPS }.to_string());
+        let tokens = process_default_value(&parse_quote! { 60 * 60 }, &ty, "field").unwrap();
+        assert_eq!(tokens.to_string(), quote! { 60 * 60 }.to_string());
+
+        let ty = create_type("String");
+        let tokens = process_default_value(&parse_quote! { localhost }, &ty, "field").unwrap();
+        assert_eq!(tokens.to_string(), quote! { "localhost".to_string() }.to_string());
+        // A bare word is a string even when it looks like a constant
+        let tokens = process_default_value(&parse_quote! { INFO }, &ty, "field").unwrap();
+        assert_eq!(tokens.to_string(), quote! { "INFO".to_string() }.to_string());
+        let tokens = process_default_value(&parse_quote! { self::DEFAULT_HOST }, &ty, "field").unwrap();
+        assert_eq!(tokens.to_string(), quote! { ::std::string::ToString::to_string(&self::DEFAULT_HOST) }.to_string());
+        let tokens = process_default_value(&parse_quote! { hosts::primary }, &ty, "field").unwrap();
+        assert!(tokens.to_string().contains("hosts :: primary"), "{}", tokens);
+    }
 }
@@ -43,15 +43,20 @@ fn make_struct(struct_name: &str, fields: Vec<(&str, Type, Option<Expr>, Option<
 }
 
 #[test]
-fn test_get_type_last_ident_primitives_and_struct() {
-    let ty: Type = parse_quote! { i32 };
-    assert_eq!(get_type_last_ident(&ty).as_deref(), Some("i32"));
-    let ty: Type = parse_quote! { std::string::String };
-    assert_eq!(get_type_last_ident(&ty).as_deref(), Some("String"));
-    let ty: Type = parse_quote! { MyStruct };
-    assert_eq!(get_type_last_ident(&ty).as_deref(), Some("MyStruct"));
-    let ty: Type = parse_quote! { &str };
-    assert_eq!(get_type_last_ident(&ty), None);
+fn test_handler_routes_every_field_through_config_value() {
+    // User-defined leaf types need no special casing: the derive only names the trait
+    let di: DeriveInput = make_struct(
+        "Billing",
+        vec![
+            ("currency", parse_quote! { Currency }, Some(parse_quote! { "EUR" }), Some("currency")),
+            ("port", parse_quote! { u16 }, Some(parse_quote! { 8080 }), None),
+        ],
+    );
+    let s = handler(di).to_string();
+    assert!(s.contains("impl libelp :: config :: value :: ConfigField for Billing"), "{}", s);
+    assert!(s.contains("< Currency as libelp :: config :: value :: ConfigField > :: __ELP_DEPTH"), "{}", s);
+    assert!(s.contains("default_from_literal :: < Currency > (\"currency\" , \"EUR\")"), "{}", s);
+    assert!(s.contains("libelp::config::value::deserialize_field"), "{}", s);
 }
 
 #[test]
@@ -120,25 +125,7 @@ fn test_handler_depth_assert_over_two_levels_panics() {
 }
 
 #[test]
-fn test_toml_utils_field_value_and_generate_impls() {
-    // Verify field_value_to_toml_string branches for default values and types; indirectly cover generate_to_toml_impl
-    let field: Field = parse_quote! { #[config(default = "hi", note = "n")] pub name: String };
-    let ty = field.ty.clone();
-    let s = super::toml_utils::field_value_to_toml_string(&field, &Some(parse_quote! { "hi" }), &ty)
-        .unwrap();
-    assert_eq!(s, "\"hi\"");
-
-    let field_num: Field = parse_quote! { #[config(default = 10)] pub n: u32 };
-    let s = super::toml_utils::field_value_to_toml_string(&field_num, &Some(parse_quote! { 10 }), &field_num.ty)
-        .unwrap();
-    assert_eq!(s, "10");
-
-    let field_bool: Field = parse_quote! { #[config(default = true)] pub b: bool };
-    let s = super::toml_utils::field_value_to_toml_string(&field_bool, &Some(parse_quote! { true }), &field_bool.ty)
-        .unwrap();
-    assert_eq!(s, "true");
-
-    // Generate to_toml / from_toml code
+fn test_toml_utils_generate_impls() {
     let di: DeriveInput = make_struct(
         "Cfg",
         vec![
@@ -151,43 +138,8 @@ fn test_toml_utils_field_value_and_generate_impls() {
     let s = ts.to_string();
     assert!(s.contains("from_toml"));
     assert!(s.contains("to_toml"));
-}
-
-#[test]
-fn test_toml_utils_field_value_expr_path_for_string_and_int() {
-    let field_s: Field = parse_quote! { #[config(default = localhost)] pub host: String };
-    let s = super::toml_utils::field_value_to_toml_string(&field_s, &Some(parse_quote! { localhost }), &field_s.ty)
-        .unwrap();
-    assert_eq!(s, "\"localhost\"");
-
-    let field_n: Field = parse_quote! { #[config(default = TEN)] pub n: u32 };
-    let s = super::toml_utils::field_value_to_toml_string(&field_n, &Some(parse_quote! { TEN }), &field_n.ty)
-        .unwrap();
-    assert_eq!(s, "TEN");
-}
-
-#[test]
-fn test_toml_utils_field_value_default_none_by_type_and_non_literal_expr() {
-    let field_s: Field = parse_quote! { pub s: String };
-    assert_eq!(super::toml_utils::field_value_to_toml_string(&field_s, &None, &field_s.ty).unwrap(), "\"\"");
-
-    let field_i: Field = parse_quote! { pub i: i32 };
-    assert_eq!(super::toml_utils::field_value_to_toml_string(&field_i, &None, &field_i.ty).unwrap(), "0");
-
-    let field_f: Field = parse_quote! { pub f: f64 };
-    assert_eq!(super::toml_utils::field_value_to_toml_string(&field_f, &None, &field_f.ty).unwrap(), "0.0");
-
-    let field_b: Field = parse_quote! { pub b: bool };
-    assert_eq!(super::toml_utils::field_value_to_toml_string(&field_b, &None, &field_b.ty).unwrap(), "false");
-
-    let field_u: Field = parse_quote! { pub x: UnknownType };
-    assert_eq!(super::toml_utils::field_value_to_toml_string(&field_u, &None, &field_u.ty).unwrap(), "null");
-
-    // Non-literal expression -> default_value
-    let field_s2: Field = parse_quote! { pub a: String };
-    let s = super::toml_utils::field_value_to_toml_string(&field_s2, &Some(parse_quote!{ some_call() }), &field_s2.ty)
-        .unwrap();
-    assert_eq!(s, "default_value");
+    assert!(s.contains("< String as libelp :: config :: value :: ConfigField > :: __elp_type_name ()"), "{}", s);
+    assert!(s.contains("libelp :: config :: value :: inline"), "{}", s);
 }
 
 #[test]
@@ -207,11 +159,10 @@ fn test_generate_from_toml_impl_contains_expected_branches() {
     ];
     let ts = super::toml_utils::generate_from_toml_impl(&parse_quote! { Test }, &fields);
     let s = ts.to_string();
-    assert!(s.contains("as_str"));
-    assert!(s.contains("as_integer"));
-    assert!(s.contains("as_float"));
-    assert!(s.contains("as_bool"));
     assert!(s.contains("from_toml"));
+    assert!(s.contains("< Sub as libelp :: config :: value :: ConfigField > :: __elp_from_toml_lenient"), "{}", s);
+    assert!(s.contains("< u16 as libelp :: config :: value :: ConfigField > :: __elp_from_toml_lenient"), "{}", s);
+    assert!(s.contains("Self :: __elp_new () . sub"), "{}", s);
 }
 
 #[test]
@@ -220,7 +171,7 @@ fn test_generate_to_toml_impl_error_on_unnamed_field() {
     let unnamed: Field = parse_quote! { #[config(default = 1)] u32 };
     let res = super::toml_utils::generate_to_toml_impl(
         &parse_quote! { S },
-        &[],
+        None,
         &[(unnamed, Some(parse_quote! { 1 }), Some("n".to_string()))],
     );
     assert!(res.is_err());
@@ -243,3 +194,212 @@ fn test_handler_error_from_invalid_default_literal() {
 }
 
 
+
+#[test]
+fn test_default_fn_becomes_a_call_and_conflicts_with_default() {
+    let di: DeriveInput = parse_quote! {
+        struct Workers {
+            #[config(default_fn = "num_threads", note = "worker threads")]
+            threads: usize,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("threads : num_threads ()"), "{}", s);
+
+    let di: DeriveInput = parse_quote! {
+        struct Workers {
+            #[config(default = 4, default_fn = "num_threads")]
+            threads: usize,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("compile_error"), "{}", s);
+    assert!(s.contains("sets both `default` and `default_fn`"), "{}", s);
+}
+
+#[test]
+fn test_builder_setters_take_what_the_field_type_assigns() {
+    // Values, aliases, custom value types and sections all get the same
+    // setter; the `Assign` impls of the field type decide the argument
+    let di: DeriveInput = parse_quote! {
+        pub struct App {
+            #[config(default = "app", note = "app name")]
+            name: String,
+            #[config(default = 8080)]
+            port: Port,
+            #[config(default = "EUR")]
+            currency: Currency,
+            database: Database,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("pub struct AppBuilder"), "{}", s);
+    for ty in ["String", "Port", "Currency", "Database"] {
+        assert!(s.contains(&format!("{} : libelp :: config :: builder :: Assign < __V >", ty)), "{}", s);
+    }
+    assert!(s.contains("pub fn port < __V > (mut self , value : __V) -> Self"), "{}", s);
+    assert!(!s.contains("Section"), "{}", s);
+    assert!(s.contains("impl < __F > libelp :: config :: builder :: Assign < __F > for App"), "{}", s);
+    assert!(s.contains("pub fn build (self) -> libelp :: ConfigurationResult < App >"), "{}", s);
+}
+
+#[test]
+fn test_field_metadata_and_bounds() {
+    let di: DeriveInput = parse_quote! {
+        struct Pool {
+            #[config(default = 8, min = 1, max = limits::MAX_POOL, note = "pool size")]
+            size: u32,
+            #[config(default = "30s", min = "1s", deprecated = "use `idle`")]
+            timeout: std::time::Duration,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("pub const FIELDS"), "{}", s);
+    assert!(s.contains("type_name : \"std::time::Duration\""), "{}", s);
+    assert!(s.contains("Constraint :: Max (\"limits::MAX_POOL\")"), "{}", s);
+    assert!(s.contains("deprecated : Some (\"use `idle`\")"), "{}", s);
+    assert!(s.contains("if self . size < 1u32"), "{}", s);
+    assert!(s.contains("\"must be at least 1s, got {}\""), "{}", s);
+    assert_eq!(super::metadata::written(&quote! { Option < Vec < u8 > > }), "Option<Vec<u8>>");
+}
+
+#[test]
+fn test_doc_comments_become_notes() {
+    let di: DeriveInput = parse_quote! {
+        /// Connection pool
+        struct Pool {
+            /// Pool size
+            ///
+            /// Shared by all workers
+            #[config(default = 8)]
+            size: u32,
+            /// Ignored in favour of the note
+            #[config(default = 1, note = "retries")]
+            retries: u32,
+        }
+    };
+    assert_eq!(doc_text(&di.attrs).as_deref(), Some("Connection pool"));
+    let s = handler(di).to_string();
+    assert!(s.contains("note : Some (\"Pool size\\n\\nShared by all workers\")"), "{}", s);
+    assert!(s.contains("note : Some (\"retries\")"), "{}", s);
+    assert!(!s.contains("Ignored in favour"), "{}", s);
+    assert!(s.contains("const __ELP_DOC : Option < & 'static str > = Some (\"Connection pool\")"), "{}", s);
+}
+
+#[test]
+fn test_example_and_unit_reach_fields_and_to_toml() {
+    let di: DeriveInput = parse_quote! {
+        struct Http {
+            #[config(default = 500, unit = "ms", example = 250)]
+            timeout: u64,
+            #[config(default = "localhost", example = "api.internal")]
+            host: String,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("example : Some (\"250\") , unit : Some (\"ms\")"), "{}", s);
+    assert!(s.contains("example : Some (\"\\\"api.internal\\\"\") , unit : None"), "{}", s);
+    assert!(s.contains("allowed : < u64 as libelp :: config :: value :: ConfigField > :: __ELP_ALLOWED"), "{}", s);
+    assert!(s.contains("pub fn to_toml_with (& self , options : & libelp :: config :: template :: TemplateOptions)"), "{}", s);
+    assert!(s.contains("let __meta = & Self :: FIELDS [1usize]"), "{}", s);
+}
+
+#[test]
+fn test_required_reaches_fields_and_missing_keys_take_defaults() {
+    let di: DeriveInput = parse_quote! {
+        struct Jwt {
+            #[config(default = "jwt.key", required)]
+            key_file: String,
+            #[config(default = 3600)]
+            ttl: u64,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("secret : false , required : true"), "{}", s);
+    assert!(s.contains("secret : false , required : false"), "{}", s);
+    assert!(s.contains("# [serde (default)] struct __ElpSerdeHelper"), "{}", s);
+    assert!(s.contains("let __defaults = < Jwt > :: __elp_new ()"), "{}", s);
+}
+
+#[test]
+fn test_required_without_default_has_no_plain_constructor() {
+    let di: DeriveInput = parse_quote! {
+        struct Jwt {
+            #[config(required)]
+            key_file: String,
+            #[config(default = 3600)]
+            ttl: u64,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(!s.contains("fn new () -> Self"), "{}", s);
+    assert!(!s.contains("impl Default for Jwt"), "{}", s);
+    assert!(!s.contains("# [serde (default)]"), "{}", s);
+    assert!(s.contains("# [serde (default = \"__elp_default_ttl\")] ttl : u64"), "{}", s);
+    assert!(s.contains("impl libelp :: config :: value :: Placeholder for Jwt"), "{}", s);
+    assert!(s.contains("pub fn __elp_new () -> Self"), "{}", s);
+    assert!(s.contains("libelp :: config :: meta :: check_set :: < Jwt > (& self . set) ?"), "{}", s);
+    assert!(s.contains("\"\" => \"key_file\" . to_string ()"), "{}", s);
+    assert!(s.contains("default : None , example : None , unit : None , allowed : < String as libelp :: config :: value :: ConfigField > :: __ELP_ALLOWED , secret : false , required : true"), "{}", s);
+}
+
+#[test]
+fn test_flatten_and_skip_fields() {
+    let di: DeriveInput = parse_quote! {
+        struct Database {
+            host: String,
+            #[config(flatten)]
+            pool: Pool,
+            /// Built at startup
+            #[config(skip, default_fn = "String::new")]
+            url: String,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("# [serde (flatten)] pool : & '__elp_a Pool"), "{}", s);
+    assert!(s.contains("# [serde (flatten)] pool : Pool"), "{}", s);
+    assert!(s.contains("url : < Database > :: __elp_new () . url"), "{}", s);
+    assert!(s.contains("url : String :: new ()"), "{}", s);
+    assert!(s.contains("pool : < Pool > :: __elp_from_toml_value (__table)"), "{}", s);
+    assert!(s.contains("self . pool . __elp_to_toml_parts (__depth , __prefix , __options)"), "{}", s);
+    assert!(s.contains("< Pool > :: __ELP_CHILD_DEPTH"), "{}", s);
+    assert!(s.contains("flatten : true"), "{}", s);
+    assert!(!s.contains("\"url\"") && !s.contains("stringify ! (url)") && !s.contains("Built at startup"), "{}", s);
+}
+
+#[test]
+fn test_flatten_and_skip_reject_unused_options() {
+    let di: DeriveInput = parse_quote! {
+        struct Database {
+            #[config(flatten, default = 1)]
+            pool: Pool,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("compile_error") && s.contains("Field 'pool' is `flatten`, which does not take `default`"), "{}", s);
+
+    let di: DeriveInput = parse_quote! {
+        struct Database {
+            #[config(skip, secret)]
+            url: String,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("Field 'url' is `skip`, which does not take `secret`"), "{}", s);
+}
+
+#[test]
+fn test_unknown_options_are_compile_errors() {
+    let di: DeriveInput = parse_quote! {
+        struct Database {
+            #[config(defualt = 5432, note = "port")]
+            port: u16,
+            #[config(required = "yes")]
+            host: String,
+        }
+    };
+    let s = handler(di).to_string();
+    assert!(s.contains("compile_error") && s.contains("Unknown field: `defualt`"), "{}", s);
+    assert_eq!(s.matches("compile_error").count(), 2, "{}", s);
+    assert!(!s.contains("impl Database"), "{}", s);
+}
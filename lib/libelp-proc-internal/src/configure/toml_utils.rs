@@ -1,143 +1,34 @@
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{Expr, Field, Lit, Type};
+use syn::{Expr, Field, Type};
+use syn::ext::IdentExt;
 use syn::spanned::Spanned;
 
 use super::{ConfigError, ConfigResult};
 
-/// Convert field value to TOML string representation
-pub fn field_value_to_toml_string(
-    _field: &Field,
-    default_expr: &Option<Expr>,
-    field_ty: &Type,
-) -> ConfigResult<String> {
-    let type_name = get_type_name(field_ty)?;
-
-    if let Some(default_expr) = default_expr {
-        if let Expr::Lit(lit) = default_expr {
-            match &lit.lit {
-                Lit::Str(str_lit) => Ok(format!("\"{}\"", str_lit.value())),
-                Lit::Int(int_lit) => Ok(int_lit.base10_digits().to_string()),
-                Lit::Float(float_lit) => Ok(float_lit.base10_digits().to_string()),
-                Lit::Bool(bool_lit) => Ok(bool_lit.value().to_string()),
-                _ => Err(ConfigError::unsupported_literal_type(
-                    &format!("{:?}", lit.lit),
-                    field_ty.span(),
-                )),
-            }
-        } else if let Expr::Path(path_expr) = default_expr {
-            // When string default value is parsed as path identifier (e.g., localhost)
-            if let Some(segment) = path_expr.path.segments.last() {
-                let ident_text = segment.ident.to_string();
-                match type_name.as_str() {
-                    "String" | "str" => Ok(format!("\"{}\"", ident_text)),
-                    _ => Ok(ident_text),
-                }
-            } else {
-                Ok("default_value".to_string())
-            }
-        } else {
-            // For other non-literal expressions, return placeholder
-            Ok("default_value".to_string())
-        }
-    } else {
-        // No default value, return default TOML representation based on type
-        match type_name.as_str() {
-            "String" => Ok("\"\"".to_string()),
-            "str" => Ok("\"\"".to_string()),
-            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
-            | "u128" | "usize" => Ok("0".to_string()),
-            "f32" | "f64" => Ok("0.0".to_string()),
-            "bool" => Ok("false".to_string()),
-            _ => Ok("null".to_string()),
-        }
-    }
-}
-
-/// Get type name
-fn get_type_name(ty: &Type) -> ConfigResult<String> {
-    match ty {
-        Type::Path(type_path) => {
-            if let Some(segment) = type_path.path.segments.last() {
-                Ok(segment.ident.to_string())
-            } else {
-                Err(ConfigError::cannot_identify_type_path(ty.span()))
-            }
-        }
-        _ => Err(ConfigError::unsupported_type_format(ty.span())),
-    }
-}
-
 /// Generate from_toml method implementation
+///
+/// Missing or unreadable values fall back to the field's default; nested
+/// sections are read field by field the same way, flattened ones from this
+/// table. Only absent `#[config(required)]` keys are an error.
 pub fn generate_from_toml_impl(_struct_name: &syn::Ident, fields: &[&Field]) -> TokenStream {
     let field_assignments: Vec<TokenStream> = fields
         .iter()
         .filter_map(|field| {
             let field_name = field.ident.as_ref()?;
             let field_type = &field.ty;
-            let type_name = get_type_name(field_type).ok()?;
-
-            // Generate different parsing logic based on type
-            let parse_logic = match type_name.as_str() {
-                "String" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_default()
-                },
-                "i32" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_integer())
-                        .and_then(|i| i.try_into().ok())
-                        .unwrap_or_default()
-                },
-                "i64" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_integer())
-                        .unwrap_or_default()
-                },
-                "u16" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_integer())
-                        .and_then(|i| i.try_into().ok())
-                        .unwrap_or_default()
-                },
-                "u32" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_integer())
-                        .and_then(|i| i.try_into().ok())
-                        .unwrap_or_default()
-                },
-                "u64" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_integer())
-                        .and_then(|i| i.try_into().ok())
-                        .unwrap_or_default()
-                },
-                "f64" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_float())
-                        .unwrap_or_default()
-                },
-                "bool" => quote! {
-                    toml_value.get(stringify!(#field_name))
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or_default()
-                },
-                _ => quote! {
-                    // Nested struct (max two levels): recursively parse from sub-table
-                    match toml_value.get(stringify!(#field_name)).and_then(|v| v.as_table()) {
-                        Some(tbl) => {
-                            let sub_str = ::toml::to_string(tbl).unwrap_or_default();
-                            <#field_type>::from_toml(&sub_str).unwrap_or_default()
-                        }
-                        None => Default::default(),
-                    }
-                },
-            };
-
+            if super::skipped(field) {
+                return Some(quote! { #field_name: Self::__elp_new().#field_name, });
+            }
+            if super::flattened(field) {
+                return Some(quote! { #field_name: <#field_type>::__elp_from_toml_value(__table), });
+            }
+            let key = field_name.unraw().to_string();
             Some(quote! {
-                #field_name: #parse_logic,
+                #field_name: __table
+                    .get(#key)
+                    .and_then(<#field_type as libelp::config::value::ConfigField>::__elp_from_toml_lenient)
+                    .unwrap_or_else(|| Self::__elp_new().#field_name),
             })
         })
         .collect();
@@ -145,101 +36,160 @@ pub fn generate_from_toml_impl(_struct_name: &syn::Ident, fields: &[&Field]) ->
     quote! {
         pub fn from_toml(toml_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
             let toml_value: toml::Value = toml::from_str(toml_str)?;
+            libelp::config::meta::check_required::<Self>(&toml_value)?;
+            Ok(Self::__elp_from_toml_value(&toml_value))
+        }
 
-            Ok(Self {
+        pub fn __elp_from_toml_value(__table: &toml::Value) -> Self {
+            Self {
                 #(#field_assignments)*
-            })
+            }
         }
     }
 }
 
 /// Generate to_toml method implementation
+///
+/// `doc` is the struct's doc comment, written as a comment above the document
+/// or, unless the field holding the struct has a note, above its section.
+/// Comments come from `libelp::config::template` using `Self::FIELDS`.
+///
+/// Values are written before sections, and every section gets a `[a.b]`
+/// header with its full path, so the output reads back as the same value.
+/// A flattened struct adds its values and sections to those of this table.
 pub fn generate_to_toml_impl(
     _struct_name: &syn::Ident,
-    _fields: &[&Field],
+    doc: Option<&str>,
     field_configs: &[(Field, Option<Expr>, Option<String>)],
 ) -> ConfigResult<TokenStream> {
     // Generate branch code for each field
     let mut per_field_snippets: Vec<TokenStream> = Vec::new();
 
-    for (field, default_expr, note) in field_configs {
+    for (index, (field, default_expr, _)) in field_configs.iter().enumerate() {
         let field_ident = field
             .ident
             .as_ref()
             .ok_or_else(|| ConfigError::field_must_have_name(field.span()))?;
-        let type_name = get_type_name(&field.ty)?;
-        let note_text = note.as_deref().unwrap_or("");
-        let default_value_lit = field_value_to_toml_string(field, default_expr, &field.ty)?;
+        let field_ty: &Type = &field.ty;
+        // The key serde reads, e.g. `type` for `r#type`
+        let key = field_ident.unraw().to_string();
+
+        if super::flattened(field) {
+            per_field_snippets.push(quote! {
+                let (mut __lines, mut __sections) = self.#field_ident.__elp_to_toml_parts(__depth, __prefix, __options);
+                lines.append(&mut __lines);
+                sections.append(&mut __sections);
+            });
+            continue;
+        }
 
         let default_compare_tokens: TokenStream = if let Some(expr) = default_expr {
-            match super::process_default_value::process_default_value(expr, &field.ty) {
-                Ok(ts) => ts,
-                Err(e) => return Err(e),
-            }
+            super::process_default_value::process_default_value(expr, field_ty, &key)?
         } else {
             // Use explicit type annotation to help with type inference
-            quote! { <#field.ty>::default() }
+            quote! { <#field_ty as libelp::config::value::Placeholder>::__elp_placeholder() }
         };
 
-        let is_primitive = matches!(
-            type_name.as_str(),
-            "String"
-                | "str"
-                | "i8"
-                | "i16"
-                | "i32"
-                | "i64"
-                | "i128"
-                | "isize"
-                | "u8"
-                | "u16"
-                | "u32"
-                | "u64"
-                | "u128"
-                | "usize"
-                | "f32"
-                | "f64"
-                | "bool"
-        );
-
-        let snippet = if is_primitive {
-            quote! {
-                // Comment
-                lines.push(format!("# {}, {}, default: {}", #note_text, #type_name, #default_value_lit));
-                // Value line
-                {
-                    let __is_default = self.#field_ident == #default_compare_tokens;
-                    let __line = {
-                        let mut __m = ::std::collections::BTreeMap::new();
-                        __m.insert(stringify!(#field_ident).to_string(), self.#field_ident.clone());
-                        ::toml::to_string(&__m).unwrap_or_default().trim_end().to_string()
-                    };
-                    if __is_default { lines.push(format!("# {}", __line)); } else { lines.push(__line); }
+        // Nested structs render a section after the values; every other value renders its comment and one line
+        let snippet = quote! {
+            let __meta = &Self::FIELDS[#index];
+            let __path = if __prefix.is_empty() {
+                libelp::config::template::toml_key(#key)
+            } else {
+                format!("{}.{}", __prefix, libelp::config::template::toml_key(#key))
+            };
+            match libelp::config::value::ConfigField::__elp_section(&self.#field_ident, __depth + 1, &__path, __options) {
+                Some(__section) => {
+                    let __note = __meta.note.or(<#field_ty as libelp::config::value::ConfigField>::__ELP_DOC);
+                    let __type = <#field_ty as libelp::config::value::ConfigField>::__elp_type_name();
+                    if let Some(__comment) = libelp::config::template::section_comment(__note, Some(__type), __options) {
+                        sections.push(__comment);
+                    }
+                    sections.push(format!("[{}]", __path));
+                    sections.push(__section);
                 }
-                lines.push(String::new());
-            }
-        } else {
-            // Nested struct: max two levels. Add section name when depth is 0; directly concatenate child content when depth >= 1 (it will self-limit to two levels internally).
-            quote! {
-                if __depth == 0 {
-                    lines.push(format!("[{}]", stringify!(#field_ident)));
+                None => {
+                    let __default: #field_ty = #default_compare_tokens;
+                    let __default_value = libelp::config::value::ConfigField::__elp_to_value(&__default);
+                    lines.push(libelp::config::template::field_comment(
+                        __meta,
+                        <#field_ty as libelp::config::value::ConfigField>::__elp_type_name(),
+                        &__path,
+                        &__default_value,
+                        __options,
+                    ));
+                    // Value line
+                    let __value = libelp::config::value::ConfigField::__elp_to_value(&self.#field_ident);
+                    let __line = format!(
+                        "{} = {}",
+                        libelp::config::template::toml_key(#key),
+                        libelp::config::value::inline(&__value)
+                    );
+                    // Required keys stay in, or the file would not load
+                    if __value == __default_value && !__meta.required {
+                        lines.push(format!("# {}", __line));
+                    } else {
+                        lines.push(__line);
+                    }
+                    lines.push(String::new());
                 }
-                lines.push(self.#field_ident.__elp_to_toml_depth(__depth + 1));
-                lines.push(String::new());
             }
         };
 
         per_field_snippets.push(snippet);
     }
 
+    // Only the outermost struct writes its own doc; sections get theirs from the parent
+    let header = doc.map(|doc| {
+        quote! {
+            if __depth == 0 {
+                if let Some(__comment) = libelp::config::template::section_comment(Some(#doc), None, __options) {
+                    lines.push(__comment);
+                    lines.push(String::new());
+                }
+            }
+        }
+    });
+
     let expanded = quote! {
-        pub fn to_toml(&self) -> String { self.__elp_to_toml_depth(0) }
+        pub fn to_toml(&self) -> String {
+            self.to_toml_with(&libelp::config::template::TemplateOptions::new())
+        }
+
+        /// Like `to_toml()`, with comments chosen by `options`
+        pub fn to_toml_with(&self, options: &libelp::config::template::TemplateOptions) -> String {
+            self.__elp_to_toml_depth(0, "", options)
+        }
 
-        pub fn __elp_to_toml_depth(&self, __depth: usize) -> String {
+        pub fn __elp_to_toml_depth(
+            &self,
+            __depth: usize,
+            __prefix: &str,
+            __options: &libelp::config::template::TemplateOptions,
+        ) -> String {
             let mut lines: ::std::vec::Vec<::std::string::String> = Vec::new();
-            #(#per_field_snippets)*
+            #header
+            let (mut __lines, mut __sections) = self.__elp_to_toml_parts(__depth, __prefix, __options);
+            lines.append(&mut __lines);
+            lines.append(&mut __sections);
             lines.join("\n")
         }
+
+        // Value lines and sections apart, so a flattening parent can merge them with its own
+        #[doc(hidden)]
+        pub fn __elp_to_toml_parts(
+            &self,
+            __depth: usize,
+            __prefix: &str,
+            __options: &libelp::config::template::TemplateOptions,
+        ) -> (::std::vec::Vec<::std::string::String>, ::std::vec::Vec<::std::string::String>) {
+            let mut lines: ::std::vec::Vec<::std::string::String> = Vec::new();
+            // Tables come last: a key after a `[header]` would belong to it
+            let mut sections: ::std::vec::Vec<::std::string::String> = Vec::new();
+            let _ = (__prefix, __options);
+            #(#per_field_snippets)*
+            (lines, sections)
+        }
     };
 
     Ok(expanded)
@@ -0,0 +1,111 @@
+//! Compile-time parsing of human duration and byte-size defaults
+//!
+//! Mirrors `libelp::config::units` so `#[config(default = "5m")]` is rejected
+//! by the compiler when the runtime parser would reject it.
+
+const DURATION_UNITS: &[(&str, u128)] = &[
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+const BYTE_UNITS: &[(&str, u64)] = &[
+    ("b", 1),
+    ("kb", 1_000),
+    ("mb", 1_000_000),
+    ("gb", 1_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+];
+
+/// Parse a duration such as `1h30m` into `(seconds, nanoseconds)`
+pub fn parse_duration(text: &str) -> Result<(u64, u32), String> {
+    let text = text.trim();
+    if text == "0" {
+        return Ok((0, 0));
+    }
+    if text.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    let mut total: u128 = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("expected a number at `{}`", rest));
+        }
+        let number: u128 = rest[..digits].parse().map_err(|e| format!("{}", e))?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest.find(|c: char| c.is_ascii_digit() || c.is_whitespace()).unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        let scale = DURATION_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, scale)| *scale)
+            .ok_or_else(|| {
+                if unit.is_empty() {
+                    format!("missing unit after `{}` (use d, h, m, s, ms, us or ns)", number)
+                } else {
+                    format!("unknown unit `{}` (use d, h, m, s, ms, us or ns)", unit)
+                }
+            })?;
+        total = number
+            .checked_mul(scale)
+            .and_then(|n| total.checked_add(n))
+            .ok_or_else(|| "duration is too large".to_string())?;
+        rest = rest[unit_len..].trim_start();
+    }
+    let secs = u64::try_from(total / 1_000_000_000).map_err(|_| "duration is too large".to_string())?;
+    Ok((secs, (total % 1_000_000_000) as u32))
+}
+
+/// Parse a byte size such as `10MiB` into a number of bytes
+pub fn parse_byte_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    if digits == 0 {
+        return Err(format!("expected a number at `{}`", text));
+    }
+    let number: u64 = text[..digits].parse().map_err(|e| format!("{}", e))?;
+    let unit = text[digits..].trim();
+    let scale = if unit.is_empty() {
+        1
+    } else {
+        BYTE_UNITS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+            .map(|(_, scale)| *scale)
+            .ok_or_else(|| format!("unknown unit `{}` (use B, KB, MB, GB, TB, KiB, MiB, GiB or TiB)", unit))?
+    };
+    number.checked_mul(scale).ok_or_else(|| "size is too large".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5m"), Ok((300, 0)));
+        assert_eq!(parse_duration("1s 250ms"), Ok((1, 250_000_000)));
+        assert_eq!(parse_duration("0"), Ok((0, 0)));
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("5 minutes").is_err());
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("10MiB"), Ok(10 << 20));
+        assert_eq!(parse_byte_size("2 kb"), Ok(2000));
+        assert_eq!(parse_byte_size("7"), Ok(7));
+        assert!(parse_byte_size("MiB").is_err());
+        assert!(parse_byte_size("1 zb").is_err());
+    }
+}
@@ -7,7 +7,12 @@ use syn::parse_macro_input;
 /// This macro generates configuration handling code for structs, including:
 /// - `new()` method with default values
 /// - `from_toml()` method for parsing TOML configuration
-/// - `to_toml()` method for generating TOML configuration
+/// - `to_toml()` method for generating TOML configuration, and `to_toml_with()`
+///   taking `libelp::TemplateOptions` for full comment blocks
+/// - `builder()` returning a `<Name>Builder` with a setter per field; nested
+///   sections take a closure (`.database(|d| d.host("db"))`) and `build()`
+///   runs `validate()`
+/// - `FIELDS` describing every field and `validate()` checking `min`/`max`
 /// - Compile-time validation of configuration structure
 /// 
 /// # Example
@@ -15,9 +20,11 @@ use syn::parse_macro_input;
 /// ```rust,ignore
 /// use libelp::Configuration;
 /// 
+/// /// Application settings
 /// #[derive(Configuration)]
 /// struct AppConfig {
-///     #[config(default = "localhost", note = "Server hostname")]
+///     /// Server hostname
+///     #[config(default = "localhost")]
 ///     host: String,
 ///     #[config(default = 8080, note = "Server port")]
 ///     port: u16,
@@ -25,12 +32,57 @@ use syn::parse_macro_input;
 ///     debug: bool,
 /// }
 /// ```
-/// 
+///
+/// # Field attributes
+///
+/// - `default = <expr>` - a literal, a constant such as `limits::MAX_CONNECTIONS`
+///   or a const expression such as `60 * 60`; bare words are strings for
+///   `String` fields (`default = localhost`, `default = INFO`), so a `String`
+///   constant is named by its path (`default = self::DEFAULT_HOST`)
+/// - `default_fn = "path"` - function called for the default, e.g. `"num_cpus::get"`
+/// - `note = "..."` - description written above the field in templates;
+///   without it the field's `///` doc comment is used, line by line
+/// - `example = <expr>` - sample value shown in full templates, written like a default
+/// - `unit = "..."` - unit of a plain number, e.g. `unit = "ms"`
+/// - `min = <expr>`, `max = <expr>` - bounds checked by `validate()`, written
+///   like defaults (`min = 1`, `max = "1h"`)
+/// - `deprecated = "..."` - reason shown to tooling through `FIELDS`
+/// - `required` - loading a file that leaves the key out fails with a
+///   validation error instead of using the default. Without a default the
+///   struct gets no `new()` and no `Default`: build it with `builder()`,
+///   whose `build()` fails until the field's setter is called (even with an
+///   empty value), and templates write the key uncommented, marked
+///   `REQUIRED`. A field holding such a struct must be `required` as well
+/// - `secret` - the field holds a password, token or key; loading warns about
+///   files other users can read and saving creates new files with mode `0600`
+/// - `flatten` - a nested configuration struct whose keys sit in the parent's
+///   table instead of a section of their own, in files, templates, env
+///   variables and key paths; it takes no other option
+/// - `skip` - a runtime-only field, e.g. a computed connection string: never
+///   read, written or listed, and set from `default_fn` or `Default`; its type
+///   need not implement `libelp::ConfigValue`
+///
+/// The struct's own doc comment heads the output of `to_toml()`, or the
+/// section when the struct is nested in another configuration; a note on the
+/// field holding the section takes precedence.
+///
+/// Computed defaults are evaluated by `new()`, and `to_toml()` shows the
+/// resulting value. Keys missing from a file take their default, so the
+/// output of `to_toml()`, with its defaults commented out, reads back as the
+/// same value. Field types implement `libelp::ConfigValue` or derive
+/// `Configuration`, except those of `skip` fields.
+///
+/// Literal defaults of fields not written as a primitive type, such as
+/// `Duration`, `type Port = u16` or a user-defined value, are checked at
+/// compile time against the type's `ConfigValue::DEFAULT_LITERALS` and
+/// `ConfigValue::DEFAULT_RANGE`.
+///
 /// # Errors
 /// 
 /// This macro will generate compilation errors for:
 /// - Non-struct types (only structs are supported)
-/// - Invalid default value types
+/// - Invalid default value types, or defaults the field type does not accept
+/// - `flatten` or `skip` fields with options they do not use
 /// - Configuration nesting exceeding 2 levels
 /// - Other configuration validation errors
 #[proc_macro_derive(Configuration, attributes(config))]
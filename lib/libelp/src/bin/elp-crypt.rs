@@ -0,0 +1,92 @@
+//! Manage encrypted configuration values
+//!
+//! ```text
+//! elp-crypt keygen <key-file>
+//! elp-crypt encrypt <key-file> [value]
+//! elp-crypt decrypt <key-file> <enc:v1:...>
+//! elp-crypt encrypt-field <key-file> <config.toml> <key.path>...
+//! elp-crypt rotate <key-file> <config.toml>...
+//! ```
+//!
+//! `encrypt` reads the value from stdin when it is not given, so secrets need
+//! not appear in shell history.
+
+use libelp::config::encryption::{encrypt_field, rotate_key};
+use libelp::config::persist::write_atomic;
+use libelp::{ConfigurationError, ConfigurationResult, EncryptionKey, RenderStyle};
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage:
+  elp-crypt keygen <key-file>
+  elp-crypt encrypt <key-file> [value]
+  elp-crypt decrypt <key-file> <enc:v1:...>
+  elp-crypt encrypt-field <key-file> <config.toml> <key.path>...
+  elp-crypt rotate <key-file> <config.toml>...";
+
+fn run(args: &[String]) -> ConfigurationResult<Option<String>> {
+    let usage = || ConfigurationError::validation_error(USAGE, None);
+    let (command, key_file, rest) = match args {
+        [command, key_file, rest @ ..] => (command.as_str(), key_file, rest),
+        _ => return Err(usage()),
+    };
+
+    match (command, rest) {
+        ("keygen", []) => {
+            if fs::metadata(key_file).is_ok() {
+                return Err(ConfigurationError::save_error("Key file already exists; use `rotate` to replace it", key_file));
+            }
+            EncryptionKey::generate().save(key_file)?;
+            Ok(None)
+        }
+        ("encrypt", [] | [_]) => {
+            let key = EncryptionKey::load(key_file)?;
+            let value = match rest.first() {
+                Some(value) => value.clone(),
+                None => {
+                    let mut value = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut value)
+                        .map_err(|e| ConfigurationError::load_error(e.to_string(), "stdin"))?;
+                    value.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            Ok(Some(key.encrypt(&value)))
+        }
+        ("decrypt", [value]) => Ok(Some(EncryptionKey::load(key_file)?.decrypt(value)?)),
+        ("encrypt-field", [config, paths @ ..]) if !paths.is_empty() => {
+            let key = EncryptionKey::load(key_file)?;
+            let mut source = fs::read_to_string(config)
+                .map_err(|e| ConfigurationError::file_error(format!("Failed to read file: {}", e), config))?;
+            for path in paths {
+                source = encrypt_field(&source, path, &key)?;
+            }
+            write_atomic(config, source.as_bytes())?;
+            Ok(None)
+        }
+        ("rotate", files) if !files.is_empty() => {
+            let files: Vec<PathBuf> = files.iter().map(PathBuf::from).collect();
+            let count = rotate_key(key_file, &files)?;
+            Ok(Some(format!("re-encrypted {} value(s) in {} file(s)", count, files.len())))
+        }
+        _ => Err(usage()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => {
+            if let Some(output) = output {
+                println!("{}", output);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprint!("{}", e.render(RenderStyle::detect()));
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::config::value::ConfigValue;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+/// How a builder setter takes the new value of a field of type `Self`
+///
+/// Values are set as they are, and `String` and `PathBuf` fields also take
+/// string slices. Structs deriving `Configuration` are nested sections, set
+/// through their own builder: `Config::builder().database(|d| d.host("db"))`.
+///
+/// Setters require `Field: Assign<V>`, so a field type with a single impl
+/// decides what is passed: integer literals take the type of the field and
+/// closures receive the section's builder.
+pub trait Assign<V> {
+    /// Set the field, returning the key paths set below it
+    ///
+    /// An empty path stands for the field itself.
+    #[doc(hidden)]
+    fn __elp_assign(&mut self, value: V) -> Vec<String>;
+}
+
+impl<T: ConfigValue> Assign<T> for T {
+    fn __elp_assign(&mut self, value: T) -> Vec<String> {
+        *self = value;
+        vec![String::new()]
+    }
+}
+
+impl Assign<&str> for String {
+    fn __elp_assign(&mut self, value: &str) -> Vec<String> {
+        self.__elp_assign(value.to_string())
+    }
+}
+
+impl Assign<&String> for String {
+    fn __elp_assign(&mut self, value: &String) -> Vec<String> {
+        self.__elp_assign(value.clone())
+    }
+}
+
+impl<S: AsRef<OsStr> + ?Sized> Assign<&S> for PathBuf {
+    fn __elp_assign(&mut self, value: &S) -> Vec<String> {
+        self.__elp_assign(PathBuf::from(value))
+    }
+}
+
+impl Assign<String> for PathBuf {
+    fn __elp_assign(&mut self, value: String) -> Vec<String> {
+        self.__elp_assign(PathBuf::from(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConfigValue;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use toml::Value;
+
+    type Port = u16;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Weight(u8);
+
+    impl ConfigValue for Weight {
+        fn type_name() -> &'static str {
+            "Weight"
+        }
+
+        fn from_toml_value(value: &Value) -> Result<Self, String> {
+            let weight = value.as_integer().ok_or("expected a weight")?;
+            u8::try_from(weight).map(Weight).map_err(|e| e.to_string())
+        }
+
+        fn to_toml_value(&self) -> Value {
+            Value::Integer(i64::from(self.0))
+        }
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Database {
+        #[config(default = "localhost", note = "db host")]
+        host: String,
+        #[config(default = 5432)]
+        port: u16,
+        #[config(default = "5s")]
+        timeout: Duration,
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Config {
+        #[config(default = false)]
+        debug: bool,
+        #[config(default = "data")]
+        data_dir: PathBuf,
+        database: Database,
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Upstream {
+        #[config(default = 8080)]
+        port: Port,
+        #[config(default = 1)]
+        weight: Weight,
+    }
+
+    #[test]
+    fn test_builder_sets_fields_and_nested_sections() {
+        let config = Config::builder()
+            .debug(true)
+            .data_dir("/var/lib/eauth")
+            .database(|d| d.host("db").port(6432))
+            .database(|d| d.timeout(Duration::from_secs(1)))
+            .build()
+            .unwrap();
+        assert!(config.debug);
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/eauth"));
+        assert_eq!(config.database.host, "db");
+        assert_eq!(config.database.port, 6432);
+        assert_eq!(config.database.timeout, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_builder_starts_from_defaults() {
+        let config = Config::builder().with(|c| c.database.port = 1).build().unwrap();
+        assert_eq!(config.to_toml(), Config { database: Database { port: 1, ..Database::new() }, ..Config::new() }.to_toml());
+        assert_eq!(Database::builder().build().unwrap().host, "localhost");
+    }
+
+    #[test]
+    fn test_builder_sets_aliases_and_custom_values() {
+        let upstream = Upstream::builder().port(9090).weight(Weight(5)).build().unwrap();
+        assert_eq!(upstream.port, 9090);
+        assert_eq!(upstream.weight, Weight(5));
+        assert_eq!(Upstream::new().port, 8080);
+        assert_eq!(Upstream::new().weight, Weight(1));
+    }
+}
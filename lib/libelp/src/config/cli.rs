@@ -0,0 +1,664 @@
+//! `config` subcommands that any libelp-based binary can embed
+//!
+//! ```text
+//! <program> init [FILE] [--force]     write the commented template
+//! <program> check FILE...             load and validate, every profile included
+//! <program> diff A [B]                changed values between two files, or A and the defaults
+//! <program> explain [KEY]             notes, types, defaults and bounds of the fields
+//! <program> migrate FILE [--dry-run]  rewrite a file for the current fields
+//! <program> schema                    JSON Schema of the configuration
+//! ```
+//!
+//! ```rust,ignore
+//! let args: Vec<String> = std::env::args().skip(2).collect();
+//! std::process::exit(libelp::config::cli::run::<Config>("eauth config", &args).into());
+//! ```
+
+use crate::Configuration;
+use crate::config::format::Format;
+use crate::config::loader::{ConfigLoader, INCLUDE_KEY, PROFILE_KEY};
+use crate::config::meta::{self, Constraint, FieldMeta};
+use crate::config::path::{self, lookup};
+use crate::config::permissions::PermissionPolicy;
+use crate::config::persist::{SaveOptions, backup_path};
+use crate::config::render::RenderStyle;
+use crate::config::secret::from_file_reference;
+use crate::config::template;
+use crate::error::{ConfigurationError, ConfigurationResult, ErrorCategory};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+/// Exit code of a command line that could not be parsed (`EX_USAGE`)
+pub const EXIT_USAGE: u8 = 64;
+
+/// Exit code of `diff` when the configurations differ, as with diff(1)
+pub const EXIT_DIFFERENT: u8 = 1;
+
+/// Exit code for an error, following the BSD `sysexits.h` codes
+///
+/// | Category | Code |
+/// |---|---|
+/// | [`ErrorCategory::Parse`] | 65 (`EX_DATAERR`) |
+/// | [`ErrorCategory::Serialize`] | 70 (`EX_SOFTWARE`) |
+/// | [`ErrorCategory::Io`] | 74 (`EX_IOERR`) |
+/// | [`ErrorCategory::Validation`] | 78 (`EX_CONFIG`) |
+pub fn exit_code(error: &ConfigurationError) -> u8 {
+    match error.category() {
+        ErrorCategory::Parse => 65,
+        ErrorCategory::Serialize => 70,
+        ErrorCategory::Io => 74,
+        ErrorCategory::Validation => 78,
+    }
+}
+
+/// A parsed `config` subcommand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Write the commented template to a file, or print it
+    Init { file: Option<String>, force: bool },
+    /// Load and validate files; files feeding secret fields must be private
+    Check { files: Vec<String> },
+    /// Compare two files, or a file with the defaults
+    Diff { a: String, b: Option<String> },
+    /// Describe all fields, or those under a key
+    Explain { key: Option<String> },
+    /// Bring a file up to date with the current fields
+    ///
+    /// `include` and profiles are kept as written; values holding `${...}`
+    /// or `from_file` references are refused.
+    Migrate { file: String, dry_run: bool },
+    /// Print a JSON Schema
+    Schema,
+}
+
+/// What a command printed and the exit code it asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Text for stdout
+    pub text: String,
+    /// `0`, or [`EXIT_DIFFERENT`] for `diff`
+    pub exit_code: u8,
+}
+
+impl Report {
+    fn ok(text: String) -> Self {
+        Self { text, exit_code: 0 }
+    }
+}
+
+impl Command {
+    /// Parse the arguments following the `config` word
+    ///
+    /// The error is the usage text.
+    pub fn parse(program: &str, args: &[String]) -> Result<Self, String> {
+        let (flags, words): (Vec<&str>, Vec<&str>) =
+            args.iter().map(String::as_str).partition(|arg| arg.starts_with("--"));
+        let flag = |name: &str| flags.contains(&name);
+        let allowed: &[&str] = match words.first() {
+            Some(&"init") => &["--force"],
+            Some(&"migrate") => &["--dry-run"],
+            _ => &[],
+        };
+        let usage = || usage(program);
+        if flags.iter().any(|f| !allowed.contains(f)) {
+            return Err(usage());
+        }
+        let owned = |word: &&str| word.to_string();
+        match words.as_slice() {
+            ["init", file @ ..] if file.len() <= 1 => Ok(Command::Init { file: file.first().map(owned), force: flag("--force") }),
+            ["check", files @ ..] if !files.is_empty() => Ok(Command::Check { files: files.iter().map(owned).collect() }),
+            ["diff", a] => Ok(Command::Diff { a: a.to_string(), b: None }),
+            ["diff", a, b] => Ok(Command::Diff { a: a.to_string(), b: Some(b.to_string()) }),
+            ["explain", key @ ..] if key.len() <= 1 => Ok(Command::Explain { key: key.first().map(owned) }),
+            ["migrate", file] => Ok(Command::Migrate { file: file.to_string(), dry_run: flag("--dry-run") }),
+            ["schema"] => Ok(Command::Schema),
+            _ => Err(usage()),
+        }
+    }
+
+    /// Run the command for configurations of type `T`
+    pub fn execute<T: Configuration>(&self) -> ConfigurationResult<Report> {
+        match self {
+            Command::Init { file, force } => init::<T>(file.as_deref(), *force),
+            Command::Check { files } => check::<T>(files),
+            Command::Diff { a, b } => diff::<T>(a, b.as_deref()),
+            Command::Explain { key } => explain::<T>(key.as_deref()).map(Report::ok),
+            Command::Migrate { file, dry_run } => migrate::<T>(file, *dry_run),
+            Command::Schema => Ok(Report::ok(schema::<T>())),
+        }
+    }
+}
+
+/// Parse `args`, run the command, print its output and return the exit code
+///
+/// Errors are rendered to stderr; see [`exit_code`] for the codes.
+pub fn run<T: Configuration>(program: &str, args: &[String]) -> u8 {
+    let command = match Command::parse(program, args) {
+        Ok(command) => command,
+        Err(usage) => {
+            eprintln!("{}", usage);
+            return EXIT_USAGE;
+        }
+    };
+    match command.execute::<T>() {
+        Ok(report) => {
+            print!("{}", report.text);
+            report.exit_code
+        }
+        Err(e) => {
+            eprint!("{}", e.render(RenderStyle::detect()));
+            exit_code(&e)
+        }
+    }
+}
+
+fn usage(program: &str) -> String {
+    let mut text = String::from("usage:");
+    for line in [
+        "init [FILE] [--force]",
+        "check FILE...",
+        "diff A [B]",
+        "explain [KEY]",
+        "migrate FILE [--dry-run]",
+        "schema",
+    ] {
+        let _ = write!(text, "\n  {} {}", program, line);
+    }
+    text
+}
+
+fn format_of(file: &str) -> Format {
+    Format::from_path(file).unwrap_or(Format::Toml)
+}
+
+fn init<T: Configuration>(file: Option<&str>, force: bool) -> ConfigurationResult<Report> {
+    let Some(file) = file else {
+        return Ok(Report::ok(T::__elp_new().to_template(Format::Toml)?));
+    };
+    if !force && Path::new(file).exists() {
+        return Err(ConfigurationError::save_error("File already exists; pass --force to replace it", file));
+    }
+    let template = T::__elp_new().to_template(format_of(file))?;
+    SaveOptions::for_config::<T>().write(file, template.as_bytes())?;
+    Ok(Report::ok(format!("wrote {}\n", file)))
+}
+
+// Nothing would show a permission warning, so exposed secret files are errors
+fn loader(file: &str) -> ConfigLoader {
+    ConfigLoader::new().file(file).permissions(PermissionPolicy::Deny)
+}
+
+fn check<T: Configuration>(files: &[String]) -> ConfigurationResult<Report> {
+    let mut text = String::new();
+    for file in files {
+        let loader = loader(file);
+        loader.load::<T>()?.validate()?;
+        let profiles = loader.check_profiles::<T>()?;
+        let count = profiles.len();
+        for (_, result) in profiles {
+            result?;
+        }
+        let _ = match count {
+            0 => writeln!(text, "{}: ok", file),
+            n => writeln!(text, "{}: ok ({} profiles)", file, n),
+        };
+    }
+    Ok(Report::ok(text))
+}
+
+fn diff<T: Configuration>(a: &str, b: Option<&str>) -> ConfigurationResult<Report> {
+    let left = to_value(&loader(a).load::<T>()?)?;
+    let right = match b {
+        Some(b) => to_value(&loader(b).load::<T>()?)?,
+        None => to_value(&T::__elp_new())?,
+    };
+    // Values of secret fields, and of anything below them, are never printed
+    let secrets: Vec<String> = T::metadata().into_iter().filter(|f| f.secret).map(|f| f.path.into_owned()).collect();
+    let is_secret = |key: &str| {
+        secrets.iter().any(|s| key == s || key.strip_prefix(s.as_str()).is_some_and(|rest| rest.starts_with('.')))
+    };
+    let mut text = String::new();
+    let mut keys = path::paths(&left);
+    keys.extend(path::paths(&right).into_iter().filter(|key| lookup(&left, key).is_none()));
+    for key in keys {
+        let (l, r) = (lookup(&left, &key), lookup(&right, &key));
+        if l.is_some_and(Value::is_table) || r.is_some_and(Value::is_table) || l == r {
+            continue;
+        }
+        if is_secret(&key) {
+            let _ = writeln!(text, "{}: (secret changed)", key);
+            continue;
+        }
+        let show = |value: Option<&Value>| value.map(Value::to_string).unwrap_or_else(|| "(unset)".to_string());
+        let _ = writeln!(text, "{}: {} -> {}", key, show(l), show(r));
+    }
+    let exit_code = if text.is_empty() { 0 } else { EXIT_DIFFERENT };
+    Ok(Report { text, exit_code })
+}
+
+fn explain<T: Configuration>(key: Option<&str>) -> ConfigurationResult<String> {
+    let fields = T::metadata();
+    let selected: Vec<&FieldMeta> = match key {
+        None => fields.iter().collect(),
+        Some(key) => {
+            let prefix = format!("{}.", key);
+            let selected: Vec<&FieldMeta> =
+                fields.iter().filter(|f| f.path == key || f.path.starts_with(&prefix)).collect();
+            if selected.is_empty() {
+                return Err(path::unknown_key(key, fields.iter().map(|f| &f.path)));
+            }
+            selected
+        }
+    };
+    // Computed defaults show their value rather than the expression
+    let defaults = to_value(&T::__elp_new())?;
+    let mut text = String::new();
+    for field in selected {
+        let _ = writeln!(text, "{} ({})", field.path, field.type_name);
+        for line in field.note.iter().flat_map(|note| note.lines()) {
+            let _ = writeln!(text, "{}", format!("  {}", line).trim_end());
+        }
+        if let Some(unit) = field.unit {
+            let _ = writeln!(text, "  unit: {}", unit);
+        }
+        if let Some(default) = lookup(&defaults, &field.path).filter(|v| !v.is_table() && !field.is_mandatory()) {
+            let _ = writeln!(text, "  default: {}", default);
+        }
+        if let Some(example) = field.example {
+            let _ = writeln!(text, "  example: {}", example);
+        }
+        if let Some(allowed) = field.allowed {
+            let _ = writeln!(text, "  one of: {}", allowed.join(", "));
+        }
+        for constraint in field.constraints {
+            let _ = writeln!(text, "  must be {}", constraint);
+        }
+        if field.required {
+            let _ = writeln!(text, "  required: yes");
+        }
+        if field.secret {
+            let _ = writeln!(text, "  secret: keep the file private");
+        }
+        if let Some(reason) = field.deprecated {
+            let _ = writeln!(text, "  deprecated: {}", reason);
+        }
+    }
+    Ok(text)
+}
+
+fn migrate<T: Configuration>(file: &str, dry_run: bool) -> ConfigurationResult<Report> {
+    let format = format_of(file);
+    let source = fs::read_to_string(file)
+        .map_err(|e| ConfigurationError::file_error(format!("Failed to read file: {}", e), file))?;
+    let old = Value::Table(format.parse::<toml::Table>(&source).map_err(|e| e.with_file(file))?);
+
+    // Start from the defaults and keep every value the file sets for a known key
+    // A required key without a default has only a placeholder to fall back to
+    meta::check_required::<T>(&old)?;
+    let defaults = to_value(&T::__elp_new())?;
+    let mut merged = defaults.clone();
+    let known = path::paths(&merged);
+    // Includes and profiles are no fields; they are written back as they are
+    let kept: Vec<&str> =
+        [INCLUDE_KEY, PROFILE_KEY].into_iter().filter(|key| lookup(&old, key).is_some() && lookup(&merged, key).is_none()).collect();
+    let is_kept = |key: &str| kept.iter().any(|k| key == *k || key.strip_prefix(k).is_some_and(|rest| rest.starts_with('.')));
+    let mut text = String::new();
+    for key in path::paths(&old).into_iter().filter(|key| !is_kept(key)) {
+        let value = lookup(&old, &key).expect("path taken from the table");
+        // Writing the merged values back would resolve or drop these
+        if let Some(what) = unresolved(value) {
+            return Err(ConfigurationError::validation_error(
+                format!("Cannot migrate a value that holds {}; resolve it by hand first", what),
+                Some(key),
+            ));
+        }
+        match path::lookup_mut(&mut merged, &key) {
+            Some(slot) if !slot.is_table() => *slot = value.clone(),
+            Some(_) => {}
+            None if value.is_table() => {}
+            None => {
+                let _ = writeln!(text, "removed unknown key {}", key);
+            }
+        }
+    }
+    for key in known.iter().filter(|key| lookup(&old, key).is_none()) {
+        if let Some(value) = lookup(&merged, key).filter(|v| !v.is_table()) {
+            let _ = writeln!(text, "added {} = {}", key, value);
+        }
+    }
+    for field in T::metadata().iter().filter(|f| f.deprecated.is_some() && lookup(&old, &f.path).is_some()) {
+        let _ = writeln!(text, "deprecated {}: {}", field.path, field.deprecated.unwrap_or_default());
+    }
+
+    let config = T::deserialize(merged).map_err(|e| ConfigurationError::validation_error(e.to_string(), None))?;
+    config.validate()?;
+    let mut values = template::to_table(format, &config)?;
+    for key in kept {
+        values.insert(key.to_string(), lookup(&old, key).expect("kept keys are in the file").clone());
+    }
+    let defaults = template::to_table(format, &defaults)?;
+    let rendered = template::render(format, &values, Some(&defaults), &T::field_note, &T::metadata())?;
+    if dry_run {
+        return Ok(Report::ok(rendered));
+    }
+    SaveOptions::for_config::<T>().backups(1).write(file, rendered.as_bytes())?;
+    let _ = writeln!(text, "wrote {} (previous version kept as {})", file, backup_path(file, 1).display());
+    Ok(Report::ok(text))
+}
+
+// What keeps `value` from being read as it is: a `${...}` reference or a
+// `{ from_file = "..." }` table
+fn unresolved(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::String(s) if s.replace("$${", "").contains("${") => Some("a `${...}` reference"),
+        Value::Array(items) => items.iter().find_map(unresolved),
+        _ if from_file_reference(value).is_some() => Some("a `from_file` reference"),
+        _ => None,
+    }
+}
+
+fn schema<T: Configuration>() -> String {
+    let defaults = to_value(&T::__elp_new()).unwrap_or(Value::Table(Default::default()));
+    let fields = T::metadata();
+    let top: Vec<&FieldMeta> = fields.iter().filter(|f| !f.path.contains('.')).collect();
+    let mut out = String::new();
+    write_object(&mut out, &fields, &top, &defaults, 0, true);
+    out.push('\n');
+    out
+}
+
+// Objects of the schema; `fields` is the flattened metadata, `members` the fields of this object
+fn write_object(out: &mut String, fields: &[FieldMeta], members: &[&FieldMeta], defaults: &Value, depth: usize, root: bool) {
+    let pad = "  ".repeat(depth);
+    out.push_str("{\n");
+    if root {
+        let _ = writeln!(out, "{}  \"$schema\": \"https://json-schema.org/draft/2020-12/schema\",", pad);
+    }
+    let _ = writeln!(out, "{}  \"type\": \"object\",", pad);
+    let _ = write!(out, "{}  \"properties\": {{", pad);
+    for (i, field) in members.iter().enumerate() {
+        let _ = write!(out, "{}\n{}    {}: ", if i == 0 { "" } else { "," }, pad, json_string(field.name));
+        let default = lookup(defaults, &field.path);
+        if default.is_some_and(Value::is_table) {
+            let prefix = format!("{}.", field.path);
+            let children: Vec<&FieldMeta> = fields
+                .iter()
+                .filter(|f| f.path.strip_prefix(&prefix).is_some_and(|rest| !rest.contains('.')))
+                .collect();
+            write_object(out, fields, &children, defaults, depth + 2, false);
+        } else {
+            write_property(out, field, default, depth + 2);
+        }
+    }
+    let _ = write!(out, "\n{}  }},", pad);
+    let required: Vec<String> = members.iter().filter(|f| f.required).map(|f| json_string(f.name)).collect();
+    if !required.is_empty() {
+        let _ = write!(out, "\n{}  \"required\": [{}],", pad, required.join(", "));
+    }
+    let _ = write!(out, "\n{}  \"additionalProperties\": false\n{}}}", pad, pad);
+}
+
+fn write_property(out: &mut String, field: &FieldMeta, default: Option<&Value>, depth: usize) {
+    let mut members: Vec<(&str, String)> = Vec::new();
+    let kind = match default {
+        Some(Value::Integer(_)) => "integer",
+        Some(Value::Float(_)) => "number",
+        Some(Value::Boolean(_)) => "boolean",
+        Some(Value::Array(_)) => "array",
+        _ => "string",
+    };
+    members.push(("type", json_string(kind)));
+    if let Some(note) = field.note {
+        members.push(("description", json_string(note)));
+    }
+    if let Some(default) = default.filter(|_| !field.is_mandatory()) {
+        members.push(("default", json_value(default)));
+    }
+    for constraint in field.constraints {
+        let (name, bound) = match constraint {
+            Constraint::Min(bound) => ("minimum", bound),
+            Constraint::Max(bound) => ("maximum", bound),
+        };
+        // Bounds given as constants or units are described by `explain` only
+        if kind != "string" && bound.parse::<f64>().is_ok() {
+            members.push((name, bound.to_string()));
+        }
+    }
+    if field.secret {
+        members.push(("writeOnly", "true".to_string()));
+    }
+    if field.deprecated.is_some() {
+        members.push(("deprecated", "true".to_string()));
+    }
+    let pad = "  ".repeat(depth);
+    out.push_str("{\n");
+    for (i, (name, value)) in members.iter().enumerate() {
+        let comma = if i + 1 < members.len() { "," } else { "" };
+        let _ = writeln!(out, "{}  \"{}\": {}{}", pad, name, value, comma);
+    }
+    let _ = write!(out, "{}}}", pad);
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => json_string(s),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) if f.is_finite() => f.to_string(),
+        Value::Float(_) => "null".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Datetime(d) => json_string(&d.to_string()),
+        Value::Array(items) => format!("[{}]", items.iter().map(json_value).collect::<Vec<_>>().join(", ")),
+        Value::Table(table) => format!(
+            "{{{}}}",
+            table.iter().map(|(k, v)| format!("{}: {}", json_string(k), json_value(v))).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn to_value<T: Configuration>(config: &T) -> ConfigurationResult<Value> {
+    Value::try_from(config).map_err(|e| ConfigurationError::toml_serialize_error(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_util::temp_dir;
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Database {
+        #[config(default = "localhost", note = "db host")]
+        host: String,
+        #[config(default = 5432, min = 1, note = "db port")]
+        port: u16,
+        #[config(default = "", secret)]
+        password: String,
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Config {
+        #[config(default = false, note = "enable debug output", deprecated = "use `log.level`")]
+        debug: bool,
+        database: Database,
+    }
+
+    // Private like the files `init` writes, so loading does not warn
+    fn write(path: &str, content: &str) {
+        SaveOptions::new().mode(0o600).write(path, content.as_bytes()).unwrap();
+    }
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn execute(line: &str) -> ConfigurationResult<Report> {
+        Command::parse("app config", &args(line)).unwrap().execute::<Config>()
+    }
+
+    #[test]
+    fn test_parse_commands_and_usage() {
+        assert_eq!(
+            Command::parse("app config", &args("init a.toml --force")),
+            Ok(Command::Init { file: Some("a.toml".into()), force: true })
+        );
+        assert_eq!(Command::parse("app config", &args("diff a.toml")), Ok(Command::Diff { a: "a.toml".into(), b: None }));
+        for bad in ["", "check", "schema x", "init --dry-run", "frobnicate"] {
+            let usage = Command::parse("app config", &args(bad)).unwrap_err();
+            assert!(usage.contains("\n  app config migrate FILE [--dry-run]"), "{}", usage);
+        }
+        assert_eq!(run::<Config>("app config", &args("bogus")), EXIT_USAGE);
+    }
+
+    #[test]
+    fn test_init_check_and_diff() {
+        let dir = temp_dir("libelp_cli");
+        let file = dir.join("app.toml").display().to_string();
+        execute(&format!("init {}", file)).unwrap();
+        assert!(fs::read_to_string(&file).unwrap().contains("# db host"));
+        assert!(matches!(execute(&format!("init {}", file)), Err(ConfigurationError::SaveError { .. })));
+
+        write(&file, "debug = false\n[database]\nhost = \"localhost\"\nport = 5432\npassword = \"\"\n");
+        assert_eq!(execute(&format!("check {}", file)).unwrap().text, format!("{}: ok\n", file));
+        assert_eq!(execute(&format!("diff {}", file)).unwrap(), Report { text: String::new(), exit_code: 0 });
+
+        let other = dir.join("other.toml").display().to_string();
+        write(&other, "debug = false\n[database]\nhost = \"localhost\"\nport = 6432\npassword = \"\"\n");
+        let report = execute(&format!("diff {} {}", file, other)).unwrap();
+        assert_eq!(report, Report { text: "database.port: 5432 -> 6432\n".into(), exit_code: EXIT_DIFFERENT });
+        write(&other, "debug = false\n[database]\nhost = \"localhost\"\nport = 5432\npassword = \"hunter2\"\n");
+        let report = execute(&format!("diff {}", other)).unwrap();
+        assert_eq!(report, Report { text: "database.password: (secret changed)\n".into(), exit_code: EXIT_DIFFERENT });
+
+        write(&other, "debug = false\n[database]\nhost = \"localhost\"\nport = 0\npassword = \"\"\n");
+        let err = execute(&format!("check {}", other)).unwrap_err();
+        assert_eq!(exit_code(&err), 78);
+        write(&other, "[database\n");
+        assert_eq!(exit_code(&execute(&format!("check {}", other)).unwrap_err()), 65);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+            let err = execute(&format!("check {}", file)).unwrap_err();
+            assert!(matches!(err, ConfigurationError::InsecurePermissions { .. }), "{:?}", err);
+        }
+        assert_eq!(exit_code(&execute("check /nonexistent/app.toml").unwrap_err()), 74);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_explain_and_schema() {
+        let text = execute("explain database.port").unwrap().text;
+        assert_eq!(text, "database.port (u16)\n  db port\n  default: 5432\n  must be at least 1\n");
+        assert!(execute("explain").unwrap().text.contains("debug (bool)\n  enable debug output\n  default: false\n  deprecated: use `log.level`\n"));
+        assert!(matches!(execute("explain databse"), Err(ConfigurationError::UnknownKey { suggestions, .. }) if suggestions == ["database"]));
+
+        let schema = execute("schema").unwrap().text;
+        assert!(schema.starts_with("{\n  \"$schema\": \"https://json-schema.org/draft/2020-12/schema\",\n  \"type\": \"object\","), "{}", schema);
+        assert!(schema.contains("\"port\": {\n          \"type\": \"integer\",\n          \"description\": \"db port\",\n          \"default\": 5432,\n          \"minimum\": 1\n        }"), "{}", schema);
+        assert!(schema.contains("\"writeOnly\": true"), "{}", schema);
+        assert!(schema.contains("\"deprecated\": true"), "{}", schema);
+        assert_eq!(json_string("a\"b\n"), "\"a\\\"b\\n\"");
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Signing {
+        #[config(default = "signing.key", required)]
+        key_file: String,
+    }
+
+    fn worker_threads() -> u16 {
+        2 * 4
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Workers {
+        #[config(default_fn = "worker_threads")]
+        threads: u16,
+    }
+
+    #[test]
+    fn test_explain_shows_computed_defaults() {
+        assert_eq!(explain::<Workers>(None).unwrap(), "threads (u16)\n  default: 8\n");
+    }
+
+    #[test]
+    fn test_required_fields_in_explain_and_schema() {
+        assert!(explain::<Signing>(None).unwrap().contains("key_file (String)\n  default: \"signing.key\"\n  required: yes\n"));
+        assert!(schema::<Signing>().contains("  },\n  \"required\": [\"key_file\"],\n  \"additionalProperties\": false"));
+    }
+
+    #[test]
+    fn test_migrate_keeps_files_missing_required_keys() {
+        #[derive(libelp::Configuration, Debug)]
+        struct Token {
+            #[config(required)]
+            key_file: String,
+        }
+
+        let dir = temp_dir("libelp_cli");
+        let file = dir.join("token.toml").display().to_string();
+        write(&file, "legacy = 1\n");
+        let err = Command::parse("app config", &args(&format!("migrate {}", file))).unwrap().execute::<Token>().unwrap_err();
+        assert!(matches!(&err, ConfigurationError::ValidationError { field, .. } if field.as_deref() == Some("key_file")), "{:?}", err);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "legacy = 1\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_migrate_keeps_includes_and_profiles() {
+        let dir = temp_dir("libelp_cli");
+        let file = dir.join("app.toml").display().to_string();
+        write(&file, "include = [\"local.toml\"]\nlegacy = 1\n[profile.qa.database]\nhost = \"qa-db\"\n");
+        let preview = execute(&format!("migrate {} --dry-run", file)).unwrap().text;
+        let migrated: toml::Table = preview.parse().unwrap();
+        assert_eq!(migrated["include"], Value::Array(vec![Value::String("local.toml".into())]), "{}", preview);
+        assert_eq!(migrated["profile"]["qa"]["database"]["host"].as_str(), Some("qa-db"), "{}", preview);
+        assert!(!migrated.contains_key("legacy"), "{}", preview);
+
+        for unresolved in ["[database]\nhost = \"${env:DB_HOST}\"\n", "[database.password]\nfrom_file = \"db.pass\"\n"] {
+            write(&file, unresolved);
+            let err = execute(&format!("migrate {}", file)).unwrap_err();
+            assert!(matches!(&err, ConfigurationError::ValidationError { message, .. } if message.starts_with("Cannot migrate")), "{:?}", err);
+            assert_eq!(fs::read_to_string(&file).unwrap(), unresolved);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_migrate_adds_missing_and_drops_unknown_keys() {
+        let dir = temp_dir("libelp_cli");
+        let file = dir.join("app.toml").display().to_string();
+        write(&file, "debug = true\nlegacy = 1\n[database]\nhost = \"db\"\n");
+
+        let preview = execute(&format!("migrate {} --dry-run", file)).unwrap().text;
+        assert!(preview.contains("\nhost = \"db\""), "{}", preview);
+        assert!(!preview.contains("legacy"), "{}", preview);
+
+        let text = execute(&format!("migrate {}", file)).unwrap().text;
+        assert!(text.contains("removed unknown key legacy\n"), "{}", text);
+        assert!(text.contains("added database.port = 5432\n"), "{}", text);
+        assert!(text.contains("deprecated debug: use `log.level`\n"), "{}", text);
+        assert!(fs::read_to_string(backup_path(&file, 1)).unwrap().contains("legacy = 1"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), preview);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,12 @@
-use crate::error::{ConfigurationError, ConfigurationResult, utils};
+use crate::config::format::Format;
+use crate::config::loader::ConfigLoader;
+use crate::config::meta::{self, FieldMeta};
+use crate::config::path;
+use crate::config::persist::SaveOptions;
+use crate::config::template;
+use crate::error::{ConfigurationError, ConfigurationResult};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Trait for configuration structs generated by the `Configuration` derive macro
 /// 
@@ -10,11 +16,30 @@ pub trait Configuration: Sized + Serialize + for<'de> Deserialize<'de> {
     /// Create a new configuration with default values
     /// 
     /// This method is automatically generated by the `Configuration` derive macro.
-    fn new() -> Self;
-    /// Load configuration from a TOML file
+    /// Structs with a `#[config(required)]` field without a default have no
+    /// defaults to create: the derive leaves this method out and calling it
+    /// panics. Build them with `builder()` or load them instead.
+    fn new() -> Self {
+        panic!("{} has required keys without a default; build it with `builder()`", std::any::type_name::<Self>())
+    }
+
+    // Values before anything is loaded, the base of templates, `diff` and
+    // `migrate`; the derive fills required fields without a default with the
+    // empty value of their type
+    #[doc(hidden)]
+    fn __elp_new() -> Self {
+        Self::new()
+    }
+
+    /// Load configuration from a file
+    /// 
+    /// The format is chosen by file extension (see [`Format::from_path`]);
+    /// files with an unknown or missing extension are read as TOML.
+    /// Use [`ConfigLoader`] to also merge the files named by an `include`
+    /// key or fragment directories.
     /// 
     /// # Arguments
-    /// * `path` - Path to the TOML configuration file
+    /// * `path` - Path to the configuration file
     /// 
     /// # Returns
     /// * `Ok(config)` - Successfully loaded configuration
@@ -38,10 +63,12 @@ pub trait Configuration: Sized + Serialize + for<'de> Deserialize<'de> {
     /// }
     /// ```
     fn load_from_file(path: impl AsRef<Path>) -> ConfigurationResult<Self> {
-        utils::load_from_file(path)
+        ConfigLoader::new().file(path).load()
     }
 
-    /// Save configuration to a TOML file
+    /// Save configuration to a file
+    /// 
+    /// The format is chosen by file extension like in [`Configuration::load_from_file`].
     /// 
     /// # Arguments
     /// * `self` - Configuration to save
@@ -69,7 +96,74 @@ pub trait Configuration: Sized + Serialize + for<'de> Deserialize<'de> {
     /// }
     /// ```
     fn save_to_file(self, path: impl AsRef<Path>) -> ConfigurationResult<()> {
-        utils::save_to_file(&self, path)
+        SaveOptions::for_config::<Self>().save(&self, path)
+    }
+
+    /// Load configuration from a file without blocking the async runtime
+    ///
+    /// Behaves like [`Configuration::load_from_file`]; the work runs on
+    /// tokio's blocking pool.
+    #[cfg(feature = "async")]
+    fn load_from_file_async(path: impl AsRef<Path>) -> impl Future<Output = ConfigurationResult<Self>> + Send
+    where
+        Self: Send + 'static,
+    {
+        let loader = ConfigLoader::new().file(path);
+        async move { loader.load_async().await }
+    }
+
+    /// Save configuration to a file without blocking the async runtime
+    ///
+    /// The configuration is serialized right away; only the write is awaited.
+    #[cfg(feature = "async")]
+    fn save_to_file_async(self, path: impl AsRef<Path>) -> impl Future<Output = ConfigurationResult<()>> + Send {
+        let path = path.as_ref().to_path_buf();
+        let content = Format::from_path(&path).unwrap_or(Format::Toml).serialize(&self);
+        let options = SaveOptions::for_config::<Self>();
+        async move { crate::error::utils::write_file_async(&path, content?, options).await }
+    }
+
+    /// Load configuration from a file in an explicitly chosen format
+    /// 
+    /// # Arguments
+    /// * `path` - Path to the configuration file
+    /// * `format` - Format of the file, regardless of its extension
+    fn load_from_file_as(path: impl AsRef<Path>, format: Format) -> ConfigurationResult<Self> {
+        ConfigLoader::new().file_as(path, format).load()
+    }
+
+    /// Save configuration to a file in an explicitly chosen format
+    /// 
+    /// # Arguments
+    /// * `path` - Path where to save the configuration file
+    /// * `format` - Format to write, regardless of the extension
+    fn save_to_file_as(self, path: impl AsRef<Path>, format: Format) -> ConfigurationResult<()> {
+        SaveOptions::for_config::<Self>().save_as(&self, path, format)
+    }
+
+    /// Load configuration from a string in the given format
+    ///
+    /// Absent keys take their defaults; absent `#[config(required)]` keys
+    /// are a validation error.
+    fn from_str_as(source: &str, format: Format) -> ConfigurationResult<Self> {
+        if Self::metadata().iter().any(|field| field.required) {
+            meta::check_required::<Self>(&format.parse(source)?)?;
+        }
+        format.parse(source).map_err(|e| e.with_field_meta::<Self>())
+    }
+
+    /// Convert configuration to a string in the given format
+    fn to_string_as(&self, format: Format) -> ConfigurationResult<String> {
+        format.serialize(self)
+    }
+
+    /// Render a commented template in the given format
+    /// 
+    /// The format-independent counterpart of the derived `to_toml()`: each field
+    /// is preceded by its note, kind and default, and values equal to the
+    /// defaults are commented out. JSON has no comments and is written plain.
+    fn to_template(&self, format: Format) -> ConfigurationResult<String> {
+        template::render_template(self, format)
     }
 
     /// Load configuration from a TOML string
@@ -81,16 +175,7 @@ pub trait Configuration: Sized + Serialize + for<'de> Deserialize<'de> {
     /// * `Ok(config)` - Successfully parsed configuration
     /// * `Err(error)` - Error parsing the configuration
     fn from_toml_string(toml_str: &str) -> ConfigurationResult<Self> {
-        toml::from_str(toml_str)
-            .map_err(|e| {
-                // Try to extract line and column information from TOML error
-                let (line, column) = extract_toml_error_location(&e);
-                ConfigurationError::toml_parse_error(
-                    e.to_string(),
-                    line,
-                    column
-                )
-            })
+        Self::from_str_as(toml_str, Format::Toml)
     }
 
     /// Convert configuration to a TOML string
@@ -103,10 +188,76 @@ pub trait Configuration: Sized + Serialize + for<'de> Deserialize<'de> {
             .map_err(|e| ConfigurationError::toml_serialize_error(e.to_string()))
     }
 
+    /// Look up the `note` declared for a field
+    /// 
+    /// # Arguments
+    /// * `path` - Dotted field path, e.g. `database.port`
+    /// 
+    /// Generated by the `Configuration` derive macro from `#[config(note = "...")]`
+    /// or the field's doc comment; used to enrich parse errors. The default implementation knows no notes.
+    fn field_note(_path: &str) -> Option<&'static str> {
+        None
+    }
+
+    /// Whether any field, including fields of nested sections, is marked `#[config(secret)]`
+    ///
+    /// Loading such types checks file permissions (see [`PermissionPolicy`](crate::PermissionPolicy))
+    /// and saving creates new files with mode `0600`.
+    fn has_secrets() -> bool {
+        false
+    }
+
+    /// Description of every field, including the fields of nested sections
+    ///
+    /// Derived implementations flatten `Self::FIELDS` with dotted paths (see
+    /// [`flatten`](crate::config::meta::flatten)); others describe no fields.
+    fn metadata() -> Vec<FieldMeta> {
+        Vec::new()
+    }
+
+    /// Current value of the field at a dotted key path, e.g. `database.port`
+    ///
+    /// Sections are returned as tables; unknown paths give `None`.
+    fn get_path(&self, path: &str) -> Option<toml::Value> {
+        let value = toml::Value::try_from(self).ok()?;
+        path::lookup(&value, path).filter(|_| !path.is_empty()).cloned()
+    }
+
+    /// Replace the field at a dotted key path, checking the value against its type
+    ///
+    /// Unknown paths fail with [`ConfigurationError::UnknownKey`] listing the
+    /// closest known paths; a value of the wrong type leaves `self` unchanged.
+    fn set_path(&mut self, path: &str, value: impl Into<toml::Value>) -> ConfigurationResult<()> {
+        let mut root = toml::Value::try_from(&*self).map_err(|e| ConfigurationError::toml_serialize_error(e.to_string()))?;
+        let (parent, key) = match path.rsplit_once('.') {
+            Some((parent, key)) => (parent, key),
+            None => ("", path),
+        };
+        let slot = path::lookup_mut(&mut root, parent).and_then(|table| table.get_mut(key));
+        match slot {
+            Some(slot) if !path.is_empty() => *slot = value.into(),
+            _ => return Err(path::unknown_key(path, path::paths(&root))),
+        }
+        *self = Self::deserialize(root)
+            .map_err(|e| ConfigurationError::validation_error(e.to_string(), Some(path.to_string())))?;
+        Ok(())
+    }
+
+    /// Expand and resolve the `PathBuf` fields of a loaded configuration
+    ///
+    /// `base_dir` maps a dotted key path to the directory of the file that
+    /// set it. Derived implementations apply `~`/`$VAR` expansion and resolve
+    /// relative paths against that directory; see
+    /// [`resolve_field`](crate::config::paths::resolve_field).
+    fn resolve_paths(&mut self, _base_dir: &dyn Fn(&str) -> Option<PathBuf>) -> ConfigurationResult<()> {
+        Ok(())
+    }
+
     /// Validate the configuration
     /// 
     /// This method can be overridden by implementors to provide custom validation logic.
-    /// The default implementation always returns `Ok(())`.
+    /// The default implementation always returns `Ok(())`; derived implementations
+    /// check `#[config(min = ..., max = ...)]` bounds, including in nested sections.
     /// 
     /// # Returns
     /// * `Ok(())` - Configuration is valid
@@ -134,8 +285,9 @@ pub trait Configuration: Sized + Serialize + for<'de> Deserialize<'de> {
                 Ok(config)
             }
             Err(ConfigurationError::FileError { .. }) => {
-                // File doesn't exist or can't be read, use defaults
-                let default_config = Self::new();
+                // File doesn't exist or can't be read, use defaults unless some keys are required
+                meta::check_required::<Self>(&toml::Value::Table(Default::default()))?;
+                let default_config = Self::__elp_new();
                 default_config.validate()?;
                 Ok(default_config)
             }
@@ -147,40 +299,12 @@ pub trait Configuration: Sized + Serialize + for<'de> Deserialize<'de> {
     }
 }
 
-/// Extract line and column information from TOML error
-fn extract_toml_error_location(error: &toml::de::Error) -> (Option<usize>, Option<usize>) {
-    let error_str = error.to_string();
-    
-    // Look for patterns like "line 5 column 10"
-    if let Some(caps) = regex::Regex::new(r"line (\d+) column (\d+)")
-        .unwrap()
-        .captures(&error_str)
-    {
-        if let (Ok(line), Ok(column)) = (caps[1].parse(), caps[2].parse()) {
-            return (Some(line), Some(column));
-        }
-    }
-    
-    // Look for patterns like "at line 5"
-    if let Some(caps) = regex::Regex::new(r"at line (\d+)")
-        .unwrap()
-        .captures(&error_str)
-    {
-        if let Ok(line) = caps[1].parse() {
-            return (Some(line), None);
-        }
-    }
-    
-    (None, None)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::test_util::temp_path;
     use serde::{Deserialize, Serialize};
     use std::fs;
-    use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
     use std::collections::HashMap;
 
     #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -208,6 +332,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_and_set_path_for_manual_impls() {
+        let mut config = TestConfig::new();
+        assert_eq!(config.get_path("port"), Some(toml::Value::Integer(8080)));
+        assert_eq!(config.get_path(""), None);
+        config.set_path("host", "example.com").unwrap();
+        assert_eq!(config.host, "example.com");
+        assert!(matches!(config.set_path("port", "x"), Err(ConfigurationError::ValidationError { .. })));
+        match config.set_path("prot", 1).unwrap_err() {
+            ConfigurationError::UnknownKey { suggestions, .. } => assert_eq!(suggestions, vec!["port".to_string()]),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(config.port, 8080);
+    }
+
     #[test]
     fn test_from_toml_string() {
         let toml_str = r#"
@@ -247,16 +386,9 @@ mod tests {
         assert!(invalid_config.validate().is_err());
     }
 
-    fn unique_temp_path(prefix: &str) -> PathBuf {
-        let mut p = std::env::temp_dir();
-        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
-        p.push(format!("{}_{}.toml", prefix, nanos));
-        p
-    }
-
     #[test]
     fn test_save_and_load_file_success() {
-        let path = unique_temp_path("libelp_cfg_io_ok");
+        let path = temp_path("libelp_cfg_io_ok").with_extension("toml");
         // Save
         TestConfig { host: "example".to_string(), port: 4242 }
             .save_to_file(&path)
@@ -272,7 +404,7 @@ mod tests {
 
     #[test]
     fn test_load_with_defaults_missing_file_returns_default() {
-        let path = unique_temp_path("libelp_cfg_missing");
+        let path = temp_path("libelp_cfg_missing").with_extension("toml");
         // Ensure file does not exist
         let _ = fs::remove_file(&path);
 
@@ -283,7 +415,7 @@ mod tests {
 
     #[test]
     fn test_load_with_defaults_uses_file_when_present() {
-        let path = unique_temp_path("libelp_cfg_present");
+        let path = temp_path("libelp_cfg_present").with_extension("toml");
         let toml_str = "host = \"from_file\"\nport = 9001\n";
         fs::write(&path, toml_str).unwrap();
 
@@ -340,7 +472,7 @@ mod tests {
 
     #[test]
     fn test_load_with_defaults_invalid_default_fails_validation() {
-        let path = unique_temp_path("libelp_cfg_invalid_default");
+        let path = temp_path("libelp_cfg_invalid_default").with_extension("toml");
         let _ = fs::remove_file(&path);
         let res = InvalidDefaultConfig::load_with_defaults(&path);
         assert!(matches!(res, Err(ConfigurationError::ValidationError { .. })), "expected ValidationError");
@@ -375,6 +507,222 @@ mod tests {
         assert!(has_commented_child, "expected commented child default line");
     }
 
+    /// Upstream cache
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct DocCache {
+        /// Cache host
+        #[config(default = "localhost")]
+        host: String,
+    }
+
+    /// Service settings
+    ///
+    /// Edit and restart.
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct DocCfg {
+        /// Listen port
+        ///
+        /// Below 1024 needs root.
+        #[config(default = 8080)]
+        port: u16,
+        /// Shown only without a note
+        #[config(default = 1, note = "worker count")]
+        workers: u16,
+        cache: DocCache,
+    }
+
+    #[test]
+    fn test_doc_comments_are_notes_and_section_headers() {
+        assert_eq!(DocCfg::field_note("port"), Some("Listen port\n\nBelow 1024 needs root."));
+        assert_eq!(DocCfg::field_note("workers"), Some("worker count"));
+        assert_eq!(DocCfg::field_note("cache.host"), Some("Cache host"));
+
+        let out = DocCfg::new().to_toml();
+        assert!(out.starts_with("# Service settings\n#\n# Edit and restart.\n\n"), "{}", out);
+        assert!(out.contains("# Listen port\n#\n# Below 1024 needs root.\n# u16, default: 8080\n# port = 8080"), "{}", out);
+        assert!(out.contains("# worker count, u16, default: 1"), "{}", out);
+        assert!(out.contains("# Upstream cache, DocCache\n[cache]\n# Cache host, String"), "{}", out);
+        assert_eq!(out.matches("Upstream cache").count(), 1, "{}", out);
+
+        let template = DocCfg::new().to_template(Format::Toml).unwrap();
+        assert!(template.contains("# Listen port\n#\n# Below 1024 needs root.\n# integer, default: 8080"), "{}", template);
+    }
+
+    #[test]
+    fn test_load_from_file_fills_missing_keys_from_defaults() {
+        let path = temp_path("libelp_cfg_partial").with_extension("toml");
+        fs::write(&path, "[child]\nversion = 3\n").unwrap();
+        let loaded = ParentCfg::load_from_file(&path).unwrap();
+        assert_eq!(loaded.name, "parent");
+        assert_eq!((loaded.child.name.as_str(), loaded.child.version), ("child", 3));
+        assert_eq!(ParentCfg::from_toml_string("").unwrap(), ParentCfg::new());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct SigningCfg {
+        #[config(default = "signing.key", required)]
+        key_file: String,
+        #[config(default = "HS256")]
+        algorithm: String,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct RequiredCfg {
+        #[config(default = "issuer", required)]
+        issuer: String,
+        signing: SigningCfg,
+    }
+
+    #[test]
+    fn test_required_keys_must_be_present() {
+        let config = RequiredCfg::from_toml_string("issuer = \"me\"\n[signing]\nkey_file = \"k\"\n").unwrap();
+        assert_eq!((config.signing.key_file.as_str(), config.signing.algorithm.as_str()), ("k", "HS256"));
+
+        match RequiredCfg::from_toml_string("issuer = \"me\"\n").unwrap_err() {
+            ConfigurationError::ValidationError { message, field } => {
+                assert_eq!(message, "required key is missing");
+                assert_eq!(field.as_deref(), Some("signing.key_file"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let path = temp_path("libelp_cfg_required").with_extension("toml");
+        fs::write(&path, "[signing]\nalgorithm = \"RS256\"\n").unwrap();
+        let err = RequiredCfg::load_from_file(&path).unwrap_err();
+        assert!(
+            matches!(&err, ConfigurationError::ValidationError { message, field }
+                if message == "required keys are missing: issuer, signing.key_file" && field.as_deref() == Some("issuer")),
+            "{:?}",
+            err
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct TokenCfg {
+        /// Key signing the tokens
+        #[config(required)]
+        key_file: String,
+        #[config(default = 3600)]
+        ttl: u64,
+    }
+
+    #[test]
+    fn test_required_keys_without_default() {
+        match TokenCfg::builder().ttl(60u64).build().unwrap_err() {
+            ConfigurationError::ValidationError { message, field } => {
+                assert_eq!(message, "required key is missing");
+                assert_eq!(field.as_deref(), Some("key_file"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        // Only setters count, and an empty value set through one is kept
+        assert!(TokenCfg::builder().with(|c| c.key_file = "jwt.key".to_string()).build().is_err());
+        assert_eq!(TokenCfg::builder().key_file("").build().unwrap().key_file, "");
+        let config = TokenCfg::builder().key_file("jwt.key").build().unwrap();
+        assert_eq!(config.ttl, 3600);
+
+        let out = config.to_toml();
+        assert!(out.contains("# Key signing the tokens, String, REQUIRED
+key_file = \"jwt.key\""), "{}", out);
+        assert!(out.contains("# ttl = 3600"), "{}", out);
+        assert_eq!(TokenCfg::from_toml_string(&out).unwrap(), config);
+
+        let template = TokenCfg::__elp_new().to_template(Format::Toml).unwrap();
+        assert!(template.contains("REQUIRED\nkey_file = \"\""), "{}", template);
+        assert!(!template.contains("# key_file"), "{}", template);
+        assert!(std::panic::catch_unwind(<TokenCfg as Configuration>::new).is_err());
+
+        assert!(TokenCfg::from_toml("ttl = 5\n").is_err());
+        // Plain serde leaves the key missing too instead of filling in a placeholder
+        assert!(toml::from_str::<TokenCfg>("ttl = 5\n").unwrap_err().to_string().contains("missing field `key_file`"));
+        assert_eq!(toml::from_str::<TokenCfg>("key_file = \"k\"\n").unwrap().ttl, 3600);
+        let err = TokenCfg::load_with_defaults(temp_path("libelp_cfg_absent").with_extension("toml")).unwrap_err();
+        assert!(matches!(&err, ConfigurationError::ValidationError { message, .. } if message == "required key is missing"), "{:?}", err);
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct AuthCfg {
+        #[config(required)]
+        token: TokenCfg,
+    }
+
+    #[test]
+    fn test_required_keys_in_sections_are_set_through_the_section_builder() {
+        let err = AuthCfg::builder().token(|t| t.ttl(5u64)).build().unwrap_err();
+        assert!(matches!(&err, ConfigurationError::ValidationError { field, .. } if field.as_deref() == Some("token.key_file")), "{:?}", err);
+        let config = AuthCfg::builder().token(|t| t.key_file("jwt.key")).build().unwrap();
+        assert_eq!(config.token.key_file, "jwt.key");
+        assert!(AuthCfg::builder().build().is_err());
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct PoolCfg {
+        /// Open connections at most
+        #[config(default = 10, min = 1)]
+        max_size: u32,
+        #[config(default = "30s")]
+        idle_timeout: std::time::Duration,
+    }
+
+    fn connect_log() -> Vec<String> {
+        vec!["not connected".to_string()]
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct DatabaseCfg {
+        #[config(default = "localhost")]
+        host: String,
+        #[config(flatten)]
+        pool: PoolCfg,
+        #[config(skip, default_fn = "connect_log")]
+        log: Vec<String>,
+        #[config(skip)]
+        url: Option<String>,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct StoreCfg {
+        database: DatabaseCfg,
+        #[config(flatten)]
+        redis: PoolCfg,
+    }
+
+    #[test]
+    fn test_flattened_and_skipped_fields() {
+        let paths: Vec<String> = StoreCfg::metadata().iter().map(|f| f.path.to_string()).collect();
+        assert_eq!(paths, ["database", "database.host", "database.max_size", "database.idle_timeout", "max_size", "idle_timeout"]);
+
+        let mut config = StoreCfg::from_toml_string("max_size = 3\n[database]\nmax_size = 5\nlog = \"x\"\n").unwrap();
+        assert_eq!((config.database.pool.max_size, config.redis.max_size), (5, 3));
+        assert_eq!((config.database.log.as_slice(), config.database.url.as_deref()), (&["not connected".to_string()][..], None));
+
+        config.database.url = Some("postgres://localhost".to_string());
+        config.set_path("database.idle_timeout", "1m").unwrap();
+        assert_eq!(config.get_path("database.idle_timeout"), Some(toml::Value::String("1m".to_string())));
+        assert!(config.get_path("database.pool").is_none() && config.get_path("database.url").is_none());
+        assert!(config.set_path("database.log", "y").is_err());
+        assert_eq!(StoreCfg::field_note("database.max_size"), Some("Open connections at most"));
+
+        let out = config.to_toml();
+        assert!(out.starts_with("# Open connections at most, u32, default: 10\nmax_size = 3"), "{}", out);
+        assert!(out.contains("[database]\n# String, default: \"localhost\"\n# host = \"localhost\"\n\n# Open connections"), "{}", out);
+        assert!(!out.contains("pool") && !out.contains("url") && !out.contains("log"), "{}", out);
+        let read = StoreCfg::from_toml_string(&out).unwrap();
+        assert_eq!(read.database.pool, config.database.pool);
+        assert_eq!(read.redis, config.redis);
+
+        let template = StoreCfg::new().to_template(Format::Toml).unwrap();
+        assert!(template.contains("max_size = 10") && !template.contains("pool"), "{}", template);
+
+        config.database.pool.max_size = 0;
+        match config.validate().unwrap_err() {
+            ConfigurationError::ValidationError { field, .. } => assert_eq!(field.as_deref(), Some("database.max_size")),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_nested_from_toml_parses_child_values() {
         let s = r#"
@@ -390,9 +738,76 @@ mod tests {
         assert_eq!(p.child.version, 2);
     }
 
+    mod limits {
+        pub const MAX_CONNECTIONS: u32 = 512;
+    }
+    const DEFAULT_REALM: &str = "internal";
+
+    fn worker_threads() -> usize {
+        3 + 4
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct ComputedCfg {
+        #[config(default_fn = "worker_threads", note = "worker threads")]
+        workers: usize,
+        #[config(default = limits::MAX_CONNECTIONS, note = "connection limit")]
+        max_connections: u32,
+        #[config(default = 60 * 60, note = "token lifetime in seconds")]
+        token_ttl: u64,
+        #[config(default = self::DEFAULT_REALM, note = "realm")]
+        realm: String,
+        #[config(default = INFO, note = "log level")]
+        level: String,
+    }
+
+    #[test]
+    fn test_computed_defaults_are_rendered_with_their_values() {
+        let cfg = ComputedCfg::new();
+        assert_eq!(cfg.workers, 7);
+        assert_eq!(cfg.max_connections, 512);
+        assert_eq!(cfg.token_ttl, 3600);
+        assert_eq!(cfg.realm, "internal");
+        assert_eq!(cfg.level, "INFO");
+
+        let out = cfg.to_toml();
+        assert!(out.contains("# worker threads, usize, default: 7\n# workers = 7"), "{}", out);
+        assert!(out.contains("default: 512"), "{}", out);
+        assert!(out.contains("# token_ttl = 3600"), "{}", out);
+        assert!(out.contains("# realm = \"internal\""), "{}", out);
+        assert!(!out.contains("default_value"), "{}", out);
+
+        let template = cfg.to_template(Format::Toml).unwrap();
+        assert!(template.contains("# workers = 7"), "{}", template);
+    }
+
+    #[test]
+    fn test_derived_field_note_resolves_nested_paths() {
+        assert_eq!(ParentCfg::field_note("name"), Some("parent name"));
+        assert_eq!(ParentCfg::field_note("child.version"), Some("child version"));
+        assert_eq!(ParentCfg::field_note("child.missing"), None);
+        assert_eq!(ParentCfg::field_note("child"), None);
+    }
+
+    #[test]
+    fn test_load_from_file_parse_error_names_file_field_and_note() {
+        let path = temp_path("libelp_cfg_parse_ctx").with_extension("toml");
+        fs::write(&path, "name = \"root\"\n\n[child]\nversion = \"two\"\n").unwrap();
+
+        let err = ParentCfg::load_from_file(&path).unwrap_err();
+        let ctx = err.parse_context().expect("expected parse context");
+        assert_eq!(ctx.file.as_deref(), Some(path.display().to_string().as_str()));
+        assert_eq!(ctx.field.as_deref(), Some("child.version"));
+        assert_eq!(ctx.expected.as_deref(), Some("u16"));
+        assert_eq!(ctx.note.as_deref(), Some("child version"));
+        assert!(matches!(err, ConfigurationError::TomlParseError { line: Some(4), column: Some(11), .. }));
+
+        let _ = fs::remove_file(&path);
+    }
+
     #[test]
     fn test_load_with_defaults_invalid_file_propagates_parse_error() {
-        let path = unique_temp_path("libelp_cfg_invalid_file");
+        let path = temp_path("libelp_cfg_invalid_file").with_extension("toml");
         // invalid: port should be integer
         let toml_str = "host = \"ok\"\nport = \"oops\"\n";
         fs::write(&path, toml_str).unwrap();
@@ -427,4 +842,26 @@ mod tests {
         let p = ParentCfg::new();
         assert!(p.validate().is_ok());
     }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_load_and_save_round_trip() {
+        let path = temp_path("libelp_cfg_async").with_extension("toml");
+        let mut cfg = ParentCfg::new();
+        cfg.child.version = 9;
+        cfg.save_to_file_async(&path).await.unwrap();
+
+        let loaded = ParentCfg::load_from_file_async(&path).await.unwrap();
+        assert_eq!(loaded.child.version, 9);
+        let plain: ParentCfg = crate::error::utils::load_from_file_async(&path).await.unwrap();
+        assert_eq!(plain, loaded);
+
+        fs::write(&path, "name = 1\n").unwrap();
+        let err = ConfigLoader::new().file(&path).load_async::<ParentCfg>().await.unwrap_err();
+        assert_eq!(err.parse_context().and_then(|c| c.field.as_deref()), Some("name"));
+        let _ = fs::remove_file(&path);
+
+        let err = ParentCfg::load_from_file_async(&path).await.unwrap_err();
+        assert!(matches!(err, ConfigurationError::FileError { .. }), "{:?}", err);
+    }
 }
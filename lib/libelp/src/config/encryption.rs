@@ -0,0 +1,405 @@
+use crate::config::persist::write_atomic;
+use crate::config::secret::read_secret;
+use crate::error::{ConfigurationError, ConfigurationResult};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prefix marking an encrypted string value
+///
+/// The rest of the value is base64 of a 12-byte nonce followed by the
+/// ChaCha20-Poly1305 ciphertext of the UTF-8 plaintext.
+pub const ENC_PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+/// Whether a string is an encrypted value
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENC_PREFIX)
+}
+
+/// 256-bit key used to encrypt and decrypt configuration values
+///
+/// Key files hold the key as a single line of base64. They are read with the
+/// same rules as secret files: trimmed, and refused when world-readable.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl EncryptionKey {
+    /// Generate a new random key
+    pub fn generate() -> Self {
+        Self(ChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// Parse a key from its base64 form
+    pub fn from_base64(text: &str) -> ConfigurationResult<Self> {
+        let bytes = STANDARD
+            .decode(text.trim())
+            .map_err(|e| ConfigurationError::load_error(format!("Invalid encryption key: {}", e), "encryption key"))?;
+        let bytes: [u8; 32] = bytes.try_into().map_err(|b: Vec<u8>| {
+            ConfigurationError::load_error(
+                format!("Invalid encryption key: expected 32 bytes, got {}", b.len()),
+                "encryption key",
+            )
+        })?;
+        Ok(Self(bytes))
+    }
+
+    /// Base64 form of the key, as stored in key files
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    /// Read a key file
+    pub fn load(path: impl AsRef<Path>) -> ConfigurationResult<Self> {
+        let path = path.as_ref();
+        let text = read_secret(path, "encryption key")?;
+        Self::from_base64(&text).map_err(|e| match e {
+            ConfigurationError::LoadError { message, .. } => {
+                ConfigurationError::secret_error(message, path.display().to_string(), "encryption key")
+            }
+            other => other,
+        })
+    }
+
+    /// Write the key to a file readable only by its owner
+    pub fn save(&self, path: impl AsRef<Path>) -> ConfigurationResult<()> {
+        let path = path.as_ref();
+        let display = path.display().to_string();
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .map_err(|e| ConfigurationError::save_error(format!("Failed to write key file: {}", e), &display))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .map_err(|e| ConfigurationError::save_error(format!("Failed to restrict key file: {}", e), &display))?;
+        }
+        std::io::Write::write_all(&mut file, format!("{}\n", self.to_base64()).as_bytes())
+            .map_err(|e| ConfigurationError::save_error(format!("Failed to write key file: {}", e), &display))
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+
+    /// Encrypt a value, producing an `enc:v1:` string
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("ChaCha20-Poly1305 encryption of an in-memory buffer cannot fail");
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        format!("{}{}", ENC_PREFIX, STANDARD.encode(payload))
+    }
+
+    /// Decrypt an `enc:v1:` string
+    ///
+    /// Fails with a [`ConfigurationError::LoadError`] when the value is
+    /// malformed or was encrypted with a different key.
+    pub fn decrypt(&self, value: &str) -> ConfigurationResult<String> {
+        let malformed = |reason: &str| ConfigurationError::load_error(format!("malformed encrypted value: {}", reason), "encrypted value");
+        let encoded = value.strip_prefix(ENC_PREFIX).ok_or_else(|| malformed("missing `enc:v1:` prefix"))?;
+        let payload = STANDARD.decode(encoded).map_err(|e| malformed(&e.to_string()))?;
+        if payload.len() < NONCE_LEN {
+            return Err(malformed("too short"));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = self.cipher().decrypt(Nonce::from_slice(nonce), ciphertext).map_err(|_| {
+            ConfigurationError::load_error("wrong key or corrupted encrypted value", "encrypted value")
+        })?;
+        String::from_utf8(plaintext).map_err(|_| malformed("plaintext is not UTF-8"))
+    }
+}
+
+/// Decrypt every `enc:v1:` string in a parsed tree
+///
+/// `on_error` turns a failure at a dotted key path into the reported error.
+pub fn decrypt_table(
+    table: &mut toml::Table,
+    key: Option<&EncryptionKey>,
+    on_error: &dyn Fn(&str, String) -> ConfigurationError,
+) -> ConfigurationResult<()> {
+    for (name, value) in table.iter_mut() {
+        decrypt_value(value, name, key, on_error)?;
+    }
+    Ok(())
+}
+
+fn decrypt_value(
+    value: &mut toml::Value,
+    path: &str,
+    key: Option<&EncryptionKey>,
+    on_error: &dyn Fn(&str, String) -> ConfigurationError,
+) -> ConfigurationResult<()> {
+    match value {
+        toml::Value::String(s) if is_encrypted(s) => {
+            let Some(key) = key else {
+                return Err(on_error(path, "value is encrypted but no decryption key was configured".to_string()));
+            };
+            *s = key.decrypt(s).map_err(|e| match e {
+                ConfigurationError::LoadError { message, .. } => on_error(path, message),
+                other => other,
+            })?;
+        }
+        toml::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                decrypt_value(item, &format!("{}[{}]", path, i), key, on_error)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (name, item) in table.iter_mut() {
+                decrypt_value(item, &format!("{}.{}", path, name), key, on_error)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Call `f` with the dotted path and content of every string in a TOML document
+///
+/// When `f` returns a new string it replaces the old one; comments, layout
+/// and the rest of the document are left untouched.
+fn rewrite_strings(
+    source: &str,
+    f: &mut dyn FnMut(&str, &str) -> ConfigurationResult<Option<String>>,
+) -> ConfigurationResult<String> {
+    let mut doc: toml_edit::DocumentMut = source
+        .parse()
+        .map_err(|e: toml_edit::TomlError| ConfigurationError::toml_parse_error(e.message(), None, None))?;
+    rewrite_table(doc.as_table_mut(), "", f)?;
+    Ok(doc.to_string())
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) }
+}
+
+fn rewrite_table(
+    table: &mut toml_edit::Table,
+    prefix: &str,
+    f: &mut dyn FnMut(&str, &str) -> ConfigurationResult<Option<String>>,
+) -> ConfigurationResult<()> {
+    for (key, item) in table.iter_mut() {
+        let path = join(prefix, key.get());
+        match item {
+            toml_edit::Item::Value(value) => rewrite_value(value, &path, f)?,
+            toml_edit::Item::Table(sub) => rewrite_table(sub, &path, f)?,
+            toml_edit::Item::ArrayOfTables(tables) => {
+                for (i, sub) in tables.iter_mut().enumerate() {
+                    rewrite_table(sub, &format!("{}[{}]", path, i), f)?;
+                }
+            }
+            toml_edit::Item::None => {}
+        }
+    }
+    Ok(())
+}
+
+fn rewrite_value(
+    value: &mut toml_edit::Value,
+    path: &str,
+    f: &mut dyn FnMut(&str, &str) -> ConfigurationResult<Option<String>>,
+) -> ConfigurationResult<()> {
+    match value {
+        toml_edit::Value::String(s) => {
+            if let Some(new) = f(path, s.value())? {
+                let decor = s.decor().clone();
+                *value = toml_edit::Value::from(new);
+                *value.decor_mut() = decor;
+            }
+        }
+        toml_edit::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                rewrite_value(item, &format!("{}[{}]", path, i), f)?;
+            }
+        }
+        toml_edit::Value::InlineTable(table) => {
+            for (key, item) in table.iter_mut() {
+                rewrite_value(item, &join(path, key.get()), f)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Encrypt the string at a dotted key path of a TOML document
+///
+/// Already encrypted values are left as they are. Fails with a
+/// [`ConfigurationError::ValidationError`] when there is no string at `path`.
+pub fn encrypt_field(source: &str, path: &str, key: &EncryptionKey) -> ConfigurationResult<String> {
+    let mut found = false;
+    let output = rewrite_strings(source, &mut |p, value| {
+        if p != path {
+            return Ok(None);
+        }
+        found = true;
+        Ok((!is_encrypted(value)).then(|| key.encrypt(value)))
+    })?;
+    if !found {
+        return Err(ConfigurationError::validation_error(
+            format!("No string value at `{}` to encrypt", path),
+            Some(path.to_string()),
+        ));
+    }
+    Ok(output)
+}
+
+/// Re-encrypt every encrypted value of a TOML document with a new key
+///
+/// Returns the new document and the number of values re-encrypted.
+pub fn reencrypt(source: &str, old: &EncryptionKey, new: &EncryptionKey) -> ConfigurationResult<(String, usize)> {
+    let mut count = 0;
+    let output = rewrite_strings(source, &mut |path, value| {
+        if !is_encrypted(value) {
+            return Ok(None);
+        }
+        let plaintext = old.decrypt(value).map_err(|e| match e {
+            ConfigurationError::LoadError { message, .. } => {
+                ConfigurationError::load_error(format!("Cannot decrypt `{}`: {}", path, message), path)
+            }
+            other => other,
+        })?;
+        count += 1;
+        Ok(Some(new.encrypt(&plaintext)))
+    })?;
+    Ok((output, count))
+}
+
+/// Replace the key in `key_file` and re-encrypt `files` with the new key
+///
+/// All files are re-encrypted in memory before anything is written. The new
+/// key is staged next to the old one and only moved into place once every
+/// file has been rewritten. Returns the number of values re-encrypted.
+pub fn rotate_key(key_file: impl AsRef<Path>, files: &[PathBuf]) -> ConfigurationResult<usize> {
+    let key_file = key_file.as_ref();
+    let old = EncryptionKey::load(key_file)?;
+    let new = EncryptionKey::generate();
+
+    let mut rewritten = Vec::with_capacity(files.len());
+    let mut total = 0;
+    for file in files {
+        let display = file.display().to_string();
+        let source = fs::read_to_string(file)
+            .map_err(|e| ConfigurationError::file_error(format!("Failed to read file: {}", e), &display))?;
+        let (output, count) = reencrypt(&source, &old, &new).map_err(|e| match e {
+            ConfigurationError::LoadError { message, .. } => ConfigurationError::load_error(message, &display),
+            other => other,
+        })?;
+        total += count;
+        rewritten.push((file, output));
+    }
+
+    let mut staged = key_file.as_os_str().to_owned();
+    staged.push(".new");
+    let staged = PathBuf::from(staged);
+    new.save(&staged)?;
+    for (file, output) in rewritten {
+        write_atomic(file, output.as_bytes())?;
+    }
+    fs::rename(&staged, key_file).map_err(|e| {
+        ConfigurationError::save_error(format!("Failed to replace key file: {}", e), key_file.display().to_string())
+    })?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_util::temp_dir;
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip_uses_fresh_nonces() {
+        let key = EncryptionKey::generate();
+        let a = key.encrypt("hunter2");
+        let b = key.encrypt("hunter2");
+        assert!(a.starts_with(ENC_PREFIX));
+        assert_ne!(a, b);
+        assert_eq!(key.decrypt(&a).unwrap(), "hunter2");
+        assert!(key.decrypt("enc:v1:!!!").is_err());
+
+        let other = EncryptionKey::generate();
+        let err = other.decrypt(&a).unwrap_err();
+        assert!(err.to_string().contains("wrong key"), "{}", err);
+    }
+
+    #[test]
+    fn test_key_base64_roundtrip_and_debug_hides_key() {
+        let key = EncryptionKey::generate();
+        assert_eq!(EncryptionKey::from_base64(&key.to_base64()).unwrap(), key);
+        assert!(EncryptionKey::from_base64("c2hvcnQ=").is_err());
+        assert_eq!(format!("{:?}", key), "EncryptionKey(..)");
+    }
+
+    #[test]
+    fn test_decrypt_table_reports_key_path() {
+        let key = EncryptionKey::generate();
+        let mut table: toml::Table =
+            toml::from_str(&format!("[db]\npassword = \"{}\"\nhosts = [\"{}\"]\n", key.encrypt("pw"), key.encrypt("h"))).unwrap();
+        let on_error = |path: &str, message: String| ConfigurationError::load_error(message, path);
+        decrypt_table(&mut table, Some(&key), &on_error).unwrap();
+        assert_eq!(table["db"]["password"].as_str(), Some("pw"));
+        assert_eq!(table["db"]["hosts"][0].as_str(), Some("h"));
+
+        let mut table: toml::Table = toml::from_str(&format!("[db]\npassword = \"{}\"\n", key.encrypt("pw"))).unwrap();
+        let err = decrypt_table(&mut table, None, &on_error).unwrap_err();
+        assert!(matches!(err, ConfigurationError::LoadError { ref config_source, .. } if config_source == "db.password"));
+    }
+
+    #[test]
+    fn test_encrypt_field_and_reencrypt_preserve_document() {
+        let key = EncryptionKey::generate();
+        let source = "# Database\n[db]\nhost = \"localhost\" # primary\npassword = \"pw\" # rotated quarterly\n";
+        let encrypted = encrypt_field(source, "db.password", &key).unwrap();
+        assert!(encrypted.starts_with("# Database\n[db]\nhost = \"localhost\" # primary\npassword = \"enc:v1:"));
+        assert!(encrypted.ends_with("\" # rotated quarterly\n"), "{}", encrypted);
+        assert_eq!(encrypt_field(&encrypted, "db.password", &key).unwrap(), encrypted);
+        assert!(encrypt_field(source, "db.user", &key).is_err());
+
+        let new = EncryptionKey::generate();
+        let (rotated, count) = reencrypt(&encrypted, &key, &new).unwrap();
+        assert_eq!(count, 1);
+        let table: toml::Table = toml::from_str(&rotated).unwrap();
+        assert_eq!(new.decrypt(table["db"]["password"].as_str().unwrap()).unwrap(), "pw");
+        assert!(rotated.contains("host = \"localhost\" # primary"));
+        assert!(reencrypt(&rotated, &key, &new).is_err());
+    }
+
+    #[test]
+    fn test_rotate_key_rewrites_files_and_key() {
+        let dir = temp_dir("libelp_rotate");
+        let key_file = dir.join("eauth.key");
+        let old = EncryptionKey::generate();
+        old.save(&key_file).unwrap();
+        let config = dir.join("eauth.toml");
+        fs::write(&config, format!("user = \"admin\"\npassword = \"{}\"\n", old.encrypt("pw"))).unwrap();
+
+        assert_eq!(rotate_key(&key_file, std::slice::from_ref(&config)).unwrap(), 1);
+        let new = EncryptionKey::load(&key_file).unwrap();
+        assert_ne!(new, old);
+        let table: toml::Table = toml::from_str(&fs::read_to_string(&config).unwrap()).unwrap();
+        assert_eq!(new.decrypt(table["password"].as_str().unwrap()).unwrap(), "pw");
+        assert!(!dir.join("eauth.key.new").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
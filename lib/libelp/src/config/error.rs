@@ -1,3 +1,4 @@
+use crate::config::format::Format;
 use thiserror::Error;
 
 /// User-facing configuration errors
@@ -16,6 +17,8 @@ pub enum ConfigurationError {
         line: Option<usize>,
         /// Column number where the error occurred (if available)
         column: Option<usize>,
+        /// Source excerpt, field path and note for rendering (if available)
+        context: Option<Box<ParseContext>>,
     },
 
     /// TOML serialization error
@@ -25,6 +28,30 @@ pub enum ConfigurationError {
         message: String,
     },
 
+    /// Parsing error for a non-TOML format (YAML, JSON, JSON5, INI)
+    #[error("Failed to parse {format} configuration: {message}")]
+    ParseError {
+        /// Format that was being parsed
+        format: Format,
+        /// Human-readable error message
+        message: String,
+        /// Line number where the error occurred (if available)
+        line: Option<usize>,
+        /// Column number where the error occurred (if available)
+        column: Option<usize>,
+        /// Source excerpt, field path and note for rendering (if available)
+        context: Option<Box<ParseContext>>,
+    },
+
+    /// Serialization error for a non-TOML format (YAML, JSON, JSON5, INI)
+    #[error("Failed to serialize configuration to {format}: {message}")]
+    SerializeError {
+        /// Target format
+        format: Format,
+        /// Human-readable error message
+        message: String,
+    },
+
     /// Configuration validation error
     #[error("Configuration validation failed: {message}")]
     ValidationError {
@@ -60,11 +87,67 @@ pub enum ConfigurationError {
         /// Destination path where save was attempted
         path: String,
     },
+
+    /// Secret file could not be used
+    #[error("Failed to read secret for {field}: {message}")]
+    SecretError {
+        /// Human-readable error message
+        message: String,
+        /// Path of the secret file
+        path: String,
+        /// Dotted key path the secret was meant for
+        field: String,
+    },
+
+    /// A file holding secrets is readable or writable by other users
+    #[error("Insecure permissions on {path}: {message}")]
+    InsecurePermissions {
+        /// What is wrong with the mode or owner
+        message: String,
+        /// Path of the offending file
+        path: String,
+    },
+
+    /// A format whose cargo feature is not enabled, when parsing or serializing
+    #[error("{format} support is not enabled; build libelp with the `{}` feature", .format.feature().unwrap_or_default())]
+    FormatDisabled {
+        /// Format that was asked for
+        format: Format,
+    },
+
+    /// A dotted key path that names no field
+    #[error("Unknown configuration key `{path}`")]
+    UnknownKey {
+        /// The dotted path as given
+        path: String,
+        /// Known paths close to it, closest first
+        suggestions: Vec<String>,
+    },
 }
 
 /// Result type for configuration operations
 pub type ConfigurationResult<T> = Result<T, ConfigurationError>;
 
+/// Additional detail attached to a [`ConfigurationError::TomlParseError`]
+///
+/// Everything here is optional: syntax errors have an excerpt but no field,
+/// errors built by hand through [`ConfigurationError::toml_parse_error`] have none of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseContext {
+    /// File the configuration was read from
+    pub file: Option<String>,
+    /// Dotted path of the offending field, e.g. `database.port`
+    pub field: Option<String>,
+    /// Declared type of the offending field, e.g. `u16`
+    pub expected: Option<String>,
+    /// `note` declared on the field via `#[config(note = "...")]`
+    pub note: Option<String>,
+    /// Text of the offending source line
+    pub excerpt: Option<String>,
+    /// Number of characters to underline starting at the error column
+    pub width: usize,
+}
+
 impl ConfigurationError {
     /// Create a TOML parse error with location information
     pub fn toml_parse_error(message: impl Into<String>, line: Option<usize>, column: Option<usize>) -> Self {
@@ -72,6 +155,64 @@ impl ConfigurationError {
             message: message.into(),
             line,
             column,
+            context: None,
+        }
+    }
+
+    /// Create a parse error for a non-TOML format
+    pub fn parse_error(format: Format, message: impl Into<String>, line: Option<usize>, column: Option<usize>) -> Self {
+        Self::ParseError {
+            format,
+            message: message.into(),
+            line,
+            column,
+            context: None,
+        }
+    }
+
+    /// Record the file a parse error originated from
+    ///
+    /// Has no effect on other variants.
+    pub fn with_file(mut self, path: impl Into<String>) -> Self {
+        if let Self::TomlParseError { context, .. } | Self::ParseError { context, .. } = &mut self {
+            context.get_or_insert_with(Default::default).file = Some(path.into());
+        }
+        self
+    }
+
+    /// Fill in the field `note` of a parse error from a path lookup
+    ///
+    /// Typically called with [`Configuration::field_note`](crate::Configuration::field_note)
+    /// of the type being loaded. Has no effect when no field path is known.
+    pub fn with_field_note(mut self, lookup: impl Fn(&str) -> Option<&'static str>) -> Self {
+        if let Self::TomlParseError { context: Some(ctx), .. } | Self::ParseError { context: Some(ctx), .. } = &mut self
+            && let Some(note) = ctx.field.as_deref().and_then(&lookup)
+        {
+            ctx.note = Some(note.to_string());
+        }
+        self
+    }
+
+    /// Fill in the field `note` and `expected` type of a parse error from `T`
+    ///
+    /// The type is the one the field is declared with in [`Configuration::metadata`](crate::Configuration::metadata),
+    /// the note comes from [`Configuration::field_note`](crate::Configuration::field_note).
+    /// Has no effect when no field path is known.
+    pub fn with_field_meta<T: crate::Configuration>(self) -> Self {
+        let mut err = self.with_field_note(T::field_note);
+        if let Self::TomlParseError { context: Some(ctx), .. } | Self::ParseError { context: Some(ctx), .. } = &mut err
+            && let Some(field) = ctx.field.as_deref()
+        {
+            ctx.expected = T::metadata().iter().find(|f| f.path == field).map(|f| f.type_name.to_string());
+        }
+        err
+    }
+
+    /// Get the parse context, if this is a parse error carrying one
+    pub fn parse_context(&self) -> Option<&ParseContext> {
+        match self {
+            Self::TomlParseError { context, .. } | Self::ParseError { context, .. } => context.as_deref(),
+            _ => None,
         }
     }
 
@@ -82,6 +223,14 @@ impl ConfigurationError {
         }
     }
 
+    /// Create a serialize error for a non-TOML format
+    pub fn serialize_error(format: Format, message: impl Into<String>) -> Self {
+        Self::SerializeError {
+            format,
+            message: message.into(),
+        }
+    }
+
     /// Create a validation error
     pub fn validation_error(message: impl Into<String>, field: Option<String>) -> Self {
         Self::ValidationError {
@@ -90,6 +239,22 @@ impl ConfigurationError {
         }
     }
 
+    /// Prefix the field of a validation error with the key of its section
+    ///
+    /// Other errors are returned unchanged.
+    pub fn in_section(self, key: &str) -> Self {
+        match self {
+            Self::ValidationError { message, field } => Self::ValidationError {
+                message,
+                field: Some(match field {
+                    Some(field) => format!("{}.{}", key, field),
+                    None => key.to_string(),
+                }),
+            },
+            other => other,
+        }
+    }
+
     /// Create a file error
     pub fn file_error(message: impl Into<String>, path: impl Into<String>) -> Self {
         Self::FileError {
@@ -114,20 +279,62 @@ impl ConfigurationError {
         }
     }
 
+    /// Create a secret error
+    pub fn secret_error(message: impl Into<String>, path: impl Into<String>, field: impl Into<String>) -> Self {
+        Self::SecretError {
+            message: message.into(),
+            path: path.into(),
+            field: field.into(),
+        }
+    }
+
+    /// Create an unknown key error
+    pub fn unknown_key(path: impl Into<String>, suggestions: Vec<String>) -> Self {
+        Self::UnknownKey {
+            path: path.into(),
+            suggestions,
+        }
+    }
+
+    /// Create an insecure permissions error
+    pub fn insecure_permissions(message: impl Into<String>, path: impl Into<String>) -> Self {
+        Self::InsecurePermissions {
+            message: message.into(),
+            path: path.into(),
+        }
+    }
+
     /// Get a user-friendly error message with suggestions
     pub fn user_message(&self) -> String {
         match self {
-            ConfigurationError::TomlParseError { message, line, column } => {
+            ConfigurationError::TomlParseError { message, line, column, context } => {
                 let mut msg = format!("Configuration file has invalid TOML syntax: {}", message);
                 if let (Some(line), Some(column)) = (line, column) {
                     msg.push_str(&format!(" (at line {}, column {})", line, column));
                 }
+                if let Some(field) = context.as_ref().and_then(|c| c.field.as_ref()) {
+                    msg.push_str(&format!(" (field: {})", field));
+                }
                 msg.push_str("\n\nSuggestion: Check your TOML syntax and ensure all strings are properly quoted.");
                 msg
             }
             ConfigurationError::TomlSerializeError { message } => {
                 format!("Failed to convert configuration to TOML format: {}\n\nSuggestion: Check that all configuration values are serializable.", message)
             }
+            ConfigurationError::ParseError { format, message, line, column, context } => {
+                let mut msg = format!("Configuration file has invalid {} syntax: {}", format, message);
+                if let (Some(line), Some(column)) = (line, column) {
+                    msg.push_str(&format!(" (at line {}, column {})", line, column));
+                }
+                if let Some(field) = context.as_ref().and_then(|c| c.field.as_ref()) {
+                    msg.push_str(&format!(" (field: {})", field));
+                }
+                msg.push_str(&format!("\n\nSuggestion: Check your {} syntax and that each value has the expected type.", format));
+                msg
+            }
+            ConfigurationError::SerializeError { format, message } => {
+                format!("Failed to convert configuration to {} format: {}\n\nSuggestion: Check that all configuration values can be represented in {}.", format, message, format)
+            }
             ConfigurationError::ValidationError { message, field } => {
                 let mut msg = format!("Configuration validation failed: {}", message);
                 if let Some(field) = field {
@@ -145,6 +352,20 @@ impl ConfigurationError {
             ConfigurationError::SaveError { message, path } => {
                 format!("Failed to save configuration to '{}': {}\n\nSuggestion: Check write permissions and ensure the directory exists.", path, message)
             }
+            ConfigurationError::SecretError { message, path, field } => {
+                format!("Failed to read secret '{}' for {}: {}\n\nSuggestion: Ensure the secret file exists and is readable only by the service user (chmod 600).", path, field, message)
+            }
+            ConfigurationError::InsecurePermissions { message, path } => {
+                format!("Configuration file '{}' holds secrets but {}\n\nSuggestion: Restrict it to the service user (chmod 600 and chown to that user).", path, message)
+            }
+            ConfigurationError::FormatDisabled { format } => {
+                format!("{}\n\nSuggestion: Enable the `{}` feature of libelp or convert the file to TOML.", self, format.feature().unwrap_or_default())
+            }
+            ConfigurationError::UnknownKey { path, suggestions } => match suggestions.as_slice() {
+                [] => format!("Unknown configuration key '{}'\n\nSuggestion: Check the key against the configuration template.", path),
+                [one] => format!("Unknown configuration key '{}'\n\nSuggestion: Did you mean '{}'?", path, one),
+                many => format!("Unknown configuration key '{}'\n\nSuggestion: Did you mean one of: {}?", path, many.join(", ")),
+            },
         }
     }
 
@@ -153,10 +374,16 @@ impl ConfigurationError {
         match self {
             ConfigurationError::TomlParseError { .. } => ErrorCategory::Parse,
             ConfigurationError::TomlSerializeError { .. } => ErrorCategory::Serialize,
+            ConfigurationError::ParseError { .. } => ErrorCategory::Parse,
+            ConfigurationError::SerializeError { .. } => ErrorCategory::Serialize,
             ConfigurationError::ValidationError { .. } => ErrorCategory::Validation,
             ConfigurationError::FileError { .. } => ErrorCategory::Io,
             ConfigurationError::LoadError { .. } => ErrorCategory::Io,
             ConfigurationError::SaveError { .. } => ErrorCategory::Io,
+            ConfigurationError::SecretError { .. } => ErrorCategory::Io,
+            ConfigurationError::InsecurePermissions { .. } => ErrorCategory::Io,
+            ConfigurationError::FormatDisabled { .. } => ErrorCategory::Io,
+            ConfigurationError::UnknownKey { .. } => ErrorCategory::Validation,
         }
     }
 }
@@ -164,9 +391,9 @@ impl ConfigurationError {
 /// Error categories for programmatic error handling
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorCategory {
-    /// TOML parsing errors
+    /// TOML (or other format) parsing errors
     Parse,
-    /// TOML serialization errors
+    /// TOML (or other format) serialization errors
     Serialize,
     /// Configuration validation errors
     Validation,
@@ -177,11 +404,24 @@ pub enum ErrorCategory {
 /// Error handling utilities
 pub mod utils {
     use super::*;
+    use crate::config::location::{SourceLocation, field_path_at};
+    use crate::config::persist::SaveOptions;
     use std::fs;
     use std::path::Path;
 
-    /// Load configuration from a TOML file
+    /// Load configuration from a file, choosing the format by extension
+    ///
+    /// Files with an unknown or missing extension are read as TOML.
     pub fn load_from_file<T>(path: impl AsRef<Path>) -> ConfigurationResult<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let path = path.as_ref();
+        load_from_file_as(path, Format::from_path(path).unwrap_or(Format::Toml))
+    }
+
+    /// Load configuration from a file in the given format
+    pub fn load_from_file_as<T>(path: impl AsRef<Path>, format: Format) -> ConfigurationResult<T>
     where
         T: serde::de::DeserializeOwned,
     {
@@ -192,62 +432,114 @@ pub mod utils {
                 path.display().to_string()
             ))?;
 
-        toml::from_str(&content)
-            .map_err(|e| {
-                // Try to extract line and column information from TOML error
-                let (line, column) = extract_toml_error_location(&e);
-                ConfigurationError::toml_parse_error(
-                    e.to_string(),
-                    line,
-                    column
-                )
-            })
+        format
+            .parse(&content)
+            .map_err(|e| e.with_file(path.display().to_string()))
     }
 
-    /// Save configuration to a TOML file
+    /// Save configuration to a file, choosing the format by extension
+    ///
+    /// Files with an unknown or missing extension are written as TOML.
     pub fn save_to_file<T>(config: &T, path: impl AsRef<Path>) -> ConfigurationResult<()>
     where
         T: serde::Serialize,
     {
         let path = path.as_ref();
-        let toml_string = toml::to_string_pretty(config)
-            .map_err(|e| ConfigurationError::toml_serialize_error(e.to_string()))?;
+        save_to_file_as(config, path, Format::from_path(path).unwrap_or(Format::Toml))
+    }
+
+    /// Save configuration to a file in the given format
+    ///
+    /// The file is replaced atomically; see [`SaveOptions`](crate::config::persist::SaveOptions).
+    pub fn save_to_file_as<T>(config: &T, path: impl AsRef<Path>, format: Format) -> ConfigurationResult<()>
+    where
+        T: serde::Serialize,
+    {
+        SaveOptions::new().save_as(config, path, format)
+    }
 
-        fs::write(path, toml_string)
+    /// Load configuration from a file without blocking the async runtime
+    #[cfg(feature = "async")]
+    pub async fn load_from_file_async<T>(path: impl AsRef<Path>) -> ConfigurationResult<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let path = path.as_ref();
+        let content = tokio::fs::read_to_string(path)
+            .await
             .map_err(|e| ConfigurationError::file_error(
-                format!("Failed to write file: {}", e),
+                format!("Failed to read file: {}", e),
                 path.display().to_string()
             ))?;
 
-        Ok(())
+        Format::from_path(path)
+            .unwrap_or(Format::Toml)
+            .parse(&content)
+            .map_err(|e| e.with_file(path.display().to_string()))
     }
 
-    /// Extract line and column information from TOML error
-    fn extract_toml_error_location(error: &toml::de::Error) -> (Option<usize>, Option<usize>) {
-        // TOML errors don't always include line/column info, but we can try to extract it
-        let error_str = error.to_string();
-        
-        // Look for patterns like "line 5 column 10"
-        if let Some(caps) = regex::Regex::new(r"line (\d+) column (\d+)")
-            .unwrap()
-            .captures(&error_str)
-        {
-            if let (Ok(line), Ok(column)) = (caps[1].parse(), caps[2].parse()) {
-                return (Some(line), Some(column));
-            }
+    /// Save configuration to a file without blocking the async runtime
+    ///
+    /// The configuration is serialized before the first `.await`.
+    #[cfg(feature = "async")]
+    pub async fn save_to_file_async<T>(config: &T, path: impl AsRef<Path>) -> ConfigurationResult<()>
+    where
+        T: serde::Serialize,
+    {
+        let path = path.as_ref();
+        let content = Format::from_path(path).unwrap_or(Format::Toml).serialize(config)?;
+        write_file_async(path, content, SaveOptions::new()).await
+    }
+
+    /// Atomically write serialized configuration on tokio's blocking pool
+    #[cfg(feature = "async")]
+    pub async fn write_file_async(path: impl AsRef<Path>, content: String, options: SaveOptions) -> ConfigurationResult<()> {
+        let path = path.as_ref().to_path_buf();
+        run_blocking(move || options.write(&path, content.as_bytes())).await
+    }
+
+    /// Run blocking configuration work on tokio's blocking pool
+    ///
+    /// A panic inside `work` is resumed in the caller.
+    #[cfg(feature = "async")]
+    pub(crate) async fn run_blocking<R, F>(work: F) -> ConfigurationResult<R>
+    where
+        F: FnOnce() -> ConfigurationResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        match tokio::task::spawn_blocking(work).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(ConfigurationError::load_error(format!("Loading task failed: {}", e), "async")),
         }
-        
-        // Look for patterns like "at line 5"
-        if let Some(caps) = regex::Regex::new(r"at line (\d+)")
-            .unwrap()
-            .captures(&error_str)
-        {
-            if let Ok(line) = caps[1].parse() {
-                return (Some(line), None);
-            }
+    }
+
+    /// Convert a TOML deserialization error into a `TomlParseError`
+    ///
+    /// Line, column, excerpt and field path are derived from the error span
+    /// against `source`, the text that was being parsed.
+    pub fn toml_parse_error(error: &toml::de::Error, source: &str) -> ConfigurationError {
+        let message = error.message().trim_end().to_string();
+        let Some(span) = error.span() else {
+            return ConfigurationError::toml_parse_error(message, None, None);
+        };
+
+        let location = SourceLocation::from_span(source, span.clone());
+        let context = ParseContext {
+            file: None,
+            field: field_path_at(source, span.start),
+            expected: None,
+            note: None,
+            excerpt: Some(location.excerpt),
+            width: location.width,
+        };
+
+        ConfigurationError::TomlParseError {
+            message,
+            line: Some(location.line),
+            column: Some(location.column),
+            context: Some(Box::new(context)),
         }
-        
-        (None, None)
     }
 }
 
@@ -279,6 +571,7 @@ mod tests {
         assert_eq!(ConfigurationError::file_error("test", "path").category(), ErrorCategory::Io);
         assert_eq!(ConfigurationError::load_error("load fail", "source").category(), ErrorCategory::Io);
         assert_eq!(ConfigurationError::save_error("save fail", "dest").category(), ErrorCategory::Io);
+        assert_eq!(ConfigurationError::secret_error("unreadable", "/run/secrets/x", "x").category(), ErrorCategory::Io);
     }
 
     #[test]
@@ -0,0 +1,436 @@
+#[cfg(any(feature = "yaml", feature = "json", feature = "json5", feature = "ini"))]
+use crate::config::location::SourceLocation;
+#[cfg(any(feature = "json5", feature = "ini"))]
+use crate::config::template;
+#[cfg(any(feature = "yaml", feature = "json", feature = "json5", feature = "ini"))]
+use crate::error::ParseContext;
+use crate::error::{ConfigurationError, ConfigurationResult, utils};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::path::Path;
+
+/// Configuration file formats
+///
+/// TOML is always available. The other formats are compiled in through the
+/// libelp cargo feature of the same name (`yaml`, `json`, `json5`, `ini`);
+/// using a format whose feature is disabled returns an error instead of
+/// failing to compile, so callers can match on `Format` unconditionally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// TOML (`.toml`)
+    Toml,
+    /// YAML (`.yaml`, `.yml`), feature `yaml`
+    Yaml,
+    /// JSON (`.json`), feature `json`
+    Json,
+    /// JSON5 (`.json5`), feature `json5`
+    Json5,
+    /// INI (`.ini`), feature `ini`; supports scalar values in at most one level of sections
+    Ini,
+}
+
+impl Format {
+    /// All known formats, enabled or not
+    pub const ALL: [Format; 5] = [Format::Toml, Format::Yaml, Format::Json, Format::Json5, Format::Ini];
+
+    /// Display name of the format
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Toml => "TOML",
+            Format::Yaml => "YAML",
+            Format::Json => "JSON",
+            Format::Json5 => "JSON5",
+            Format::Ini => "INI",
+        }
+    }
+
+    /// File extensions recognized for the format (lowercase, without dot)
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Format::Toml => &["toml"],
+            Format::Yaml => &["yaml", "yml"],
+            Format::Json => &["json"],
+            Format::Json5 => &["json5"],
+            Format::Ini => &["ini"],
+        }
+    }
+
+    /// Cargo feature that enables the format (`None` for TOML)
+    pub fn feature(self) -> Option<&'static str> {
+        match self {
+            Format::Toml => None,
+            Format::Yaml => Some("yaml"),
+            Format::Json => Some("json"),
+            Format::Json5 => Some("json5"),
+            Format::Ini => Some("ini"),
+        }
+    }
+
+    /// Whether support for the format is compiled in
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Format::Toml => true,
+            Format::Yaml => cfg!(feature = "yaml"),
+            Format::Json => cfg!(feature = "json"),
+            Format::Json5 => cfg!(feature = "json5"),
+            Format::Ini => cfg!(feature = "ini"),
+        }
+    }
+
+    /// Line comment prefix, or `None` if the format has no comments (JSON)
+    pub fn comment_prefix(self) -> Option<&'static str> {
+        match self {
+            Format::Toml | Format::Yaml => Some("#"),
+            Format::Json5 => Some("//"),
+            Format::Ini => Some(";"),
+            Format::Json => None,
+        }
+    }
+
+    /// Look up a format by file extension (case-insensitive, without dot)
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        let ext = ext.to_ascii_lowercase();
+        Self::ALL.into_iter().find(|f| f.extensions().contains(&ext.as_str()))
+    }
+
+    /// Detect the format from a path's extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        path.as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(Self::from_extension)
+    }
+
+    /// Deserialize a value from source text in this format
+    ///
+    /// Parse errors carry a [`ParseContext`] with the excerpt and, where the
+    /// format allows, the dotted path of the offending field.
+    pub fn parse<T: DeserializeOwned>(self, source: &str) -> ConfigurationResult<T> {
+        match self {
+            Format::Toml => toml::from_str(source).map_err(|e| utils::toml_parse_error(&e, source)),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => parse_yaml(source),
+            #[cfg(feature = "json")]
+            Format::Json => parse_json(source),
+            #[cfg(feature = "json5")]
+            Format::Json5 => parse_json5(source),
+            #[cfg(feature = "ini")]
+            Format::Ini => parse_ini(source),
+            #[allow(unreachable_patterns)]
+            _ => Err(ConfigurationError::FormatDisabled { format: self }),
+        }
+    }
+
+    /// Serialize a value to text in this format
+    pub fn serialize<T: Serialize>(self, value: &T) -> ConfigurationResult<String> {
+        match self {
+            Format::Toml => toml::to_string_pretty(value)
+                .map_err(|e| ConfigurationError::toml_serialize_error(e.to_string())),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_string(value)
+                .map_err(|e| ConfigurationError::serialize_error(self, e.to_string())),
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_string_pretty(value)
+                .map(|s| s + "\n")
+                .map_err(|e| ConfigurationError::serialize_error(self, e.to_string())),
+            #[cfg(any(feature = "json5", feature = "ini"))]
+            Format::Json5 | Format::Ini if self.is_enabled() => {
+                template::render(self, &template::to_table(self, value)?, None, &|_| None, &[])
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(ConfigurationError::FormatDisabled { format: self }),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Build a located `ParseError` from a parser-reported 1-based line/column
+#[cfg(any(feature = "yaml", feature = "json", feature = "json5", feature = "ini"))]
+fn located_error(
+    format: Format,
+    source: &str,
+    message: String,
+    line_column: Option<(usize, usize)>,
+    field: Option<String>,
+) -> ConfigurationError {
+    let Some((line, column)) = line_column else {
+        let mut err = ConfigurationError::parse_error(format, message, None, None);
+        if let ConfigurationError::ParseError { context, .. } = &mut err {
+            *context = field.map(|field| Box::new(ParseContext { field: Some(field), ..Default::default() }));
+        }
+        return err;
+    };
+
+    // Parsers append their own "at line X column Y"; the location is reported separately
+    let message = message
+        .trim_end()
+        .trim_end_matches(&format!(" at line {} column {}", line, column))
+        .to_string();
+    let location = SourceLocation::from_line_column(source, line, column);
+    let context = ParseContext {
+        file: None,
+        field,
+        expected: None,
+        note: None,
+        excerpt: Some(location.excerpt),
+        width: location.width,
+    };
+    ConfigurationError::ParseError {
+        format,
+        message,
+        line: Some(location.line),
+        column: Some(location.column),
+        context: Some(Box::new(context)),
+    }
+}
+
+/// Dotted field path recorded by `serde_path_to_error`, `None` for the root
+pub(crate) fn error_path<E>(error: &serde_path_to_error::Error<E>) -> Option<String> {
+    let path = error.path().to_string();
+    (path != ".").then_some(path)
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml<T: DeserializeOwned>(source: &str) -> ConfigurationResult<T> {
+    serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(source)).map_err(|e| {
+        let field = error_path(&e);
+        let inner = e.into_inner();
+        let line_column = inner.location().map(|l| (l.line(), l.column()));
+        // serde_yaml prefixes nested errors with the path we already report as the field
+        let mut message = inner.to_string();
+        if let Some(prefix) = field.as_deref().map(|f| format!("{}: ", f)) {
+            message = message.strip_prefix(&prefix).map(str::to_string).unwrap_or(message);
+        }
+        located_error(Format::Yaml, source, message, line_column, field)
+    })
+}
+
+#[cfg(feature = "json")]
+fn parse_json<T: DeserializeOwned>(source: &str) -> ConfigurationResult<T> {
+    let located = |e: serde_json::Error, field: Option<String>| {
+        let line_column = (e.line() > 0).then(|| (e.line(), e.column()));
+        located_error(Format::Json, source, e.to_string(), line_column, field)
+    };
+    let mut de = serde_json::Deserializer::from_str(source);
+    let value = serde_path_to_error::deserialize(&mut de).map_err(|e| {
+        let field = error_path(&e);
+        located(e.into_inner(), field)
+    })?;
+    de.end().map_err(|e| located(e, None))?;
+    Ok(value)
+}
+
+#[cfg(feature = "json5")]
+fn parse_json5<T: DeserializeOwned>(source: &str) -> ConfigurationResult<T> {
+    let located = |e: json5::Error, field: Option<String>| {
+        let json5::Error::Message { msg, location } = e;
+        located_error(Format::Json5, source, msg, location.map(|l| (l.line, l.column)), field)
+    };
+    let mut de = json5::Deserializer::from_str(source).map_err(|e| located(e, None))?;
+    serde_path_to_error::deserialize(&mut de).map_err(|e| {
+        let field = error_path(&e);
+        located(e.into_inner(), field)
+    })
+}
+
+#[cfg(feature = "ini")]
+fn parse_ini<T: DeserializeOwned>(source: &str) -> ConfigurationResult<T> {
+    let options = ini::ParseOption { enabled_quote: false, ..Default::default() };
+    let doc = ini::Ini::load_from_str_opt(source, options)
+        .map_err(|e| located_error(Format::Ini, source, e.msg.to_string(), Some((e.line + 1, e.col + 1)), None))?;
+
+    let mut root = toml::Table::new();
+    for (section, props) in doc.iter() {
+        let mut table = toml::Table::new();
+        for (key, value) in props.iter() {
+            table.insert(key.to_string(), ini_value(value));
+        }
+        match section {
+            None => root.extend(table),
+            Some(name) => {
+                root.insert(name.to_string(), toml::Value::Table(table));
+            }
+        }
+    }
+
+    serde_path_to_error::deserialize(toml::Value::Table(root)).map_err(|e| {
+        let field = error_path(&e);
+        let line_column = field.as_deref().and_then(|f| ini_key_location(source, f));
+        located_error(Format::Ini, source, e.into_inner().message().to_string(), line_column, field)
+    })
+}
+
+/// Interpret an INI value: quoted text is a string, otherwise bool/integer/float are inferred
+#[cfg(feature = "ini")]
+fn ini_value(raw: &str) -> toml::Value {
+    let raw = raw.trim();
+    if raw.len() >= 2 && raw.starts_with('"') && raw.ends_with('"') {
+        // Quoted values use the same escapes as TOML basic strings
+        return toml::from_str::<toml::Table>(&format!("v = {}", raw))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .filter(toml::Value::is_str)
+            .unwrap_or_else(|| toml::Value::String(raw[1..raw.len() - 1].to_string()));
+    }
+    if let Ok(b) = raw.parse::<bool>() {
+        toml::Value::Boolean(b)
+    } else if let Ok(i) = raw.parse::<i64>() {
+        toml::Value::Integer(i)
+    } else if let Ok(f) = raw.parse::<f64>() {
+        toml::Value::Float(f)
+    } else {
+        toml::Value::String(raw.to_string())
+    }
+}
+
+/// Find the 1-based line/column of `section.key` (or a top-level `key`) in INI source
+#[cfg(feature = "ini")]
+fn ini_key_location(source: &str, path: &str) -> Option<(usize, usize)> {
+    let (section, key) = match path.split_once('.') {
+        Some((section, key)) => (Some(section), key),
+        None => (None, path),
+    };
+    let mut current: Option<&str> = None;
+    for (index, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            current = Some(name.trim());
+            continue;
+        }
+        if current == section
+            && let Some((k, _)) = trimmed.split_once(['=', ':'])
+            && k.trim() == key
+        {
+            // Point at the value: first non-blank character after the separator
+            let separator = line.find(['=', ':']).unwrap_or(0);
+            let value_start = line[separator + 1..]
+                .find(|c: char| !c.is_whitespace())
+                .map(|i| separator + 1 + i)
+                .unwrap_or(separator);
+            return Some((index + 1, line[..value_start].chars().count() + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Db {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct App {
+        name: String,
+        debug: bool,
+        database: Db,
+    }
+
+    fn sample() -> App {
+        App {
+            name: "eauth".to_string(),
+            debug: true,
+            database: Db { host: "db.local".to_string(), port: 5432 },
+        }
+    }
+
+    #[test]
+    fn test_format_from_path_and_extension() {
+        assert_eq!(Format::from_path("/etc/elerp/eauth.toml"), Some(Format::Toml));
+        assert_eq!(Format::from_path("site.YML"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("site.yaml"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("a.json"), Some(Format::Json));
+        assert_eq!(Format::from_path("a.json5"), Some(Format::Json5));
+        assert_eq!(Format::from_path("a.ini"), Some(Format::Ini));
+        assert_eq!(Format::from_path("a.conf"), None);
+        assert_eq!(Format::from_path("noext"), None);
+    }
+
+    #[test]
+    fn test_toml_roundtrip_and_comment_prefix() {
+        let text = Format::Toml.serialize(&sample()).unwrap();
+        assert_eq!(Format::Toml.parse::<App>(&text).unwrap(), sample());
+        assert_eq!(Format::Toml.comment_prefix(), Some("#"));
+        assert_eq!(Format::Json.comment_prefix(), None);
+        assert!(Format::Toml.is_enabled());
+    }
+
+    #[test]
+    fn test_disabled_format_reports_feature() {
+        for format in Format::ALL.into_iter().filter(|f| !f.is_enabled()) {
+            let err = format.parse::<App>("").unwrap_err();
+            assert!(err.to_string().contains(format.feature().unwrap()), "{}", err);
+            assert!(matches!(err, ConfigurationError::FormatDisabled { format: f } if f == format), "{:?}", err);
+            let err = format.serialize(&sample()).unwrap_err();
+            assert!(matches!(err, ConfigurationError::FormatDisabled { format: f } if f == format), "{:?}", err);
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_roundtrip_and_error_location() {
+        let text = Format::Yaml.serialize(&sample()).unwrap();
+        assert_eq!(Format::Yaml.parse::<App>(&text).unwrap(), sample());
+
+        let src = "name: x\ndebug: false\ndatabase:\n  host: h\n  port: oops\n";
+        let err = Format::Yaml.parse::<App>(src).unwrap_err();
+        let ctx = err.parse_context().unwrap();
+        assert_eq!(ctx.field.as_deref(), Some("database.port"));
+        assert_eq!(ctx.excerpt.as_deref(), Some("  port: oops"));
+        assert!(matches!(err, ConfigurationError::ParseError { format: Format::Yaml, line: Some(5), .. }), "{:?}", err);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_roundtrip_and_error_location() {
+        let text = Format::Json.serialize(&sample()).unwrap();
+        assert_eq!(Format::Json.parse::<App>(&text).unwrap(), sample());
+
+        let src = "{\n  \"name\": \"x\",\n  \"debug\": false,\n  \"database\": { \"host\": \"h\", \"port\": \"oops\" }\n}";
+        let err = Format::Json.parse::<App>(src).unwrap_err();
+        let ctx = err.parse_context().unwrap();
+        assert_eq!(ctx.field.as_deref(), Some("database.port"));
+        // Only a configuration type knows the declared type, see `with_field_meta`
+        assert_eq!(ctx.expected, None);
+        assert!(matches!(err, ConfigurationError::ParseError { format: Format::Json, line: Some(4), .. }), "{:?}", err);
+    }
+
+    #[cfg(feature = "json5")]
+    #[test]
+    fn test_json5_roundtrip_and_syntax_error_location() {
+        let text = Format::Json5.serialize(&sample()).unwrap();
+        assert_eq!(Format::Json5.parse::<App>(&text).unwrap(), sample());
+
+        let src = "{\n  // comment\n  name: 'x',\n  debug: ,\n}";
+        let err = Format::Json5.parse::<App>(src).unwrap_err();
+        assert!(matches!(err, ConfigurationError::ParseError { format: Format::Json5, line: Some(4), .. }), "{:?}", err);
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn test_ini_roundtrip_and_error_location() {
+        let text = Format::Ini.serialize(&sample()).unwrap();
+        assert_eq!(Format::Ini.parse::<App>(&text).unwrap(), sample());
+
+        let src = "name = \"8080\"\ndebug = true\n\n[database]\nhost = h\nport = oops\n";
+        let err = Format::Ini.parse::<App>(src).unwrap_err();
+        let ctx = err.parse_context().unwrap();
+        assert_eq!(ctx.field.as_deref(), Some("database.port"));
+        assert!(matches!(err, ConfigurationError::ParseError { format: Format::Ini, line: Some(6), column: Some(8), .. }), "{:?}", err);
+
+        // Quoted values stay strings even when they look like numbers
+        let ok: App = Format::Ini.parse("name = \"8080\"\ndebug = false\n[database]\nhost = h\nport = 1\n").unwrap();
+        assert_eq!(ok.name, "8080");
+    }
+}
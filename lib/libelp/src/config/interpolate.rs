@@ -0,0 +1,316 @@
+use crate::error::{ConfigurationError, ConfigurationResult};
+use std::collections::HashMap;
+
+/// Prefix selecting an environment variable inside `${...}`
+pub const ENV_PREFIX: &str = "env:";
+
+/// Resolve `${...}` references in every string of a configuration tree
+///
+/// * `${database.host}` is replaced by the value at that dotted key path
+/// * `${env:HOME}` is replaced by the environment variable `HOME`
+/// * `$${` produces a literal `${`
+///
+/// A string consisting of a single key reference takes the referenced value
+/// as is, so `port = "${defaults.port}"` stays an integer. Secrets are read
+/// and decrypted after interpolation: a lone reference copies an encrypted
+/// value or `{ from_file = "..." }` table for that step, and using one inside
+/// a longer string is an error rather than a leak of the ciphertext. Errors are
+/// reported as [`ConfigurationError::LoadError`] whose source is the key path
+/// of the value being interpolated.
+pub fn interpolate(table: &mut toml::Table) -> ConfigurationResult<()> {
+    let mut resolver = Resolver {
+        root: table,
+        done: HashMap::new(),
+        stack: Vec::new(),
+    };
+    let mut resolved = toml::Table::new();
+    for key in table.keys() {
+        resolved.insert(key.clone(), resolver.resolve_key(key)?);
+    }
+    *table = resolved;
+    Ok(())
+}
+
+struct Resolver<'a> {
+    root: &'a toml::Table,
+    done: HashMap<String, toml::Value>,
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// Fully interpolated value at `path`
+    fn resolve_key(&mut self, path: &str) -> ConfigurationResult<toml::Value> {
+        if let Some(value) = self.done.get(path) {
+            return Ok(value.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|p| p == path) {
+            let mut chain = self.stack[start..].to_vec();
+            chain.push(path.to_string());
+            return Err(ConfigurationError::load_error(
+                format!("Interpolation cycle detected: {}", chain.join(" -> ")),
+                path,
+            ));
+        }
+        let raw = lookup(self.root, path).ok_or_else(|| {
+            let referrer = self.stack.last().map(String::as_str).unwrap_or(path);
+            ConfigurationError::load_error(
+                format!("Unresolved reference `${{{}}}` in `{}`", path, referrer),
+                referrer,
+            )
+        })?;
+
+        self.stack.push(path.to_string());
+        let value = self.resolve_value(raw, path)?;
+        self.stack.pop();
+        self.done.insert(path.to_string(), value.clone());
+        Ok(value)
+    }
+
+    fn resolve_value(&mut self, value: &toml::Value, path: &str) -> ConfigurationResult<toml::Value> {
+        match value {
+            toml::Value::String(s) => self.resolve_string(s, path),
+            toml::Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| self.resolve_value(item, &format!("{}[{}]", path, i)))
+                .collect::<ConfigurationResult<Vec<_>>>()
+                .map(toml::Value::Array),
+            toml::Value::Table(table) if lookup(self.root, path).is_some() => {
+                let mut resolved = toml::Table::new();
+                for key in table.keys() {
+                    resolved.insert(key.clone(), self.resolve_key(&format!("{}.{}", path, key))?);
+                }
+                Ok(toml::Value::Table(resolved))
+            }
+            // Tables inside arrays have no key path to look up; their entries resolve in place
+            toml::Value::Table(table) => {
+                let mut resolved = toml::Table::new();
+                for (key, entry) in table {
+                    resolved.insert(key.clone(), self.resolve_value(entry, &format!("{}.{}", path, key))?);
+                }
+                Ok(toml::Value::Table(resolved))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn resolve_string(&mut self, s: &str, path: &str) -> ConfigurationResult<toml::Value> {
+        // A lone reference keeps the referenced value's type
+        if let Some(inner) = s.strip_prefix("${").and_then(|r| r.strip_suffix('}'))
+            && !inner.contains('}')
+        {
+            return self.reference(inner, path);
+        }
+
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find('$') {
+            out.push_str(&rest[..i]);
+            let tail = &rest[i..];
+            if let Some(after) = tail.strip_prefix("$${") {
+                out.push_str("${");
+                rest = after;
+            } else if let Some(after) = tail.strip_prefix("${") {
+                let end = after.find('}').ok_or_else(|| {
+                    ConfigurationError::load_error(format!("Unterminated `${{` in `{}`", path), path)
+                })?;
+                let value = self.reference(&after[..end], path)?;
+                if let Some(kind) = secret_kind(&value) {
+                    return Err(ConfigurationError::load_error(
+                        format!(
+                            "Cannot interpolate {} `{}` into the string `{}`; it is only available after loading",
+                            kind,
+                            after[..end].trim(),
+                            path
+                        ),
+                        path,
+                    ));
+                }
+                match value {
+                    toml::Value::String(value) => out.push_str(&value),
+                    value @ (toml::Value::Table(_) | toml::Value::Array(_)) => {
+                        return Err(ConfigurationError::load_error(
+                            format!(
+                                "Cannot interpolate {} `{}` into the string `{}`",
+                                value.type_str(),
+                                &after[..end],
+                                path
+                            ),
+                            path,
+                        ));
+                    }
+                    value => out.push_str(&value.to_string()),
+                }
+                rest = &after[end + 1..];
+            } else {
+                out.push('$');
+                rest = &tail[1..];
+            }
+        }
+        out.push_str(rest);
+        Ok(toml::Value::String(out))
+    }
+
+    /// Value of a single `${reference}` found in the value at `path`
+    fn reference(&mut self, reference: &str, path: &str) -> ConfigurationResult<toml::Value> {
+        let reference = reference.trim();
+        if let Some(name) = reference.strip_prefix(ENV_PREFIX) {
+            return std::env::var(name).map(toml::Value::String).map_err(|_| {
+                ConfigurationError::load_error(
+                    format!("Environment variable `{}` referenced by `{}` is not set", name, path),
+                    path,
+                )
+            });
+        }
+        if lookup(self.root, reference).is_none() {
+            return Err(ConfigurationError::load_error(
+                format!("Unresolved reference `${{{}}}` in `{}`", reference, path),
+                path,
+            ));
+        }
+        self.resolve_key(reference)
+    }
+}
+
+// What kind of secret `value` holds, if it is resolved only after interpolation
+fn secret_kind(value: &toml::Value) -> Option<&'static str> {
+    if crate::config::secret::from_file_reference(value).is_some() {
+        return Some("the secret file reference");
+    }
+    #[cfg(feature = "encryption")]
+    if value.as_str().is_some_and(crate::config::encryption::is_encrypted) {
+        return Some("the encrypted value");
+    }
+    None
+}
+
+fn lookup<'a>(table: &'a toml::Table, path: &str) -> Option<&'a toml::Value> {
+    let mut parts = path.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(src: &str) -> ConfigurationResult<toml::Table> {
+        let mut table: toml::Table = src.parse().unwrap();
+        interpolate(&mut table)?;
+        Ok(table)
+    }
+
+    fn load_error(err: ConfigurationError) -> (String, String) {
+        match err {
+            ConfigurationError::LoadError { message, config_source } => (message, config_source),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_interpolates_keys_in_any_order() {
+        let table = run(concat!(
+            "url = \"postgres://${database.host}:${database.port}/erp\"\n",
+            "[database]\nhost = \"${defaults.host}\"\nport = 5432\n",
+            "[defaults]\nhost = \"db.local\"\n",
+        ))
+        .unwrap();
+        assert_eq!(table["url"].as_str(), Some("postgres://db.local:5432/erp"));
+        assert_eq!(table["database"]["host"].as_str(), Some("db.local"));
+    }
+
+    #[test]
+    fn test_lone_reference_keeps_type() {
+        let table = run("port = \"${base.port}\"\nports = [\"${base.port}\", 2]\n[base]\nport = 80\n").unwrap();
+        assert_eq!(table["port"].as_integer(), Some(80));
+        assert_eq!(table["ports"].as_array().unwrap()[0].as_integer(), Some(80));
+    }
+
+    #[test]
+    fn test_env_reference() {
+        let home = std::env::var("PATH").unwrap();
+        let table = run("dir = \"${env:PATH}/x\"\n").unwrap();
+        assert_eq!(table["dir"].as_str(), Some(format!("{}/x", home).as_str()));
+
+        let (message, source) = load_error(run("[a]\ndir = \"${env:LIBELP_SURELY_UNSET_VAR}\"\n").unwrap_err());
+        assert!(message.contains("LIBELP_SURELY_UNSET_VAR"), "{}", message);
+        assert_eq!(source, "a.dir");
+    }
+
+    #[test]
+    fn test_escape_and_plain_dollars() {
+        let table = run("a = \"cost $5, literal $${x} and $${env:HOME}\"\n").unwrap();
+        assert_eq!(table["a"].as_str(), Some("cost $5, literal ${x} and ${env:HOME}"));
+    }
+
+    #[test]
+    fn test_unresolved_reference_names_key_path() {
+        let (message, source) = load_error(run("[database]\nurl = \"${database.hots}\"\nhost = \"h\"\n").unwrap_err());
+        assert_eq!(message, "Unresolved reference `${database.hots}` in `database.url`");
+        assert_eq!(source, "database.url");
+    }
+
+    #[test]
+    fn test_tables_inside_arrays() {
+        let table = run(concat!(
+            "list = [{ host = \"${base.host}\", port = 1 }, { host = \"plain\" }]\n",
+            "[base]\nhost = \"h\"\n",
+            "[[servers]]\nurl = \"http://${base.host}\"\n[servers.tls]\ncert = \"${base.host}.pem\"\n",
+            "[[servers]]\nurl = \"plain\"\n",
+        ))
+        .unwrap();
+        assert_eq!(table["list"][0]["host"].as_str(), Some("h"));
+        assert_eq!(table["list"][1]["host"].as_str(), Some("plain"));
+        assert_eq!(table["servers"][0]["url"].as_str(), Some("http://h"));
+        assert_eq!(table["servers"][0]["tls"]["cert"].as_str(), Some("h.pem"));
+        assert_eq!(table["servers"][1]["url"].as_str(), Some("plain"));
+
+        let (message, source) = load_error(run("[[servers]]\nurl = \"${nope}\"\n").unwrap_err());
+        assert_eq!(message, "Unresolved reference `${nope}` in `servers[0].url`");
+        assert_eq!(source, "servers[0].url");
+    }
+
+    #[test]
+    fn test_secrets_are_copied_but_not_embedded() {
+        let table = run("copy = \"${db.password}\"\n[db]\npassword = { from_file = \"pw\" }\n").unwrap();
+        assert_eq!(table["copy"]["from_file"].as_str(), Some("pw"));
+
+        let (message, source) = load_error(run("dsn = \"app:${db.password}@db\"\n[db]\npassword = { from_file = \"pw\" }\n").unwrap_err());
+        assert_eq!(
+            message,
+            "Cannot interpolate the secret file reference `db.password` into the string `dsn`; it is only available after loading"
+        );
+        assert_eq!(source, "dsn");
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_values_are_not_embedded() {
+        let table = run("copy = \"${db.password}\"\n[db]\npassword = \"enc:v1:abc\"\n").unwrap();
+        assert_eq!(table["copy"].as_str(), Some("enc:v1:abc"));
+
+        let (message, _) = load_error(run("dsn = \"app:${db.password}@db\"\n[db]\npassword = \"enc:v1:abc\"\n").unwrap_err());
+        assert!(message.starts_with("Cannot interpolate the encrypted value `db.password` into the string `dsn`"), "{}", message);
+    }
+
+    #[test]
+    fn test_cycle_is_detected() {
+        let (message, _) = load_error(run("a = \"${b}\"\nb = \"x${c}\"\nc = \"${a}\"\n").unwrap_err());
+        assert_eq!(message, "Interpolation cycle detected: a -> b -> c -> a");
+
+        let (message, _) = load_error(run("a = \"${a}\"\n").unwrap_err());
+        assert_eq!(message, "Interpolation cycle detected: a -> a");
+    }
+
+    #[test]
+    fn test_unterminated_and_table_references_fail() {
+        let (message, _) = load_error(run("a = \"x${b\"\nb = 1\n").unwrap_err());
+        assert!(message.starts_with("Unterminated"), "{}", message);
+
+        let (message, _) = load_error(run("a = \"x${t}\"\n[t]\nk = 1\n").unwrap_err());
+        assert!(message.contains("Cannot interpolate table `t`"), "{}", message);
+    }
+}
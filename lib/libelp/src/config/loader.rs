@@ -0,0 +1,1121 @@
+use crate::Configuration;
+use crate::config::format::{Format, error_path};
+#[cfg(feature = "encryption")]
+use crate::config::encryption::{EncryptionKey, decrypt_table};
+use crate::config::interpolate::interpolate;
+use crate::config::location::{SourceLocation, span_of_path};
+use crate::config::meta::check_required;
+use crate::config::permissions::{PermissionPolicy, WarningHandler, check_file};
+use crate::config::secret::{env_secret_files, from_file_reference, read_secret};
+use crate::error::{ConfigurationError, ConfigurationResult, ParseContext};
+#[cfg(feature = "async")]
+use crate::error::utils::run_blocking;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Key listing further files to merge on top of the file that contains it
+///
+/// Accepts a single path or an array of paths; relative paths are resolved
+/// against the directory of the including file and may contain glob patterns.
+pub const INCLUDE_KEY: &str = "include";
+
+/// Table holding named profiles, e.g. `[profile.prod]`
+pub const PROFILE_KEY: &str = "profile";
+
+/// Suffix of the environment variable choosing the profile, e.g. `EAUTH_PROFILE`
+pub const PROFILE_ENV_SUFFIX: &str = "PROFILE";
+
+/// A file that contributed values to a layered configuration
+#[derive(Debug, Clone)]
+pub struct Source {
+    /// Path the file was read from
+    pub path: PathBuf,
+    /// Format the file was parsed as
+    pub format: Format,
+    /// Raw file contents, kept for error locations
+    pub text: String,
+}
+
+/// Merged configuration tree, remembering which source set each value
+#[derive(Debug, Clone, Default)]
+pub struct Layered {
+    /// Merged values, later sources overriding earlier ones
+    pub table: toml::Table,
+    sources: Vec<Source>,
+    origins: HashMap<String, usize>,
+    profile: Option<String>,
+}
+
+impl Layered {
+    /// Sources in the order they were merged
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Source that last set the value at a dotted path (or its closest parent table)
+    pub fn origin(&self, path: &str) -> Option<&Source> {
+        let mut path = path;
+        loop {
+            if let Some(index) = self.origins.get(path) {
+                return self.sources.get(*index);
+            }
+            path = path.rsplit_once('.')?.0;
+        }
+    }
+
+    /// Source that last set exactly the value at a dotted path
+    pub fn source_of(&self, path: &str) -> Option<&Source> {
+        self.origins.get(path).and_then(|index| self.sources.get(*index))
+    }
+
+    /// Deep-merge a parsed source on top of the current values
+    ///
+    /// Tables are merged key by key; any other value replaces the previous one.
+    pub fn merge(&mut self, source: Source, table: toml::Table) {
+        let index = self.sources.len();
+        self.sources.push(source);
+        merge_into(&mut self.table, table, "", index, &mut self.origins);
+    }
+
+    /// Names of the profiles defined under `[profile.*]`
+    pub fn profile_names(&self) -> Vec<String> {
+        match self.table.get(PROFILE_KEY) {
+            Some(toml::Value::Table(profiles)) => profiles.keys().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Remove the `[profile.*]` tables, deep-merging the named one over the base
+    ///
+    /// Values taken from the profile keep pointing at the file that defined
+    /// them. Naming a profile that does not exist is a
+    /// [`ConfigurationError::LoadError`].
+    pub fn select_profile(&mut self, name: Option<&str>) -> ConfigurationResult<()> {
+        let available = self.profile_names();
+        let profiles = self.table.remove(PROFILE_KEY);
+        let Some(name) = name else {
+            return Ok(());
+        };
+        let source = self
+            .origin(PROFILE_KEY)
+            .map(|s| s.path.display().to_string())
+            .unwrap_or_else(|| PROFILE_KEY.to_string());
+        let overrides = match profiles {
+            Some(toml::Value::Table(mut profiles)) => profiles.remove(name),
+            Some(_) => {
+                return Err(ConfigurationError::load_error(
+                    format!("`{}` must be a table of profiles", PROFILE_KEY),
+                    source,
+                ));
+            }
+            None => None,
+        };
+        let overrides = match overrides {
+            Some(toml::Value::Table(overrides)) => overrides,
+            Some(_) => {
+                return Err(ConfigurationError::load_error(
+                    format!("Profile `{}` must be a table", name),
+                    source,
+                ));
+            }
+            None => {
+                let available = if available.is_empty() { "none".to_string() } else { available.join(", ") };
+                return Err(ConfigurationError::load_error(
+                    format!("Unknown profile `{}` (available: {})", name, available),
+                    source,
+                ));
+            }
+        };
+
+        let prefix = format!("{}.{}", PROFILE_KEY, name);
+        let index = self.origins.get(&prefix).copied().unwrap_or(0);
+        let remapped: Vec<(String, usize)> = self
+            .origins
+            .iter()
+            .filter_map(|(path, i)| Some((path.strip_prefix(&prefix)?.strip_prefix('.')?.to_string(), *i)))
+            .collect();
+        merge_into(&mut self.table, overrides, "", index, &mut self.origins);
+        self.origins.extend(remapped);
+        self.profile = Some(prefix);
+        Ok(())
+    }
+
+    /// Resolve `${...}` references in the merged values
+    ///
+    /// See [`interpolate`](crate::config::interpolate::interpolate). Errors
+    /// name the key path and the file that set the offending value.
+    pub fn interpolate(&mut self) -> ConfigurationResult<()> {
+        interpolate(&mut self.table).map_err(|e| match e {
+            ConfigurationError::LoadError { message, config_source } => {
+                let config_source = match self.origin(&config_source) {
+                    Some(source) => source.path.display().to_string(),
+                    None => config_source,
+                };
+                ConfigurationError::LoadError { message, config_source }
+            }
+            other => other,
+        })
+    }
+
+    /// Decrypt `enc:v1:` values in the merged tree
+    ///
+    /// Errors name the key path and the file that set the value; encrypted
+    /// values without a key are an error rather than being passed through.
+    #[cfg(feature = "encryption")]
+    pub fn decrypt(&mut self, key: Option<&EncryptionKey>) -> ConfigurationResult<()> {
+        let mut table = std::mem::take(&mut self.table);
+        let result = decrypt_table(&mut table, key, &|path, message| {
+            let source = self.origin(path).map(|s| s.path.display().to_string()).unwrap_or_else(|| path.to_string());
+            ConfigurationError::load_error(format!("Cannot decrypt `{}`: {}", path, message), source)
+        });
+        self.table = table;
+        result
+    }
+
+    /// Replace `{ from_file = "..." }` tables by the contents of the named files
+    ///
+    /// Relative paths are resolved against the directory of the file that
+    /// contains the reference. See [`read_secret`] for trimming and permissions.
+    pub fn resolve_secrets(&mut self) -> ConfigurationResult<()> {
+        let mut table = std::mem::take(&mut self.table);
+        let result = self.resolve_secrets_in(&mut table, "");
+        self.table = table;
+        result
+    }
+
+    fn resolve_secrets_in(&self, table: &mut toml::Table, prefix: &str) -> ConfigurationResult<()> {
+        for (key, value) in table.iter_mut() {
+            let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            if let Some(file) = from_file_reference(value).map(PathBuf::from) {
+                let file = match self.origin(&path).and_then(|s| s.path.parent()) {
+                    Some(dir) if file.is_relative() => dir.join(file),
+                    _ => file,
+                };
+                *value = toml::Value::String(read_secret(&file, &path)?);
+            } else if let toml::Value::Table(sub) = value {
+                self.resolve_secrets_in(sub, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge secrets named by `<PREFIX>_..._FILE` environment variables
+    ///
+    /// `EAUTH_DATABASE__PASSWORD_FILE=/run/secrets/db_pw` with prefix `EAUTH`
+    /// sets `database.password`, overriding any value from files.
+    pub fn apply_env_secrets(&mut self, prefix: &str) -> ConfigurationResult<()> {
+        for (_, path, file) in env_secret_files(prefix, std::env::vars()) {
+            let mut value = toml::Value::String(read_secret(Path::new(&file), &path)?);
+            for part in path.rsplit('.') {
+                let mut table = toml::Table::new();
+                table.insert(part.to_string(), value);
+                value = toml::Value::Table(table);
+            }
+            if let toml::Value::Table(table) = value {
+                let source = Source { path: PathBuf::from(file), format: Format::Toml, text: String::new() };
+                self.merge(source, table);
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserialize the merged values into a configuration type
+    ///
+    /// Absent keys take their defaults; absent `#[config(required)]` keys
+    /// are a validation error.
+    /// Type errors are attributed to the source file that provided the
+    /// offending value, with an excerpt when that file is TOML.
+    pub fn deserialize<T: Configuration>(&self) -> ConfigurationResult<T> {
+        let value = toml::Value::Table(self.table.clone());
+        check_required::<T>(&value)?;
+        serde_path_to_error::deserialize(value)
+            .map_err(|e| {
+                let field = error_path(&e);
+                self.locate(e.into_inner().message().to_string(), field)
+            })
+            .map_err(|e| e.with_field_meta::<T>())
+    }
+
+    /// Build a parse error for `field`, located in the source that set it
+    pub fn locate(&self, message: String, field: Option<String>) -> ConfigurationError {
+        let source = match &field {
+            Some(field) => self.origin(field),
+            None => None,
+        };
+        let Some(source) = source else {
+            let context = ParseContext { field, ..Default::default() };
+            return ConfigurationError::TomlParseError {
+                message,
+                line: None,
+                column: None,
+                context: Some(Box::new(context)),
+            };
+        };
+
+        let location = match (source.format, &field) {
+            (Format::Toml, Some(field)) => self
+                .profile
+                .as_ref()
+                .and_then(|prefix| span_of_path(&source.text, &format!("{}.{}", prefix, field)))
+                .or_else(|| span_of_path(&source.text, field))
+                .map(|span| SourceLocation::from_span(&source.text, span)),
+            _ => None,
+        };
+        let (line, column) = match &location {
+            Some(l) => (Some(l.line), Some(l.column)),
+            None => (None, None),
+        };
+        let context = Box::new(ParseContext {
+            file: Some(source.path.display().to_string()),
+            field,
+            expected: None,
+            note: None,
+            width: location.as_ref().map(|l| l.width).unwrap_or(1),
+            excerpt: location.map(|l| l.excerpt),
+        });
+        match source.format {
+            Format::Toml => ConfigurationError::TomlParseError { message, line, column, context: Some(context) },
+            format => ConfigurationError::ParseError { format, message, line, column, context: Some(context) },
+        }
+    }
+}
+
+fn merge_into(
+    target: &mut toml::Table,
+    source: toml::Table,
+    prefix: &str,
+    index: usize,
+    origins: &mut HashMap<String, usize>,
+) {
+    for (key, value) in source {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        origins.insert(path.clone(), index);
+        match (target.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(incoming)) => {
+                merge_into(existing, incoming, &path, index, origins);
+            }
+            (_, value) => {
+                if let toml::Value::Table(incoming) = &value {
+                    record_origins(incoming, &path, index, origins);
+                }
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_origins(table: &toml::Table, prefix: &str, index: usize, origins: &mut HashMap<String, usize>) {
+    for (key, value) in table {
+        let path = format!("{}.{}", prefix, key);
+        if let toml::Value::Table(sub) = value {
+            record_origins(sub, &path, index, origins);
+        }
+        origins.insert(path, index);
+    }
+}
+
+/// Loader merging a main file, its `include`s and fragment directories
+///
+/// ```rust,no_run
+/// use libelp::{ConfigLoader, Configuration, ConfigurationResult};
+///
+/// #[derive(Configuration)]
+/// struct AppConfig {
+///     #[config(default = 8080, note = "Server port")]
+///     port: u16,
+/// }
+///
+/// fn load() -> ConfigurationResult<AppConfig> {
+///     ConfigLoader::new()
+///         .file("/etc/elerp/eauth.toml")
+///         .includes(true)
+///         .fragments_dir("/etc/elerp/eauth.d")
+///         .load()
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    files: Vec<(PathBuf, Option<Format>)>,
+    fragment_dirs: Vec<PathBuf>,
+    includes: bool,
+    interpolation: bool,
+    env_prefix: Option<String>,
+    profile: Option<String>,
+    permissions: PermissionPolicy,
+    warnings: WarningHandler,
+    #[cfg(feature = "encryption")]
+    key: Option<EncryptionKey>,
+    #[cfg(feature = "encryption")]
+    key_file: Option<PathBuf>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigLoader {
+    /// Create a loader with no sources; `${...}` processing is enabled, `include` processing is not
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            fragment_dirs: Vec::new(),
+            includes: false,
+            interpolation: true,
+            env_prefix: None,
+            profile: None,
+            permissions: PermissionPolicy::default(),
+            warnings: WarningHandler::default(),
+            #[cfg(feature = "encryption")]
+            key: None,
+            #[cfg(feature = "encryption")]
+            key_file: None,
+        }
+    }
+
+    /// Add a file, its format chosen by extension (TOML if unknown)
+    ///
+    /// Files are merged in the order they are added; the file must exist.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.files.push((path.as_ref().to_path_buf(), None));
+        self
+    }
+
+    /// Add a file in an explicitly chosen format
+    pub fn file_as(mut self, path: impl AsRef<Path>, format: Format) -> Self {
+        self.files.push((path.as_ref().to_path_buf(), Some(format)));
+        self
+    }
+
+    /// Merge every supported file in `dir`, in lexical order of file names
+    ///
+    /// Fragments are applied after all files added with [`ConfigLoader::file`].
+    /// A missing directory is not an error, so packages can ship without one.
+    pub fn fragments_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.fragment_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Enable or disable processing of the `include` key (disabled by default)
+    ///
+    /// When enabled, a top-level `include` key is taken out of every file and
+    /// names the files merged on top of it, so a configuration field named
+    /// `include` at the top level never sees its value.
+    pub fn includes(mut self, enabled: bool) -> Self {
+        self.includes = enabled;
+        self
+    }
+
+    /// Enable or disable `${...}` interpolation of string values (enabled by default)
+    pub fn interpolation(mut self, enabled: bool) -> Self {
+        self.interpolation = enabled;
+        self
+    }
+
+    /// Prefix of environment variables read by the loader, e.g. `EAUTH`
+    ///
+    /// Enables the `EAUTH_DATABASE__PASSWORD_FILE` secret file convention
+    /// and profile selection through `EAUTH_PROFILE`.
+    pub fn env_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// How to treat exposed files when the loaded type has secret fields
+    ///
+    /// Defaults to [`PermissionPolicy::Warn`]; see [`check_file`] for what is checked.
+    pub fn permissions(mut self, policy: PermissionPolicy) -> Self {
+        self.permissions = policy;
+        self
+    }
+
+    /// Receive warnings, which are dropped otherwise
+    pub fn on_warning(mut self, handler: impl Fn(&ConfigurationError) + Send + Sync + 'static) -> Self {
+        self.warnings = WarningHandler::new(handler);
+        self
+    }
+
+    /// Key used to decrypt `enc:v1:` values
+    #[cfg(feature = "encryption")]
+    pub fn encryption_key(mut self, key: EncryptionKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Key file used to decrypt `enc:v1:` values, read at load time
+    #[cfg(feature = "encryption")]
+    pub fn key_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.key_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Apply the `[profile.<name>]` overrides when loading
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    /// Choose the profile from `--profile <name>` or `--profile=<name>`, if present
+    ///
+    /// ```rust,no_run
+    /// # use libelp::ConfigLoader;
+    /// let loader = ConfigLoader::new()
+    ///     .file("/etc/elerp/eauth.toml")
+    ///     .env_prefix("EAUTH")
+    ///     .profile_from_args(std::env::args());
+    /// ```
+    pub fn profile_from_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            if let Some(name) = arg.strip_prefix("--profile=") {
+                self.profile = Some(name.to_string());
+            } else if arg == "--profile"
+                && let Some(name) = args.next()
+            {
+                self.profile = Some(name.as_ref().to_string());
+            }
+        }
+        self
+    }
+
+    /// Read and merge all sources without deserializing them
+    pub fn load_layers(&self) -> ConfigurationResult<Layered> {
+        let mut layered = Layered::default();
+        for (path, format) in &self.files {
+            self.load_file(path, *format, &mut layered, &mut Vec::new())?;
+        }
+        for dir in &self.fragment_dirs {
+            for path in fragment_files(dir)? {
+                self.load_file(&path, None, &mut layered, &mut Vec::new())?;
+            }
+        }
+        Ok(layered)
+    }
+
+    /// Name of the profile [`ConfigLoader::load`] will apply, if any
+    ///
+    /// A profile chosen with [`ConfigLoader::profile`] or
+    /// [`ConfigLoader::profile_from_args`] wins over `<PREFIX>_PROFILE`.
+    pub fn active_profile(&self) -> Option<String> {
+        self.profile.clone().or_else(|| {
+            let prefix = self.env_prefix.as_ref()?;
+            std::env::var(format!("{}_{}", prefix, PROFILE_ENV_SUFFIX)).ok().filter(|p| !p.is_empty())
+        })
+    }
+
+    /// Profiles defined under `[profile.*]` across all sources
+    pub fn profile_names(&self) -> ConfigurationResult<Vec<String>> {
+        Ok(self.load_layers()?.profile_names())
+    }
+
+    /// Read, merge and deserialize all sources
+    ///
+    /// The active profile is merged over the base values first. Interpolation
+    /// then runs on the merged tree, so references may point at values set by
+    /// any file. Encrypted values and secret files are resolved afterwards, so
+    /// their contents are taken verbatim; a reference may copy one as a whole,
+    /// but placing one inside a longer string fails. Relative `PathBuf` values are
+    /// resolved against the directory of the file that set them.
+    pub fn load<T: Configuration>(&self) -> ConfigurationResult<T> {
+        self.load_profile(self.active_profile().as_deref())
+    }
+
+    /// Like [`ConfigLoader::load`], applying the given profile (or none)
+    pub fn load_profile<T: Configuration>(&self, profile: Option<&str>) -> ConfigurationResult<T> {
+        let mut layered = self.load_layers()?;
+        if T::has_secrets() {
+            self.check_permissions(&layered)?;
+        }
+        layered.select_profile(profile)?;
+        if self.interpolation {
+            layered.interpolate()?;
+        }
+        #[cfg(feature = "encryption")]
+        {
+            let loaded = self.key_file.as_ref().map(EncryptionKey::load).transpose()?;
+            layered.decrypt(self.key.as_ref().or(loaded.as_ref()))?;
+        }
+        layered.resolve_secrets()?;
+        if let Some(prefix) = &self.env_prefix {
+            layered.apply_env_secrets(prefix)?;
+        }
+        let mut config: T = layered.deserialize().map_err(|e| e.with_field_meta::<T>())?;
+        config.resolve_paths(&|key| {
+            layered
+                .source_of(key)
+                .and_then(|source| source.path.parent())
+                .map(Path::to_path_buf)
+        })?;
+        Ok(config)
+    }
+
+    /// Load and validate every profile, for checking all of them in CI
+    ///
+    /// Fails only when the sources themselves cannot be read; each profile
+    /// gets its own result so every broken profile can be reported at once.
+    pub fn check_profiles<T: Configuration>(&self) -> ConfigurationResult<Vec<(String, ConfigurationResult<T>)>> {
+        Ok(self
+            .profile_names()?
+            .into_iter()
+            .map(|name| {
+                let result = self.load_profile::<T>(Some(&name)).and_then(|config| {
+                    config.validate()?;
+                    Ok(config)
+                });
+                (name, result)
+            })
+            .collect())
+    }
+
+    /// [`ConfigLoader::load`] on tokio's blocking pool
+    ///
+    /// Reading includes and fragment directories, decryption and secret files
+    /// all happen off the runtime's worker threads.
+    #[cfg(feature = "async")]
+    pub async fn load_async<T>(&self) -> ConfigurationResult<T>
+    where
+        T: Configuration + Send + 'static,
+    {
+        let loader = self.clone();
+        run_blocking(move || loader.load()).await
+    }
+
+    /// [`ConfigLoader::load_profile`] on tokio's blocking pool
+    #[cfg(feature = "async")]
+    pub async fn load_profile_async<T>(&self, profile: Option<&str>) -> ConfigurationResult<T>
+    where
+        T: Configuration + Send + 'static,
+    {
+        let loader = self.clone();
+        let profile = profile.map(str::to_string);
+        run_blocking(move || loader.load_profile(profile.as_deref())).await
+    }
+
+    // Apply the permission policy to every file that was read
+    fn check_permissions(&self, layered: &Layered) -> ConfigurationResult<()> {
+        if self.permissions == PermissionPolicy::Ignore {
+            return Ok(());
+        }
+        for source in layered.sources() {
+            match check_file(&source.path) {
+                Err(e @ ConfigurationError::InsecurePermissions { .. }) if self.permissions == PermissionPolicy::Warn => {
+                    self.warnings.warn(&e)
+                }
+                other => other?,
+            }
+        }
+        Ok(())
+    }
+
+    fn load_file(
+        &self,
+        path: &Path,
+        format: Option<Format>,
+        layered: &mut Layered,
+        stack: &mut Vec<PathBuf>,
+    ) -> ConfigurationResult<()> {
+        let display = path.display().to_string();
+        let canonical = fs::canonicalize(path)
+            .map_err(|e| ConfigurationError::file_error(format!("Failed to read file: {}", e), &display))?;
+        if let Some(start) = stack.iter().position(|p| p == &canonical) {
+            let chain: Vec<String> = stack[start..]
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(ConfigurationError::load_error(
+                format!("Include cycle detected: {}", chain.join(" -> ")),
+                display,
+            ));
+        }
+
+        let format = format.or_else(|| Format::from_path(path)).unwrap_or(Format::Toml);
+        let text = fs::read_to_string(path)
+            .map_err(|e| ConfigurationError::file_error(format!("Failed to read file: {}", e), &display))?;
+        let mut table: toml::Table = format.parse(&text).map_err(|e| e.with_file(&display))?;
+
+        let includes = if self.includes {
+            take_includes(&mut table, path)?
+        } else {
+            Vec::new()
+        };
+        layered.merge(Source { path: path.to_path_buf(), format, text }, table);
+
+        stack.push(canonical);
+        for include in includes {
+            self.load_file(&include, None, layered, stack)?;
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+/// Remove the `include` key from a parsed file and expand it to file paths
+fn take_includes(table: &mut toml::Table, path: &Path) -> ConfigurationResult<Vec<PathBuf>> {
+    let display = path.display().to_string();
+    let invalid = || {
+        ConfigurationError::load_error(
+            format!("`{}` must be a string or an array of strings", INCLUDE_KEY),
+            &display,
+        )
+    };
+    let patterns: Vec<String> = match table.remove(INCLUDE_KEY) {
+        None => return Ok(Vec::new()),
+        Some(toml::Value::String(s)) => vec![s],
+        Some(toml::Value::Array(items)) => items
+            .into_iter()
+            .map(|v| match v {
+                toml::Value::String(s) => Ok(s),
+                _ => Err(invalid()),
+            })
+            .collect::<ConfigurationResult<_>>()?,
+        Some(_) => return Err(invalid()),
+    };
+
+    let base = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let full = base.join(&pattern);
+        if !pattern.contains(['*', '?', '[']) {
+            files.push(full);
+            continue;
+        }
+        let mut matched: Vec<PathBuf> = glob::glob(&full.to_string_lossy())
+            .map_err(|e| ConfigurationError::load_error(format!("Invalid include pattern '{}': {}", pattern, e), &display))?
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file())
+            .collect();
+        matched.sort();
+        files.extend(matched);
+    }
+    Ok(files)
+}
+
+/// Files in `dir` with an enabled format's extension, sorted by file name
+fn fragment_files(dir: &Path) -> ConfigurationResult<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(dir).map_err(|e| {
+        ConfigurationError::file_error(format!("Failed to read directory: {}", e), dir.display().to_string())
+    })?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|p| p.is_file() && Format::from_path(p).is_some_and(Format::is_enabled))
+        .collect();
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_util::{ENV_LOCK, temp_dir};
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct Db {
+        #[config(default = "localhost", note = "db host")]
+        host: String,
+        #[config(default = 5432, note = "db port")]
+        port: u16,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct App {
+        #[config(default = "eauth", note = "service name")]
+        name: String,
+        database: Db,
+    }
+
+    const MAIN: &str = "name = \"main\"\n\n[database]\nhost = \"db\"\nport = 5432\n";
+
+    #[test]
+    fn test_fragments_dir_merges_in_lexical_order() {
+        let dir = temp_dir("libelp_loader_fragments");
+        fs::write(dir.join("eauth.toml"), MAIN).unwrap();
+        let frag = dir.join("eauth.d");
+        fs::create_dir_all(&frag).unwrap();
+        fs::write(frag.join("20-site.toml"), "[database]\nport = 7000\n").unwrap();
+        fs::write(frag.join("10-base.toml"), "name = \"base\"\n[database]\nport = 6000\n").unwrap();
+        fs::write(frag.join("README"), "ignored").unwrap();
+
+        let app: App = ConfigLoader::new().file(dir.join("eauth.toml")).fragments_dir(&frag).load().unwrap();
+        assert_eq!(app.name, "base");
+        assert_eq!(app.database.host, "db");
+        assert_eq!(app.database.port, 7000);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_missing_fragments_dir_is_ignored() {
+        let dir = temp_dir("libelp_loader_nofrag");
+        fs::write(dir.join("eauth.toml"), MAIN).unwrap();
+        let app: App = ConfigLoader::new()
+            .file(dir.join("eauth.toml"))
+            .fragments_dir(dir.join("missing.d"))
+            .load()
+            .unwrap();
+        assert_eq!(app.database.port, 5432);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_with_glob_and_relative_paths() {
+        let dir = temp_dir("libelp_loader_include");
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        fs::write(dir.join("main.toml"), format!("include = [\"conf.d/*.toml\", \"extra.toml\"]\n{}", MAIN)).unwrap();
+        fs::write(dir.join("conf.d/b.toml"), "[database]\nhost = \"b\"\n").unwrap();
+        fs::write(dir.join("conf.d/a.toml"), "[database]\nhost = \"a\"\nport = 1\n").unwrap();
+        fs::write(dir.join("extra.toml"), "name = \"extra\"\n").unwrap();
+
+        let app: App = ConfigLoader::new().file(dir.join("main.toml")).includes(true).load().unwrap();
+        assert_eq!(app.name, "extra");
+        assert_eq!(app.database.host, "b");
+        assert_eq!(app.database.port, 1);
+
+        let layered = ConfigLoader::new().file(dir.join("main.toml")).includes(true).load_layers().unwrap();
+        assert_eq!(layered.sources().len(), 4);
+        assert!(layered.origin("database.port").unwrap().path.ends_with("conf.d/a.toml"));
+        assert!(!layered.table.contains_key(INCLUDE_KEY));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Mailer {
+        #[config(default = "", note = "header copied into every mail")]
+        include: String,
+    }
+
+    #[test]
+    fn test_include_key_is_a_plain_field_unless_enabled() {
+        let dir = temp_dir("libelp_loader_include_field");
+        fs::write(dir.join("mailer.toml"), "include = \"Reply-To\"\n").unwrap();
+        let mailer = Mailer::load_from_file(dir.join("mailer.toml")).unwrap();
+        assert_eq!(mailer.include, "Reply-To");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_cycle_is_reported() {
+        let dir = temp_dir("libelp_loader_cycle");
+        fs::write(dir.join("a.toml"), "include = \"b.toml\"\n").unwrap();
+        fs::write(dir.join("b.toml"), "include = [\"a.toml\"]\n").unwrap();
+
+        let err = ConfigLoader::new().file(dir.join("a.toml")).includes(true).load_layers().unwrap_err();
+        match err {
+            ConfigurationError::LoadError { message, .. } => {
+                assert!(message.contains("Include cycle detected"), "{}", message);
+                assert!(message.contains("a.toml -> ") && message.contains("b.toml -> "), "{}", message);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_invalid_include_value_and_missing_include() {
+        let dir = temp_dir("libelp_loader_badinc");
+        fs::write(dir.join("a.toml"), "include = 3\n").unwrap();
+        let err = ConfigLoader::new().file(dir.join("a.toml")).includes(true).load_layers().unwrap_err();
+        assert!(matches!(err, ConfigurationError::LoadError { .. }), "{:?}", err);
+
+        fs::write(dir.join("b.toml"), "include = \"nope.toml\"\n").unwrap();
+        let err = ConfigLoader::new().file(dir.join("b.toml")).includes(true).load_layers().unwrap_err();
+        assert!(matches!(err, ConfigurationError::FileError { ref path, .. } if path.ends_with("nope.toml")), "{:?}", err);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_type_error_names_fragment_file_and_location() {
+        let dir = temp_dir("libelp_loader_errloc");
+        fs::write(dir.join("eauth.toml"), MAIN).unwrap();
+        let frag = dir.join("eauth.d");
+        fs::create_dir_all(&frag).unwrap();
+        fs::write(frag.join("50-site.toml"), "# site override\n[database]\nport = \"oops\"\n").unwrap();
+
+        let err = ConfigLoader::new()
+            .file(dir.join("eauth.toml"))
+            .fragments_dir(&frag)
+            .load::<App>()
+            .unwrap_err();
+        let ctx = err.parse_context().expect("parse context");
+        assert!(ctx.file.as_deref().unwrap().ends_with("50-site.toml"), "{:?}", ctx);
+        assert_eq!(ctx.field.as_deref(), Some("database.port"));
+        assert_eq!(ctx.note.as_deref(), Some("db port"));
+        assert_eq!(ctx.excerpt.as_deref(), Some("port = \"oops\""));
+        assert!(matches!(err, ConfigurationError::TomlParseError { line: Some(3), column: Some(8), .. }), "{:?}", err);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_syntax_error_in_fragment_names_file() {
+        let dir = temp_dir("libelp_loader_syntax");
+        fs::write(dir.join("eauth.toml"), MAIN).unwrap();
+        fs::write(dir.join("10-bad.toml"), "[database\n").unwrap();
+        let err = ConfigLoader::new()
+            .file(dir.join("eauth.toml"))
+            .fragments_dir(&dir)
+            .load::<App>()
+            .unwrap_err();
+        let ctx = err.parse_context().expect("parse context");
+        assert!(ctx.file.as_deref().unwrap().ends_with("10-bad.toml"), "{:?}", ctx);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_interpolation_across_fragments_and_errors_name_file() {
+        let dir = temp_dir("libelp_loader_interp");
+        fs::write(dir.join("eauth.toml"), "name = \"${database.host}:${database.port}\"\n[database]\nhost = \"db\"\nport = 1\n").unwrap();
+        let frag = dir.join("eauth.d");
+        fs::create_dir_all(&frag).unwrap();
+        fs::write(frag.join("10-port.toml"), "[database]\nport = \"${ports.db}\"\n[ports]\ndb = 6543\n").unwrap();
+
+        let loader = ConfigLoader::new().file(dir.join("eauth.toml")).fragments_dir(&frag);
+        let app: App = loader.load().unwrap();
+        assert_eq!(app.name, "db:6543");
+        assert_eq!(app.database.port, 6543);
+
+        fs::write(frag.join("20-bad.toml"), "[database]\nhost = \"${databse.host}\"\n").unwrap();
+        match loader.load::<App>().unwrap_err() {
+            ConfigurationError::LoadError { message, config_source } => {
+                assert!(message.contains("`database.host`"), "{}", message);
+                assert!(config_source.ends_with("20-bad.toml"), "{}", config_source);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        fs::write(dir.join("raw.toml"), "name = \"$${x}\"\n[database]\nhost = \"${y}\"\nport = 1\n").unwrap();
+        let raw: App = ConfigLoader::new()
+            .file(dir.join("raw.toml"))
+            .interpolation(false)
+            .load()
+            .unwrap();
+        assert_eq!(raw.name, "$${x}");
+        assert_eq!(raw.database.host, "${y}");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_interpolation_inside_arrays_of_tables() {
+        let dir = temp_dir("libelp_loader_arrays");
+        let plain = concat!(
+            "list = [{ host = \"h\" }]\n",
+            "[database]\nhost = \"db\"\nport = 1\n",
+            "[[servers]]\nhost = \"a\"\n[[servers]]\nhost = \"b\"\n",
+        );
+        fs::write(dir.join("plain.toml"), plain).unwrap();
+        let app: App = ConfigLoader::new().file(dir.join("plain.toml")).load().unwrap();
+        assert_eq!(app.database.host, "db");
+
+        let referencing = concat!(
+            "name = \"${database.host}\"\n",
+            "list = [{ host = \"${database.host}:${database.port}\" }]\n",
+            "[database]\nhost = \"db\"\nport = 1\n",
+            "[[servers]]\nhost = \"${database.host}-a\"\n",
+        );
+        fs::write(dir.join("eauth.toml"), referencing).unwrap();
+        let loader = ConfigLoader::new().file(dir.join("eauth.toml"));
+        let app: App = loader.load().unwrap();
+        assert_eq!(app.name, "db");
+        let mut layered = loader.load_layers().unwrap();
+        layered.interpolate().unwrap();
+        assert_eq!(layered.table["list"][0]["host"].as_str(), Some("db:1"));
+        assert_eq!(layered.table["servers"][0]["host"].as_str(), Some("db-a"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn private_file(path: &Path, content: &str) {
+        fs::write(path, content).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_from_file_secrets_resolve_relative_to_config() {
+        let dir = temp_dir("libelp_loader_secret");
+        fs::create_dir_all(dir.join("secrets")).unwrap();
+        private_file(&dir.join("secrets/db_host"), "db.internal\n");
+        fs::write(
+            dir.join("eauth.toml"),
+            "name = { from_file = \"${env:LIBELP_LOADER_SECRET_DIR}/name\" }\n[database]\nhost = { from_file = \"secrets/db_host\" }\nport = 1\n",
+        )
+        .unwrap();
+        private_file(&dir.join("name"), "  named  ");
+        let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: environment changes are serialized by `ENV_LOCK`
+        unsafe { std::env::set_var("LIBELP_LOADER_SECRET_DIR", &dir) };
+
+        let app: App = ConfigLoader::new().file(dir.join("eauth.toml")).load().unwrap();
+        assert_eq!(app.name, "named");
+        assert_eq!(app.database.host, "db.internal");
+
+        fs::remove_file(dir.join("secrets/db_host")).unwrap();
+        match ConfigLoader::new().file(dir.join("eauth.toml")).load::<App>().unwrap_err() {
+            ConfigurationError::SecretError { path, field, .. } => {
+                assert!(path.ends_with("secrets/db_host"), "{}", path);
+                assert_eq!(field, "database.host");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_env_file_convention_overrides_files() {
+        let dir = temp_dir("libelp_loader_envsecret");
+        fs::write(dir.join("eauth.toml"), MAIN).unwrap();
+        private_file(&dir.join("host"), "from-env\n");
+        let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: environment changes are serialized by `ENV_LOCK`
+        unsafe { std::env::set_var("LIBELPLOADERTEST_DATABASE__HOST_FILE", dir.join("host")) };
+
+        let loader = ConfigLoader::new().file(dir.join("eauth.toml"));
+        assert_eq!(loader.load::<App>().unwrap().database.host, "db");
+        let app: App = loader.clone().env_prefix("LIBELPLOADERTEST").load().unwrap();
+        assert_eq!(app.database.host, "from-env");
+        assert_eq!(app.database.port, 5432);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir.join("host"), fs::Permissions::from_mode(0o644)).unwrap();
+            let err = loader.env_prefix("LIBELPLOADERTEST").load::<App>().unwrap_err();
+            assert!(matches!(err, ConfigurationError::SecretError { ref field, .. } if field == "database.host"), "{:?}", err);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_values_are_decrypted_with_key_file() {
+        let dir = temp_dir("libelp_loader_enc");
+        let key = EncryptionKey::generate();
+        key.save(dir.join("eauth.key")).unwrap();
+        fs::write(
+            dir.join("eauth.toml"),
+            format!("name = \"eauth\"\n[database]\nhost = \"{}\"\nport = 1\n", key.encrypt("db.internal")),
+        )
+        .unwrap();
+
+        let loader = ConfigLoader::new().file(dir.join("eauth.toml"));
+        let app: App = loader.clone().key_file(dir.join("eauth.key")).load().unwrap();
+        assert_eq!(app.database.host, "db.internal");
+
+        match loader.clone().load::<App>().unwrap_err() {
+            ConfigurationError::LoadError { message, config_source } => {
+                assert!(message.contains("`database.host`") && message.contains("no decryption key"), "{}", message);
+                assert!(config_source.ends_with("eauth.toml"), "{}", config_source);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let err = loader.clone().encryption_key(EncryptionKey::generate()).load::<App>().unwrap_err();
+        assert!(err.to_string().contains("wrong key"), "{}", err);
+
+        // A copied reference is decrypted too; the ciphertext never lands in a longer string
+        let password = key.encrypt("s3cret");
+        fs::write(
+            dir.join("eauth.toml"),
+            format!("name = \"${{database.host}}\"\n[database]\nhost = \"{}\"\nport = 1\n", password),
+        )
+        .unwrap();
+        let app: App = loader.clone().key_file(dir.join("eauth.key")).load().unwrap();
+        assert_eq!(app.name, "s3cret");
+        fs::write(
+            dir.join("eauth.toml"),
+            format!("name = \"app:${{database.host}}\"\n[database]\nhost = \"{}\"\nport = 1\n", password),
+        )
+        .unwrap();
+        let err = loader.key_file(dir.join("eauth.key")).load::<App>().unwrap_err();
+        assert!(err.to_string().contains("Cannot interpolate the encrypted value `database.host` into the string `name`"), "{}", err);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    const PROFILES: &str = concat!(
+        "name = \"eauth\"\n[database]\nhost = \"localhost\"\nport = 5432\n\n",
+        "[profile.dev.database]\nport = 15432\n\n",
+        "[profile.prod]\nname = \"eauth-prod\"\n[profile.prod.database]\nhost = \"db.prod\"\n",
+    );
+
+    #[test]
+    fn test_profile_is_merged_over_base() {
+        let dir = temp_dir("libelp_loader_profile");
+        fs::write(dir.join("eauth.toml"), PROFILES).unwrap();
+        let loader = ConfigLoader::new().file(dir.join("eauth.toml"));
+
+        let base: App = loader.load().unwrap();
+        assert_eq!((base.name.as_str(), base.database.host.as_str(), base.database.port), ("eauth", "localhost", 5432));
+
+        let dev: App = loader.clone().profile("dev").load().unwrap();
+        assert_eq!((dev.name.as_str(), dev.database.host.as_str(), dev.database.port), ("eauth", "localhost", 15432));
+
+        let prod: App = loader.load_profile(Some("prod")).unwrap();
+        assert_eq!((prod.name.as_str(), prod.database.host.as_str(), prod.database.port), ("eauth-prod", "db.prod", 5432));
+        assert!(prod.to_toml().contains("host = \"db.prod\""));
+
+        assert_eq!(loader.profile_names().unwrap(), vec!["dev", "prod"]);
+        match loader.clone().profile("qa").load::<App>().unwrap_err() {
+            ConfigurationError::LoadError { message, .. } => {
+                assert_eq!(message, "Unknown profile `qa` (available: dev, prod)");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_profile_selection_from_env_and_args() {
+        let dir = temp_dir("libelp_loader_profile_sel");
+        fs::write(dir.join("eauth.toml"), PROFILES).unwrap();
+        let _env = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: environment changes are serialized by `ENV_LOCK`
+        unsafe { std::env::set_var("LIBELPPROFILETEST_PROFILE", "dev") };
+        let loader = ConfigLoader::new().file(dir.join("eauth.toml")).env_prefix("LIBELPPROFILETEST");
+        assert_eq!(loader.active_profile().as_deref(), Some("dev"));
+        assert_eq!(loader.load::<App>().unwrap().database.port, 15432);
+
+        let args = ["eauth", "--verbose", "--profile", "prod"];
+        assert_eq!(loader.clone().profile_from_args(args).active_profile().as_deref(), Some("prod"));
+        let args = ["eauth", "--profile=prod"];
+        assert_eq!(loader.clone().profile_from_args(args).load::<App>().unwrap().name, "eauth-prod");
+        assert_eq!(loader.clone().profile_from_args(["eauth"]).active_profile().as_deref(), Some("dev"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_profile_errors_point_at_profile_file_and_check_profiles() {
+        let dir = temp_dir("libelp_loader_profile_err");
+        fs::write(dir.join("eauth.toml"), PROFILES).unwrap();
+        let frag = dir.join("eauth.d");
+        fs::create_dir_all(&frag).unwrap();
+        fs::write(frag.join("10-qa.toml"), "[profile.qa.database]\nport = \"oops\"\n").unwrap();
+        let loader = ConfigLoader::new().file(dir.join("eauth.toml")).fragments_dir(&frag);
+
+        let err = loader.clone().profile("qa").load::<App>().unwrap_err();
+        let ctx = err.parse_context().expect("parse context");
+        assert!(ctx.file.as_deref().unwrap().ends_with("10-qa.toml"), "{:?}", ctx);
+        assert_eq!(ctx.field.as_deref(), Some("database.port"));
+        assert_eq!(ctx.excerpt.as_deref(), Some("port = \"oops\""));
+
+        let results = loader.check_profiles::<App>().unwrap();
+        let summary: Vec<(&str, bool)> = results.iter().map(|(n, r)| (n.as_str(), r.is_ok())).collect();
+        assert_eq!(summary, vec![("dev", true), ("prod", true), ("qa", false)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
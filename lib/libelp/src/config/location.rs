@@ -0,0 +1,181 @@
+use std::ops::Range;
+
+/// Position of a parse error inside a configuration source
+///
+/// Computed from the byte span reported by the TOML parser rather than from
+/// the rendered error message, so it is available for syntax and type errors alike.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number (in characters)
+    pub column: usize,
+    /// Full text of the line containing the error
+    pub excerpt: String,
+    /// Number of characters covered by the span on that line (at least 1)
+    pub width: usize,
+}
+
+impl SourceLocation {
+    /// Resolve a byte span against the source it was produced from
+    pub fn from_span(source: &str, span: Range<usize>) -> Self {
+        let start = floor_char_boundary(source, span.start.min(source.len()));
+        let end = floor_char_boundary(source, span.end.clamp(start, source.len()));
+
+        let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = source[start..]
+            .find('\n')
+            .map(|i| start + i)
+            .unwrap_or(source.len());
+
+        let line = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+        let excerpt = source[line_start..line_end].trim_end_matches('\r').to_string();
+        let width = source[start..end.min(line_end)].chars().count().max(1);
+
+        Self { line, column, excerpt, width }
+    }
+
+    /// Build a location from a 1-based line/column pair reported by a parser
+    ///
+    /// Out-of-range lines yield an empty excerpt; the underline is one character wide.
+    pub fn from_line_column(source: &str, line: usize, column: usize) -> Self {
+        let excerpt = source
+            .lines()
+            .nth(line.saturating_sub(1))
+            .unwrap_or("")
+            .trim_end_matches('\r')
+            .to_string();
+        Self { line: line.max(1), column: column.max(1), excerpt, width: 1 }
+    }
+}
+
+/// Find the dotted key path whose key or value covers `offset`
+///
+/// Returns `None` when the source is not syntactically valid TOML or the
+/// offset does not fall inside any key/value pair (e.g. a missing field).
+pub fn field_path_at(source: &str, offset: usize) -> Option<String> {
+    let doc = toml_edit::ImDocument::parse(source).ok()?;
+    let mut path = Vec::new();
+    if table_path_at(doc.as_table(), offset, &mut path) {
+        Some(path.join("."))
+    } else {
+        None
+    }
+}
+
+/// Find the span of the value stored at a dotted key path
+///
+/// Falls back to the span of the key when the value has none (e.g. standard tables).
+pub fn span_of_path(source: &str, path: &str) -> Option<Range<usize>> {
+    let doc = toml_edit::ImDocument::parse(source).ok()?;
+    let mut table: &dyn toml_edit::TableLike = doc.as_table();
+    let mut keys = path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let (k, item) = table.get_key_value(key)?;
+        if keys.peek().is_none() {
+            return item.span().or_else(|| k.span());
+        }
+        table = item.as_table_like()?;
+    }
+    None
+}
+
+fn table_path_at(table: &toml_edit::Table, offset: usize, path: &mut Vec<String>) -> bool {
+    for (key, item) in table.iter() {
+        path.push(key.to_string());
+        let key_hit = table.key(key).and_then(|k| k.span()).is_some_and(|s| s.contains(&offset));
+        let found = key_hit
+            || match item {
+                toml_edit::Item::Table(t) => table_path_at(t, offset, path),
+                toml_edit::Item::ArrayOfTables(a) => a.iter().any(|t| table_path_at(t, offset, path)),
+                toml_edit::Item::Value(v) => value_path_at(v, offset, path),
+                toml_edit::Item::None => false,
+            };
+        if found {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+
+fn value_path_at(value: &toml_edit::Value, offset: usize, path: &mut Vec<String>) -> bool {
+    if let toml_edit::Value::InlineTable(t) = value {
+        for (key, v) in t.iter() {
+            path.push(key.to_string());
+            let key_hit = t.key(key).and_then(|k| k.span()).is_some_and(|s| s.contains(&offset));
+            if key_hit || value_path_at(v, offset, path) {
+                return true;
+            }
+            path.pop();
+        }
+        return false;
+    }
+    value.span().is_some_and(|s| s.contains(&offset))
+}
+
+fn floor_char_boundary(s: &str, mut index: usize) -> usize {
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_span_computes_line_column_and_width() {
+        let src = "key = \"v\"\nport = \"oops\"\n";
+        let start = src.find("\"oops\"").unwrap();
+        let loc = SourceLocation::from_span(src, start..start + 6);
+        assert_eq!(loc.line, 2);
+        assert_eq!(loc.column, 8);
+        assert_eq!(loc.excerpt, "port = \"oops\"");
+        assert_eq!(loc.width, 6);
+    }
+
+    #[test]
+    fn test_from_span_clamps_width_to_line() {
+        let src = "a = [\n  1,\n]\n";
+        let loc = SourceLocation::from_span(src, 4..src.len());
+        assert_eq!(loc.line, 1);
+        assert_eq!(loc.width, 1);
+    }
+
+    #[test]
+    fn test_from_line_column_takes_excerpt_from_source() {
+        let loc = SourceLocation::from_line_column("a: 1\nb: x\n", 2, 4);
+        assert_eq!(loc.excerpt, "b: x");
+        assert_eq!((loc.line, loc.column, loc.width), (2, 4, 1));
+        assert_eq!(SourceLocation::from_line_column("a", 9, 1).excerpt, "");
+    }
+
+    #[test]
+    fn test_field_path_at_nested_and_inline_tables() {
+        let src = "name = \"x\"\n\n[database]\nport = \"oops\"\npool = { size = true }\n";
+        let port = src.find("\"oops\"").unwrap();
+        assert_eq!(field_path_at(src, port).as_deref(), Some("database.port"));
+        let size = src.find("true").unwrap();
+        assert_eq!(field_path_at(src, size).as_deref(), Some("database.pool.size"));
+        let key = src.find("name").unwrap();
+        assert_eq!(field_path_at(src, key).as_deref(), Some("name"));
+    }
+
+    #[test]
+    fn test_span_of_path_finds_values_in_tables() {
+        let src = "name = \"x\"\n\n[database]\nport = 5432\npool = { size = 4 }\n";
+        let span = span_of_path(src, "database.port").unwrap();
+        assert_eq!(&src[span], "5432");
+        let span = span_of_path(src, "database.pool.size").unwrap();
+        assert_eq!(&src[span], "4");
+        assert_eq!(span_of_path(src, "database.missing"), None);
+    }
+
+    #[test]
+    fn test_field_path_at_invalid_source_is_none() {
+        assert_eq!(field_path_at("a = ", 2), None);
+    }
+}
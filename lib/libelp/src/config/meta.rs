@@ -0,0 +1,207 @@
+use crate::Configuration;
+use crate::config::path::lookup;
+use crate::error::{ConfigurationError, ConfigurationResult};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Static description of one field of a derived configuration struct
+///
+/// `#[derive(Configuration)]` emits `Name::FIELDS` with one entry per field;
+/// [`Configuration::metadata`](crate::Configuration::metadata) flattens it
+/// with nested sections and full key paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMeta {
+    /// Field name, e.g. `port`
+    pub name: &'static str,
+    /// Dotted key path, e.g. `database.port`; just the name in `FIELDS`
+    pub path: Cow<'static, str>,
+    /// Field type as written in the struct, e.g. `u16` or `Database`
+    pub type_name: &'static str,
+    /// Text of `#[config(note = "...")]`, or else the field's doc comment
+    pub note: Option<&'static str>,
+    /// Default as written, e.g. `"localhost"`, `60 * 60` or `num_cpus::get()`
+    pub default: Option<&'static str>,
+    /// Text of `#[config(example = ...)]` as written, e.g. `"db.internal"`
+    pub example: Option<&'static str>,
+    /// Text of `#[config(unit = "...")]`, e.g. `ms`
+    pub unit: Option<&'static str>,
+    /// Values the field type accepts, see [`ConfigValue::ALLOWED_VALUES`](crate::ConfigValue::ALLOWED_VALUES)
+    pub allowed: Option<&'static [&'static str]>,
+    /// Marked `#[config(secret)]`
+    pub secret: bool,
+    /// Marked `#[config(required)]`: loading fails when the key is absent
+    pub required: bool,
+    /// Text of `#[config(deprecated = "...")]`
+    pub deprecated: Option<&'static str>,
+    /// Checks applied by `validate()`
+    pub constraints: &'static [Constraint],
+    /// Fields of the nested section, when the field is one
+    pub nested: Option<&'static [FieldMeta]>,
+    /// Marked `#[config(flatten)]`: the `nested` keys sit in the parent's table
+    pub flatten: bool,
+}
+
+/// A bound checked by the derived `validate()`, holding the literal as written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    /// `#[config(min = ...)]`
+    Min(&'static str),
+    /// `#[config(max = ...)]`
+    Max(&'static str),
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::Min(min) => write!(f, "at least {}", min),
+            Constraint::Max(max) => write!(f, "at most {}", max),
+        }
+    }
+}
+
+/// All fields of `fields` and of their nested sections, in declaration order
+///
+/// A section is listed before its own fields, whose paths are prefixed with
+/// the section's key. Flattened fields are replaced by their nested fields,
+/// with the paths of the parent.
+pub fn flatten(fields: &'static [FieldMeta]) -> Vec<FieldMeta> {
+    let mut out = Vec::new();
+    push_fields(&mut out, fields, "");
+    out
+}
+
+impl FieldMeta {
+    /// Marked `#[config(required)]` without a default: the defaults only hold
+    /// a placeholder, the empty value of the type
+    pub fn is_mandatory(&self) -> bool {
+        self.required && self.default.is_none()
+    }
+}
+
+/// Fail with a [`ConfigurationError::ValidationError`] naming every
+/// `#[config(required)]` key of `T` that `value` leaves out
+///
+/// Other keys may be absent; they take their defaults.
+pub fn check_required<T: Configuration>(value: &toml::Value) -> ConfigurationResult<()> {
+    missing_keys(
+        T::metadata()
+            .into_iter()
+            .filter(|field| field.required && lookup(value, &field.path).is_none())
+            .map(|field| field.path.into_owned())
+            .collect(),
+    )
+}
+
+/// Like [`check_required`] for a builder: `set` holds the key paths whose
+/// setters were called, and a required field without a default must be
+/// among them, inside one of them or, for a section, hold one of them
+pub fn check_set<T: Configuration>(set: &[String]) -> ConfigurationResult<()> {
+    let within = |inner: &str, outer: &str| inner.strip_prefix(outer).is_some_and(|rest| rest.starts_with('.'));
+    let is_set = |path: &str| set.iter().any(|key| path == key || within(path, key) || within(key, path));
+    missing_keys(
+        T::metadata()
+            .into_iter()
+            .filter(|field| field.is_mandatory() && !is_set(&field.path))
+            .map(|field| field.path.into_owned())
+            .collect(),
+    )
+}
+
+// The error for absent required keys; the first one is the error's field
+fn missing_keys(missing: Vec<String>) -> ConfigurationResult<()> {
+    match missing.as_slice() {
+        [] => Ok(()),
+        [key] => Err(ConfigurationError::validation_error("required key is missing", Some(key.clone()))),
+        [first, ..] => Err(ConfigurationError::validation_error(
+            format!("required keys are missing: {}", missing.join(", ")),
+            Some(first.clone()),
+        )),
+    }
+}
+
+fn push_fields(out: &mut Vec<FieldMeta>, fields: &'static [FieldMeta], prefix: &str) {
+    for field in fields {
+        if let (true, Some(nested)) = (field.flatten, field.nested) {
+            push_fields(out, nested, prefix);
+            continue;
+        }
+        let path = if prefix.is_empty() { field.path.clone() } else { Cow::Owned(format!("{}.{}", prefix, field.name)) };
+        out.push(FieldMeta { path: path.clone(), ..field.clone() });
+        if let Some(nested) = field.nested {
+            push_fields(out, nested, &path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Configuration, ConfigurationError};
+    use std::time::Duration;
+
+    mod limits {
+        pub const MAX_POOL: u32 = 64;
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Database {
+        #[config(default = "localhost", note = "db host")]
+        host: String,
+        #[config(default = "", secret)]
+        password: String,
+        #[config(default = 8, min = 1, max = limits::MAX_POOL)]
+        pool: u32,
+        #[config(default = "5s", min = "1s")]
+        timeout: Duration,
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Config {
+        #[config(default = 60 * 60, deprecated = "use `session.ttl`")]
+        ttl: u64,
+        database: Database,
+    }
+
+    #[test]
+    fn test_fields_describe_each_field() {
+        let pool = &Database::FIELDS[2];
+        assert_eq!((pool.name, pool.type_name, pool.default), ("pool", "u32", Some("8")));
+        assert_eq!(pool.constraints, &[Constraint::Min("1"), Constraint::Max("limits::MAX_POOL")]);
+        assert_eq!(Database::FIELDS[0].note, Some("db host"));
+        assert_eq!(Database::FIELDS[0].default, Some("\"localhost\""));
+        assert!(Database::FIELDS[1].secret);
+        assert_eq!(Config::FIELDS[0].default, Some("60 * 60"));
+        assert_eq!(Config::FIELDS[0].deprecated, Some("use `session.ttl`"));
+        assert_eq!(Config::FIELDS[1].nested, Some(Database::FIELDS));
+        assert_eq!(Constraint::Min("1s").to_string(), "at least 1s");
+    }
+
+    #[test]
+    fn test_metadata_walks_nested_sections() {
+        let paths: Vec<String> = Config::metadata().iter().map(|f| f.path.to_string()).collect();
+        assert_eq!(
+            paths,
+            ["ttl", "database", "database.host", "database.password", "database.pool", "database.timeout"]
+        );
+        assert_eq!(Config::metadata()[1].type_name, "Database");
+    }
+
+    #[test]
+    fn test_validate_enforces_constraints() {
+        assert!(Config::new().validate().is_ok());
+        let mut config = Config::new();
+        config.database.pool = 100;
+        let err = config.validate().unwrap_err();
+        match err {
+            ConfigurationError::ValidationError { message, field } => {
+                assert_eq!(message, "must be at most limits::MAX_POOL, got 100");
+                assert_eq!(field.as_deref(), Some("database.pool"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        config.database.pool = 1;
+        config.database.timeout = Duration::from_millis(10);
+        assert!(config.validate().unwrap_err().to_string().contains("must be at least 1s, got \"10ms\""));
+    }
+}
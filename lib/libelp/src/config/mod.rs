@@ -1,2 +1,26 @@
+pub mod builder;
+pub mod cli;
+#[allow(clippy::module_inception)]
 pub mod config;
-pub mod error;
\ No newline at end of file
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod error;
+pub mod format;
+pub mod interpolate;
+pub mod loader;
+pub mod location;
+pub mod meta;
+pub mod path;
+pub mod paths;
+pub mod permissions;
+pub mod persist;
+pub mod render;
+#[cfg(test)]
+mod roundtrip;
+pub mod secret;
+pub mod shared;
+pub mod template;
+#[cfg(test)]
+mod test_util;
+pub mod units;
+pub mod value;
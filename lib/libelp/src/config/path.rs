@@ -0,0 +1,177 @@
+use crate::config::value::ConfigField;
+use crate::error::{ConfigurationError, ConfigurationResult};
+use toml::Value;
+
+/// Value at a dotted key path such as `database.port`
+///
+/// An empty path is the value itself.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').filter(|key| !key.is_empty()).try_fold(value, |value, key| value.get(key))
+}
+
+pub(crate) fn lookup_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').filter(|key| !key.is_empty()).try_fold(value, |value, key| value.get_mut(key))
+}
+
+/// Read a value typed on a command line, e.g. the `5432` of `database.port=5432`
+///
+/// Text that is a TOML value (number, boolean, quoted string, array or
+/// inline table) is read as one; anything else is taken as a plain string.
+pub fn parse_value(text: &str) -> Value {
+    let text = text.trim();
+    toml::from_str::<toml::Table>(&format!("value = {}", text))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+/// Error for `path`, suggesting the known paths closest to it
+pub fn unknown_key<S: AsRef<str>>(path: &str, known: impl IntoIterator<Item = S>) -> ConfigurationError {
+    let limit = (path.chars().count() / 3).max(2);
+    let mut close: Vec<(usize, String)> = known
+        .into_iter()
+        .map(|candidate| (distance(path, candidate.as_ref()), candidate.as_ref().to_string()))
+        .filter(|(d, _)| *d <= limit)
+        .collect();
+    close.sort();
+    ConfigurationError::unknown_key(path, close.into_iter().take(3).map(|(_, p)| p).collect())
+}
+
+/// Dotted paths of every value and table below `value`, in order
+pub(crate) fn paths(value: &Value) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut stack = vec![(String::new(), value)];
+    while let Some((prefix, value)) = stack.pop() {
+        if let Value::Table(table) = value {
+            for (key, child) in table.iter().rev() {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                stack.push((path, child));
+            }
+        }
+        if !prefix.is_empty() {
+            out.push(prefix);
+        }
+    }
+    out
+}
+
+/// Replace `field` with `value`, converted through its `ConfigValue` impl
+#[doc(hidden)]
+pub fn set_field<T: ConfigField>(field: &mut T, key: &str, value: &Value) -> ConfigurationResult<()> {
+    *field = T::__elp_from_value(value).map_err(|e| {
+        ConfigurationError::validation_error(format!("expected {}: {}", T::__elp_type_name(), e), Some(key.to_string()))
+    })?;
+    Ok(())
+}
+
+// Levenshtein distance over characters
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Configuration;
+    use std::time::Duration;
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Redis {
+        #[config(default = "localhost")]
+        host: String,
+        #[config(default = "5s")]
+        timeout: Duration,
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Config {
+        #[config(default = 5432)]
+        port: u16,
+        redis: Redis,
+    }
+
+    #[test]
+    fn test_parse_value_and_lookup() {
+        assert_eq!(parse_value("5432"), Value::Integer(5432));
+        assert_eq!(parse_value("true"), Value::Boolean(true));
+        assert_eq!(parse_value("\"a b\""), Value::String("a b".into()));
+        assert_eq!(parse_value("cache1"), Value::String("cache1".into()));
+        assert_eq!(parse_value("[1, 2]"), Value::Array(vec![Value::Integer(1), Value::Integer(2)]));
+
+        let value = parse_value("{ a = { b = 1 } }");
+        assert_eq!(lookup(&value, "a.b"), Some(&Value::Integer(1)));
+        assert_eq!(lookup(&value, ""), Some(&value));
+        assert_eq!(lookup(&value, "a.c"), None);
+    }
+
+    #[test]
+    fn test_get_and_set_by_path() {
+        let mut config = Config::new();
+        assert_eq!(config.get_path("port"), Some(Value::Integer(5432)));
+        assert_eq!(config.get_path("redis.timeout"), Some(Value::String("5s".into())));
+        assert!(config.get_path("redis").unwrap().is_table());
+        assert_eq!(config.get_path("redis.nope"), None);
+
+        config.set_path("redis.host", "cache1").unwrap();
+        config.set_path("redis.timeout", parse_value("1m")).unwrap();
+        config.set_path("port", 6432).unwrap();
+        assert_eq!(config.redis.host, "cache1");
+        assert_eq!(config.redis.timeout, Duration::from_secs(60));
+        assert_eq!(config.port, 6432);
+    }
+
+    #[derive(libelp::Configuration, Debug)]
+    struct Backend {
+        /// Kind of store
+        #[config(default = "redis", required)]
+        r#type: String,
+    }
+
+    #[test]
+    fn test_raw_identifiers_are_keyed_without_prefix() {
+        let mut backend = Backend::builder().r#type("memcached").build().unwrap();
+        assert_eq!(backend.get_path("type"), Some(Value::String("memcached".into())));
+        backend.set_path("type", "redis").unwrap();
+        assert_eq!(backend.r#type, "redis");
+        assert_eq!(Backend::field_note("type"), Some("Kind of store"));
+        assert_eq!(Backend::metadata()[0].path, "type");
+        assert_eq!(Backend::from_toml("type = \"disk\"\n").unwrap().r#type, "disk");
+        assert!(Backend::from_toml("").is_err());
+    }
+
+    #[test]
+    fn test_set_path_rejects_bad_values_and_unknown_keys() {
+        let mut config = Config::new();
+        match config.set_path("redis.timeout", "soon").unwrap_err() {
+            ConfigurationError::ValidationError { message, field } => {
+                assert!(message.starts_with("expected Duration: "), "{}", message);
+                assert_eq!(field.as_deref(), Some("redis.timeout"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(config.set_path("port", 70000).is_err());
+        assert_eq!(config.port, 5432);
+
+        match config.set_path("redis.hots", "x").unwrap_err() {
+            ConfigurationError::UnknownKey { path, suggestions } => {
+                assert_eq!(path, "redis.hots");
+                assert_eq!(suggestions, vec!["redis.host".to_string()]);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let err = config.set_path("port.x", 1).unwrap_err();
+        assert!(err.user_message().contains("Did you mean 'port'?"), "{}", err.user_message());
+        assert!(matches!(config.set_path("zzzzzzzz", 1), Err(ConfigurationError::UnknownKey { suggestions, .. }) if suggestions.is_empty()));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::error::{ConfigurationError, ConfigurationResult};
+use std::path::{Path, PathBuf};
+
+/// Expand a leading `~` and `$VAR` / `${VAR}` references in a path
+///
+/// `~` and `~/...` use `HOME`; `~user` forms are left untouched. Unset
+/// variables are an error. Paths that are not valid UTF-8 are returned as is.
+pub fn expand_path(path: &Path) -> Result<PathBuf, String> {
+    let Some(text) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+    let home = || std::env::var("HOME").map_err(|_| "`~` used but HOME is not set".to_string());
+    let text = if text == "~" {
+        home()?
+    } else if let Some(rest) = text.strip_prefix("~/") {
+        format!("{}/{}", home()?, rest)
+    } else {
+        text.to_string()
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced.find('}').ok_or_else(|| format!("unterminated `${{` in `{}`", text))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            out.push('$');
+        } else {
+            let value = std::env::var(name).map_err(|_| format!("environment variable `{}` is not set", name))?;
+            out.push_str(&value);
+        }
+        rest = remainder;
+    }
+    out.push_str(rest);
+    Ok(PathBuf::from(out))
+}
+
+/// Expand a path field and resolve it against the directory of its source file
+///
+/// Used by the code `#[derive(Configuration)]` generates for `PathBuf`
+/// fields. `base_dir` maps the field's key path to the directory of the file
+/// that set it; values that did not come from a file stay relative.
+pub fn resolve_field(
+    path: &mut PathBuf,
+    key: &str,
+    base_dir: &dyn Fn(&str) -> Option<PathBuf>,
+) -> ConfigurationResult<()> {
+    let expanded = expand_path(path).map_err(|e| {
+        ConfigurationError::load_error(format!("Cannot expand path `{}` in `{}`: {}", path.display(), key, e), key)
+    })?;
+    *path = match base_dir(key) {
+        Some(dir) if expanded.is_relative() => dir.join(expanded),
+        _ => expanded,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConfigLoader;
+    use crate::config::test_util::temp_dir;
+    use std::fs;
+    use std::net::{IpAddr, SocketAddr};
+
+    #[test]
+    fn test_expand_home_and_variables() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_path(Path::new("~/data")).unwrap(), PathBuf::from(format!("{}/data", home)));
+        assert_eq!(expand_path(Path::new("~")).unwrap(), PathBuf::from(&home));
+        assert_eq!(expand_path(Path::new("/srv/~x")).unwrap(), PathBuf::from("/srv/~x"));
+        assert_eq!(expand_path(Path::new("$HOME/a")).unwrap(), PathBuf::from(format!("{}/a", home)));
+        assert_eq!(expand_path(Path::new("${HOME}x/$")).unwrap(), PathBuf::from(format!("{}x/$", home)));
+        assert!(expand_path(Path::new("$LIBELP_SURELY_UNSET_PATH_VAR/a")).unwrap_err().contains("LIBELP_SURELY_UNSET_PATH_VAR"));
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Storage {
+        #[config(default = "data", note = "data directory")]
+        dir: PathBuf,
+        #[config(default = "~/.cache/eauth", note = "cache directory")]
+        cache: PathBuf,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Net {
+        #[config(default = "127.0.0.1:8080", note = "listen address")]
+        listen: SocketAddr,
+        #[config(default = "::1", note = "admin address")]
+        admin: IpAddr,
+        storage: Storage,
+    }
+
+    #[test]
+    fn test_derive_defaults_for_network_and_path_types() {
+        let net = Net::new();
+        assert_eq!(net.listen, "127.0.0.1:8080".parse::<SocketAddr>().unwrap());
+        assert_eq!(net.admin, "::1".parse::<IpAddr>().unwrap());
+        assert_eq!(net.storage.dir, PathBuf::from("data"));
+
+        let out = net.to_toml();
+        assert!(out.contains("# listen address, SocketAddr, default: \"127.0.0.1:8080\""), "{}", out);
+        assert!(out.contains("# listen = \"127.0.0.1:8080\""), "{}", out);
+        assert!(out.contains("# dir = \"data\""), "{}", out);
+    }
+
+    #[test]
+    fn test_loader_resolves_paths_against_config_dir() {
+        let dir = temp_dir("libelp_paths");
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        fs::write(
+            dir.join("eauth.toml"),
+            "listen = \"0.0.0.0:443\"\nadmin = \"10.0.0.1\"\n[storage]\ndir = \"var/data\"\ncache = \"/tmp/cache\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("conf.d/10.toml"), "[storage]\ncache = \"~/c\"\n").unwrap();
+
+        let net: Net = ConfigLoader::new()
+            .file(dir.join("eauth.toml"))
+            .fragments_dir(dir.join("conf.d"))
+            .load()
+            .unwrap();
+        assert_eq!(net.listen.port(), 443);
+        assert_eq!(net.storage.dir, dir.join("var/data"));
+        assert_eq!(net.storage.cache, PathBuf::from(std::env::var("HOME").unwrap()).join("c"));
+
+        fs::write(dir.join("conf.d/10.toml"), "listen = \"nope\"\n").unwrap();
+        let err = ConfigLoader::new()
+            .file(dir.join("eauth.toml"))
+            .fragments_dir(dir.join("conf.d"))
+            .load::<Net>()
+            .unwrap_err();
+        assert_eq!(err.parse_context().and_then(|c| c.field.as_deref()), Some("listen"));
+        assert_eq!(err.parse_context().and_then(|c| c.note.as_deref()), Some("listen address"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn test_derive_url_field() {
+        #[derive(libelp_proc::Configuration, Debug)]
+        struct Upstream {
+            #[config(default = "https://auth.example.com/v1/", note = "upstream")]
+            url: crate::Url,
+        }
+        let upstream = Upstream::new();
+        assert_eq!(upstream.url.host_str(), Some("auth.example.com"));
+        let upstream = Upstream::from_toml("url = \"http://localhost:9000/\"").unwrap();
+        assert_eq!(upstream.url.port(), Some(9000));
+        assert!(upstream.to_toml().contains("url = \"http://localhost:9000/\""));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::error::{ConfigurationError, ConfigurationResult};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// What loading does when a file feeding a configuration with secret fields is exposed
+///
+/// Only types with `#[config(secret)]` fields are checked. A file is exposed
+/// when other users may read or write it, or when it is owned by someone
+/// other than the current user or root.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PermissionPolicy {
+    /// Do not inspect file permissions
+    Ignore,
+    /// Report a [`ConfigurationError::InsecurePermissions`] to the warning handler and continue
+    ///
+    /// Warnings are dropped unless a handler is set with
+    /// [`ConfigLoader::on_warning`](crate::ConfigLoader::on_warning).
+    #[default]
+    Warn,
+    /// Fail with [`ConfigurationError::InsecurePermissions`]
+    Deny,
+}
+
+/// Callback receiving warnings raised while loading
+#[derive(Clone)]
+pub struct WarningHandler(Arc<dyn Fn(&ConfigurationError) + Send + Sync>);
+
+impl WarningHandler {
+    /// Wrap a callback
+    pub fn new(handler: impl Fn(&ConfigurationError) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(handler))
+    }
+
+    /// Pass a warning to the callback
+    pub fn warn(&self, warning: &ConfigurationError) {
+        (self.0)(warning)
+    }
+}
+
+impl Default for WarningHandler {
+    /// Drop warnings; a library has no business writing to stderr
+    fn default() -> Self {
+        Self::new(|_| {})
+    }
+}
+
+impl fmt::Debug for WarningHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WarningHandler(..)")
+    }
+}
+
+/// Check that only the current user (or root) can access a file holding secrets
+///
+/// Always succeeds on platforms without Unix permissions.
+pub fn check_file(path: &Path) -> ConfigurationResult<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let display = path.display().to_string();
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| ConfigurationError::file_error(format!("Failed to inspect file: {}", e), &display))?;
+        let mode = metadata.mode() & 0o777;
+        let mut problems = Vec::new();
+        if mode & 0o044 != 0 {
+            problems.push("is readable by other users");
+        }
+        if mode & 0o022 != 0 {
+            problems.push("is writable by other users");
+        }
+        if !problems.is_empty() {
+            return Err(ConfigurationError::insecure_permissions(
+                format!("it {} (mode {:04o})", problems.join(" and "), mode),
+                display,
+            ));
+        }
+        // SAFETY: geteuid has no preconditions and cannot fail
+        let euid = unsafe { libc::geteuid() };
+        if metadata.uid() != euid && metadata.uid() != 0 {
+            return Err(ConfigurationError::insecure_permissions(
+                format!("it is owned by uid {} instead of the current user (uid {})", metadata.uid(), euid),
+                display,
+            ));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::config::test_util::{temp_dir, temp_path};
+    use crate::{ConfigLoader, Configuration, SaveOptions};
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Mutex;
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Db {
+        #[config(default = "localhost", note = "db host")]
+        host: String,
+        #[config(default = "", note = "db password", secret)]
+        password: String,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct App {
+        #[config(default = "app", note = "name")]
+        name: String,
+        db: Db,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Public {
+        #[config(default = "app", note = "name")]
+        name: String,
+    }
+
+    fn temp_file(prefix: &str, mode: u32) -> std::path::PathBuf {
+        let path = temp_path(prefix).with_extension("toml");
+        fs::write(&path, "name = \"x\"\n[db]\nhost = \"h\"\npassword = \"p\"\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_has_secrets_follows_nested_fields() {
+        assert!(Db::has_secrets());
+        assert!(App::has_secrets());
+        assert!(!Public::has_secrets());
+    }
+
+    #[test]
+    fn test_policy_denies_warns_or_ignores_exposed_files() {
+        let path = temp_file("libelp_perm_open", 0o644);
+        let err = ConfigLoader::new()
+            .file(&path)
+            .permissions(PermissionPolicy::Deny)
+            .load::<App>()
+            .unwrap_err();
+        assert!(matches!(err, ConfigurationError::InsecurePermissions { .. }), "{:?}", err);
+        assert!(err.to_string().contains("readable by other users (mode 0644)"), "{}", err);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let app: App = ConfigLoader::new()
+            .file(&path)
+            .on_warning(move |w| sink.lock().unwrap().push(w.to_string()))
+            .load()
+            .unwrap();
+        assert_eq!(app.db.password, "p");
+        assert_eq!(seen.lock().unwrap().len(), 1);
+
+        // Types without secrets are never checked
+        assert!(ConfigLoader::new().file(&path).permissions(PermissionPolicy::Deny).load::<Public>().is_ok());
+        assert!(check_file(&path).is_err());
+        let _ = fs::remove_file(&path);
+
+        let path = temp_file("libelp_perm_closed", 0o600);
+        assert!(check_file(&path).is_ok());
+        assert!(ConfigLoader::new().file(&path).permissions(PermissionPolicy::Deny).load::<App>().is_ok());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_save_creates_secret_files_private() {
+        let dir = temp_dir("libelp_perm_save");
+        App::new().save_to_file(dir.join("app.toml")).unwrap();
+        assert_eq!(fs::metadata(dir.join("app.toml")).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // Existing files keep their mode
+        fs::set_permissions(dir.join("app.toml"), fs::Permissions::from_mode(0o640)).unwrap();
+        SaveOptions::for_config::<App>().save(&App::new(), dir.join("app.toml")).unwrap();
+        assert_eq!(fs::metadata(dir.join("app.toml")).unwrap().permissions().mode() & 0o777, 0o640);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::Configuration;
+use crate::config::format::Format;
+use crate::error::{ConfigurationError, ConfigurationResult};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How configuration files are written
+///
+/// Every write goes to a temporary file in the destination's directory,
+/// is flushed to disk and then renamed over the destination, so readers
+/// and crashes see either the old or the new file, never a truncated one.
+/// An existing file keeps its permissions and, where allowed, its owner.
+///
+/// [`SaveOptions::mode`] sets the mode of newly created files.
+/// With [`SaveOptions::backups`] the previous contents are kept as
+/// `eauth.toml.1` (newest) up to `eauth.toml.<n>` (oldest).
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    backups: usize,
+    mode: Option<u32>,
+}
+
+impl SaveOptions {
+    /// Atomic writes without backups
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options for saving `T`: new files are created `0600` when `T` has secret fields
+    pub fn for_config<T: Configuration>() -> Self {
+        let options = Self::new();
+        if T::has_secrets() { options.mode(0o600) } else { options }
+    }
+
+    /// Unix mode for newly created files; existing files keep their own
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Keep up to `keep` rotated backups of the file being replaced
+    pub fn backups(mut self, keep: usize) -> Self {
+        self.backups = keep;
+        self
+    }
+
+    /// Serialize `config` in the format chosen by the file extension and write it
+    ///
+    /// Files with an unknown or missing extension are written as TOML.
+    pub fn save<T: Serialize>(&self, config: &T, path: impl AsRef<Path>) -> ConfigurationResult<()> {
+        let path = path.as_ref();
+        self.save_as(config, path, Format::from_path(path).unwrap_or(Format::Toml))
+    }
+
+    /// Serialize `config` in the given format and write it
+    pub fn save_as<T: Serialize>(&self, config: &T, path: impl AsRef<Path>, format: Format) -> ConfigurationResult<()> {
+        let content = format.serialize(config)?;
+        self.write(path, content.as_bytes())
+    }
+
+    /// Atomically replace `path` with `content`, rotating backups first
+    pub fn write(&self, path: impl AsRef<Path>, content: &[u8]) -> ConfigurationResult<()> {
+        let path = path.as_ref();
+        let error = |e: io::Error| {
+            ConfigurationError::file_error(format!("Failed to write file: {}", e), path.display().to_string())
+        };
+        if self.backups > 0 {
+            rotate_backups(path, self.backups).map_err(error)?;
+        }
+        replace(path, content, self.mode).map_err(error)
+    }
+}
+
+/// Atomically replace `path` with `content`; see [`SaveOptions`]
+pub fn write_atomic(path: impl AsRef<Path>, content: &[u8]) -> ConfigurationResult<()> {
+    SaveOptions::new().write(path, content)
+}
+
+/// Path of the `n`-th backup of `path`, e.g. `eauth.toml.2`
+pub fn backup_path(path: impl AsRef<Path>, n: usize) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Atomically replace `path` with its `n`-th backup (`1` is the newest)
+///
+/// The backup itself is kept and the current contents are not rotated.
+pub fn restore_backup(path: impl AsRef<Path>, n: usize) -> ConfigurationResult<()> {
+    let path = path.as_ref();
+    let backup = backup_path(path, n);
+    let content = fs::read(&backup).map_err(|e| {
+        ConfigurationError::file_error(format!("Failed to read backup: {}", e), backup.display().to_string())
+    })?;
+    write_atomic(path, &content)
+}
+
+/// Shift `path.1 .. path.<keep-1>` up by one and copy `path` to `path.1`
+fn rotate_backups(path: &Path, keep: usize) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    for n in (1..keep).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Write a temporary sibling, fsync it, carry over mode and owner, rename it over `path`
+fn replace(path: &Path, content: &[u8], mode: Option<u32>) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let temp = dir.join(format!(".{}.{}.{}.tmp", name, std::process::id(), nanos));
+
+    let result = (|| {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        let mut file = options.open(&temp)?;
+        file.write_all(content)?;
+        if let Ok(existing) = fs::metadata(path) {
+            copy_ownership(&file, &existing)?;
+        }
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+    sync_dir(&dir);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_ownership(file: &fs::File, existing: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{MetadataExt, fchown};
+    file.set_permissions(existing.permissions())?;
+    // Only root may give files away; anyone else keeps owning the new file
+    match fchown(file, Some(existing.uid()), Some(existing.gid())) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+        other => other,
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(file: &fs::File, existing: &fs::Metadata) -> io::Result<()> {
+    file.set_permissions(existing.permissions())
+}
+
+// Persist the rename itself; not every platform can open a directory
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_util::temp_dir;
+
+    #[test]
+    fn test_write_atomic_replaces_and_keeps_mode() {
+        let dir = temp_dir("libelp_persist_atomic");
+        let path = dir.join("eauth.toml");
+        write_atomic(&path, b"a = 1\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        write_atomic(&path, b"a = 2\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        }
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let err = write_atomic(&dir, b"x").unwrap_err();
+        assert!(matches!(err, ConfigurationError::FileError { .. }), "{:?}", err);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_backups_rotate_and_restore() {
+        let dir = temp_dir("libelp_persist_backup");
+        let path = dir.join("eauth.toml");
+        let options = SaveOptions::new().backups(2);
+        for n in 1..=4 {
+            options.write(&path, format!("v = {}\n", n).as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v = 4\n");
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "v = 3\n");
+        assert_eq!(fs::read_to_string(backup_path(&path, 2)).unwrap(), "v = 2\n");
+        assert!(!backup_path(&path, 3).exists());
+
+        restore_backup(&path, 2).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v = 2\n");
+        assert!(backup_path(&path, 2).exists());
+
+        let err = restore_backup(&path, 5).unwrap_err();
+        assert!(err.to_string().contains("Failed to read backup"), "{}", err);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::error::ConfigurationError;
+use std::fmt::Write;
+use std::io::IsTerminal;
+
+/// Output style for [`ConfigurationError::render`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStyle {
+    /// Plain text, suitable for log files
+    Plain,
+    /// ANSI colored output for terminals
+    Color,
+}
+
+impl RenderStyle {
+    /// Choose `Color` when stderr is a terminal and `NO_COLOR` is not set
+    pub fn detect() -> Self {
+        if std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal() {
+            RenderStyle::Color
+        } else {
+            RenderStyle::Plain
+        }
+    }
+
+    fn paint(self, code: &str, text: &str) -> String {
+        match self {
+            RenderStyle::Plain => text.to_string(),
+            RenderStyle::Color => format!("\x1b[{}m{}\x1b[0m", code, text),
+        }
+    }
+}
+
+const RED_BOLD: &str = "1;31";
+const BLUE_BOLD: &str = "1;34";
+const BOLD: &str = "1";
+
+impl ConfigurationError {
+    /// Render the error as a multi-line diagnostic
+    ///
+    /// Parse errors carrying a [`ParseContext`](crate::ParseContext) show the
+    /// offending line with a caret underline followed by the field path,
+    /// expected type and field note:
+    ///
+    /// ```text
+    /// error: invalid type: string "oops", expected u16
+    ///  --> eauth.toml:2:8
+    ///   |
+    /// 2 | port = "oops"
+    ///   |        ^^^^^^
+    ///   = field: database.port
+    ///   = expected: u16
+    ///   = note: db port
+    /// ```
+    pub fn render(&self, style: RenderStyle) -> String {
+        let mut out = String::new();
+        let (headline, location, labels) = self.render_parts();
+        let _ = writeln!(out, "{}: {}", style.paint(RED_BOLD, "error"), style.paint(BOLD, &headline));
+
+        let excerpt = self.parse_context().and_then(|c| c.excerpt.as_deref());
+        let (line, column) = match self {
+            ConfigurationError::TomlParseError { line, column, .. }
+            | ConfigurationError::ParseError { line, column, .. } => (*line, *column),
+            _ => (None, None),
+        };
+        let gutter = line.map(|l| l.to_string().len()).unwrap_or(1);
+        let pad = " ".repeat(gutter);
+        let bar = style.paint(BLUE_BOLD, "|");
+
+        if let Some(location) = location {
+            let mut loc = location;
+            if let Some(line) = line {
+                let _ = write!(loc, ":{}", line);
+                if let Some(column) = column {
+                    let _ = write!(loc, ":{}", column);
+                }
+            }
+            let _ = writeln!(out, "{}{} {}", pad, style.paint(BLUE_BOLD, "-->"), loc);
+        }
+
+        if let (Some(excerpt), Some(line)) = (excerpt, line) {
+            let width = self.parse_context().map(|c| c.width).unwrap_or(1).max(1);
+            let offset = column.unwrap_or(1).saturating_sub(1);
+            let _ = writeln!(out, "{} {}", pad, bar);
+            let _ = writeln!(out, "{} {} {}", style.paint(BLUE_BOLD, &line.to_string()), bar, excerpt);
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                pad,
+                bar,
+                " ".repeat(offset),
+                style.paint(RED_BOLD, &"^".repeat(width))
+            );
+        }
+
+        for (label, value) in labels {
+            let _ = writeln!(out, "{} {} {}: {}", pad, style.paint(BLUE_BOLD, "="), style.paint(BOLD, label), value);
+        }
+
+        out
+    }
+
+    /// Headline, location (file/source) and `= label: value` lines for rendering
+    fn render_parts(&self) -> (String, Option<String>, Vec<(&'static str, String)>) {
+        let mut labels = Vec::new();
+        match self {
+            ConfigurationError::TomlParseError { message, context, .. }
+            | ConfigurationError::ParseError { message, context, .. } => {
+                let Some(ctx) = context else {
+                    return (message.clone(), None, labels);
+                };
+                if let Some(field) = &ctx.field {
+                    labels.push(("field", field.clone()));
+                }
+                if let Some(expected) = &ctx.expected {
+                    labels.push(("expected", expected.clone()));
+                }
+                if let Some(note) = &ctx.note {
+                    labels.push(("note", note.clone()));
+                }
+                let location = ctx.file.clone().or_else(|| ctx.excerpt.as_ref().map(|_| "<string>".to_string()));
+                (message.clone(), location, labels)
+            }
+            ConfigurationError::TomlSerializeError { message }
+            | ConfigurationError::SerializeError { message, .. } => (message.clone(), None, labels),
+            ConfigurationError::ValidationError { message, field } => {
+                if let Some(field) = field {
+                    labels.push(("field", field.clone()));
+                }
+                (message.clone(), None, labels)
+            }
+            ConfigurationError::FileError { message, path }
+            | ConfigurationError::SaveError { message, path }
+            | ConfigurationError::InsecurePermissions { message, path } => {
+                (message.clone(), Some(path.clone()), labels)
+            }
+            ConfigurationError::LoadError { message, config_source } => {
+                (message.clone(), Some(config_source.clone()), labels)
+            }
+            ConfigurationError::FormatDisabled { .. } => (self.to_string(), None, labels),
+            ConfigurationError::UnknownKey { path, suggestions } => {
+                if !suggestions.is_empty() {
+                    labels.push(("did you mean", suggestions.join(", ")));
+                }
+                (format!("unknown configuration key `{}`", path), None, labels)
+            }
+            ConfigurationError::SecretError { message, path, field } => {
+                labels.push(("field", field.clone()));
+                (message.clone(), Some(path.clone()), labels)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Configuration;
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Db {
+        #[config(default = "localhost", note = "db host")]
+        host: String,
+        #[config(default = 5432, note = "db port")]
+        port: u16,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct App {
+        #[config(default = true, note = "enable feature")]
+        feature: bool,
+        database: Db,
+    }
+
+    #[test]
+    fn test_render_type_error_with_excerpt_field_and_note() {
+        let src = "feature = true\n\n[database]\nhost = \"db\"\nport = \"oops\"\n";
+        let err = App::from_toml_string(src).unwrap_err();
+        let out = err.render(RenderStyle::Plain);
+
+        assert!(out.starts_with("error: invalid type: string \"oops\", expected u16"), "{}", out);
+        assert!(out.contains("--> <string>:5:8"), "{}", out);
+        assert!(out.contains("5 | port = \"oops\""), "{}", out);
+        assert!(out.contains("  |        ^^^^^^"), "{}", out);
+        assert!(out.contains("= field: database.port"), "{}", out);
+        assert!(out.contains("= expected: u16"), "{}", out);
+        assert!(out.contains("= note: db port"), "{}", out);
+    }
+
+    #[test]
+    fn test_render_syntax_error_has_caret_but_no_field() {
+        let err = App::from_toml_string("feature = \n").unwrap_err();
+        let out = err.render(RenderStyle::Plain);
+        assert!(out.contains("1 | feature = "), "{}", out);
+        assert!(out.contains('^'), "{}", out);
+        assert!(!out.contains("= field:"), "{}", out);
+    }
+
+    #[test]
+    fn test_render_color_wraps_in_ansi_codes() {
+        let err = ConfigurationError::validation_error("Port cannot be zero", Some("port".to_string()));
+        let colored = err.render(RenderStyle::Color);
+        assert!(colored.contains("\x1b[1;31merror\x1b[0m"));
+        let plain = err.render(RenderStyle::Plain);
+        assert!(!plain.contains('\x1b'));
+        assert!(plain.contains("= field: port"));
+    }
+
+    #[test]
+    fn test_render_file_error_shows_path() {
+        let err = ConfigurationError::file_error("Failed to read file", "/etc/elerp/eauth.toml");
+        let out = err.render(RenderStyle::Plain);
+        assert!(out.contains("--> /etc/elerp/eauth.toml"));
+    }
+}
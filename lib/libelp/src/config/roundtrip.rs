@@ -0,0 +1,181 @@
+//! Property tests: `to_toml()` output reads back as the value it was written from
+//!
+//! [`arbitrary`] builds instances of any derived configuration by setting
+//! each value found in `metadata()` to a generated one through `set_path`.
+
+use crate::config::meta::FieldMeta;
+use crate::config::units::format_duration;
+use crate::{ByteSize, Configuration, TemplateOptions, Verbosity};
+use proptest::prelude::*;
+use std::fmt::Debug;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use toml::Value;
+
+/// Values for a field of the built-in types; other fields keep their default
+///
+/// Integers stay within `i64`, the range of TOML integers, and floats are finite.
+fn value_of(field: &FieldMeta) -> BoxedStrategy<Option<Value>> {
+    fn integer(min: i64, max: i64) -> BoxedStrategy<Option<Value>> {
+        (min..=max).prop_map(|n| Some(Value::Integer(n))).boxed()
+    }
+    fn text<S: Strategy<Value = String> + 'static>(strategy: S) -> BoxedStrategy<Option<Value>> {
+        strategy.prop_map(|s| Some(Value::String(s))).boxed()
+    }
+
+    let type_name = field.type_name.rsplit("::").next().unwrap_or(field.type_name);
+    match type_name {
+        "bool" => any::<bool>().prop_map(|b| Some(Value::Boolean(b))).boxed(),
+        "i8" => integer(i8::MIN.into(), i8::MAX.into()),
+        "i16" => integer(i16::MIN.into(), i16::MAX.into()),
+        "i32" => integer(i32::MIN.into(), i32::MAX.into()),
+        "i64" | "isize" | "i128" => integer(i64::MIN, i64::MAX),
+        "u8" => integer(0, u8::MAX.into()),
+        "u16" => integer(0, u16::MAX.into()),
+        "u32" => integer(0, u32::MAX.into()),
+        "u64" | "usize" | "u128" => integer(0, i64::MAX),
+        "f32" => (prop::num::f32::NORMAL | prop::num::f32::ZERO)
+            .prop_map(|f| Some(Value::Float(f.into())))
+            .boxed(),
+        "f64" => (prop::num::f64::NORMAL | prop::num::f64::SUBNORMAL | prop::num::f64::ZERO)
+            .prop_map(|f| Some(Value::Float(f)))
+            .boxed(),
+        "String" => text(any::<String>()),
+        "PathBuf" => text(any::<String>().prop_map(|s| PathBuf::from(s).to_string_lossy().into_owned())),
+        "Duration" => text((0..u64::from(u32::MAX), 0..1_000_000_000u32).prop_map(|(s, n)| format_duration(Duration::new(s, n)))),
+        "ByteSize" => (0..=i64::MAX).prop_map(|n| Some(Value::String(ByteSize(n as u64).to_string()))).boxed(),
+        "Ipv4Addr" => text(any::<[u8; 4]>().prop_map(|o| Ipv4Addr::from(o).to_string())),
+        "Ipv6Addr" => text(any::<[u8; 16]>().prop_map(|o| Ipv6Addr::from(o).to_string())),
+        "IpAddr" => text(prop_oneof![
+            any::<[u8; 4]>().prop_map(|o| IpAddr::from(o).to_string()),
+            any::<[u8; 16]>().prop_map(|o| IpAddr::from(o).to_string()),
+        ]),
+        "SocketAddr" => text((any::<[u8; 4]>(), any::<u16>()).prop_map(|(o, p)| SocketAddr::from((o, p)).to_string())),
+        _ => Just(None).boxed(),
+    }
+}
+
+/// Instances of `T` with every built-in value, nested sections included, generated
+pub fn arbitrary<T: Configuration + Debug + 'static>() -> BoxedStrategy<T> {
+    let fields: Vec<FieldMeta> = T::metadata().into_iter().filter(|f| f.nested.is_none()).collect();
+    let values: Vec<BoxedStrategy<Option<Value>>> = fields.iter().map(value_of).collect();
+    values
+        .prop_map(move |values| {
+            let mut config = T::__elp_new();
+            for (field, value) in fields.iter().zip(values) {
+                if let Some(value) = value {
+                    config
+                        .set_path(&field.path, value.clone())
+                        .unwrap_or_else(|e| panic!("generated {} for `{}`: {}", value, field.path, e));
+                }
+            }
+            config
+        })
+        .boxed()
+}
+
+/// Check that `text` reads back as `config`, showing the document when it does not
+fn reads_back<T: Configuration + Debug + PartialEq>(config: &T, text: &str) -> Result<(), TestCaseError> {
+    let read = T::from_toml_string(text).map_err(|e| TestCaseError::fail(format!("{}\n---\n{}", e, text)))?;
+    prop_assert_eq!(&read, config, "\n---\n{}", text);
+    Ok(())
+}
+
+mod types {
+    use super::*;
+
+    /// Upstream service
+    ///
+    /// Used for every outgoing call.
+    #[derive(crate::Configuration, Debug, PartialEq)]
+    pub struct Upstream {
+        /// Host name or address
+        #[config(default = "localhost", example = "api.internal")]
+        pub host: String,
+        #[config(default = 443, min = 1)]
+        pub port: u16,
+        #[config(default = "5s", unit = "s")]
+        pub timeout: Duration,
+        #[config(default = "1MiB")]
+        pub body_limit: ByteSize,
+        #[config(default = "127.0.0.1")]
+        pub bind: IpAddr,
+        #[config(default = "0.0.0.0:8080")]
+        pub listen: SocketAddr,
+        #[config(default = "certs/ca.pem")]
+        pub ca: PathBuf,
+        #[config(default = "", secret)]
+        pub token: String,
+    }
+
+    #[derive(crate::Configuration, Debug, PartialEq)]
+    pub struct Limits {
+        #[config(default = 0.25)]
+        pub ratio: f64,
+        #[config(default = 1.5)]
+        pub scale: f32,
+        #[config(default = -3)]
+        pub offset: i8,
+        #[config(default = 10)]
+        pub burst: u64,
+    }
+
+    /// Every kind of field, with values declared after the sections
+    #[derive(crate::Configuration, Debug, PartialEq)]
+    pub struct Everything {
+        #[config(default = "app", note = "service name\nshown in logs")]
+        pub name: String,
+        pub upstream: Upstream,
+        #[config(note = "per-client limits")]
+        pub limits: Limits,
+        #[config(default = 3)]
+        pub retries: u32,
+        #[config(default = "plain")]
+        pub r#type: String,
+        #[config(default = false, deprecated = "always on")]
+        pub enabled: bool,
+        #[config(default = "::1")]
+        pub ipv6: Ipv6Addr,
+        #[config(default = "10.0.0.1")]
+        pub ipv4: Ipv4Addr,
+        #[config(default = -1)]
+        pub delta: i64,
+    }
+}
+
+use types::{Everything, Limits, Upstream};
+
+#[test]
+fn test_defaults_read_back_with_every_value_commented_out() {
+    let text = Everything::new().to_toml();
+    assert!(text.lines().all(|l| l.is_empty() || l.starts_with('#') || l.starts_with('[')), "{}", text);
+    assert_eq!(Everything::from_toml_string(&text).unwrap(), Everything::new());
+    // Values first, then one full header per section
+    let headers: Vec<&str> = text.lines().filter(|l| l.starts_with('[')).collect();
+    assert_eq!(headers, ["[upstream]", "[limits]"]);
+    assert!(text.find("# type = ").unwrap() < text.find("[upstream]").unwrap(), "{}", text);
+    assert!(text.contains("# Upstream service\n#\n# Used for every outgoing call.\n# Upstream\n[upstream]"), "{}", text);
+    assert!(text.contains("# per-client limits, Limits\n[limits]"), "{}", text);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn test_to_toml_reads_back(config in arbitrary::<Everything>()) {
+        reads_back(&config, &config.to_toml())?;
+    }
+
+    #[test]
+    fn test_full_template_reads_back(config in arbitrary::<Everything>()) {
+        let options = TemplateOptions::new().verbosity(Verbosity::Full).env_prefix("APP").width(40);
+        reads_back(&config, &config.to_toml_with(&options))?;
+    }
+
+    #[test]
+    fn test_sections_read_back_on_their_own(upstream in arbitrary::<Upstream>(), limits in arbitrary::<Limits>()) {
+        reads_back(&upstream, &upstream.to_toml())?;
+        reads_back(&limits, &limits.to_toml())?;
+    }
+}
@@ -0,0 +1,158 @@
+use crate::error::{ConfigurationError, ConfigurationResult};
+use std::fs;
+use std::path::Path;
+
+/// Key of a table standing in for a value read from a file
+///
+/// `password = { from_file = "/run/secrets/db_pw" }` is replaced by the
+/// trimmed contents of `/run/secrets/db_pw` before deserialization.
+pub const FROM_FILE_KEY: &str = "from_file";
+
+/// Suffix of environment variables naming a secret file
+///
+/// With prefix `EAUTH`, `EAUTH_DATABASE__PASSWORD_FILE` sets `database.password`;
+/// `__` separates nesting levels and the rest of the name is lowercased.
+pub const FILE_SUFFIX: &str = "_FILE";
+
+/// Read a secret file for the value at `field`
+///
+/// Surrounding whitespace (including the trailing newline most tools write)
+/// is trimmed. On Unix, files readable by other users are refused.
+pub fn read_secret(path: &Path, field: &str) -> ConfigurationResult<String> {
+    let display = path.display().to_string();
+    let metadata = fs::metadata(path)
+        .map_err(|e| ConfigurationError::secret_error(e.to_string(), &display, field))?;
+    if !metadata.is_file() {
+        return Err(ConfigurationError::secret_error("not a regular file", &display, field));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o004 != 0 {
+            return Err(ConfigurationError::secret_error(
+                format!("file is world-readable (mode {:o}); restrict it with `chmod o-r`", mode),
+                &display,
+                field,
+            ));
+        }
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| ConfigurationError::secret_error(e.to_string(), &display, field))?;
+    Ok(content.trim().to_string())
+}
+
+/// Path of a `{ from_file = "..." }` table, if `value` is one
+pub fn from_file_reference(value: &toml::Value) -> Option<&str> {
+    match value.as_table() {
+        Some(table) if table.len() == 1 => table.get(FROM_FILE_KEY)?.as_str(),
+        _ => None,
+    }
+}
+
+/// Name of the variable naming a file for the value at `path`
+///
+/// With prefix `EAUTH`, `database.password` is `EAUTH_DATABASE__PASSWORD_FILE`.
+pub fn env_file_var(prefix: &str, path: &str) -> String {
+    format!("{}_{}{}", prefix, path.to_uppercase().replace('.', "__"), FILE_SUFFIX)
+}
+
+/// Key paths and files named by `<PREFIX>_..._FILE` variables in `vars`
+///
+/// `vars` is usually [`std::env::vars`]. Returned in sorted order of variable
+/// name so results are deterministic.
+pub fn env_secret_files(
+    prefix: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Vec<(String, String, String)> {
+    let head = format!("{}_", prefix);
+    let mut found: Vec<(String, String, String)> = vars
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(&head)?.strip_suffix(FILE_SUFFIX)?;
+            if key.is_empty() {
+                return None;
+            }
+            let path = key.split("__").map(str::to_lowercase).collect::<Vec<_>>().join(".");
+            Some((name, path, value))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_util::temp_path;
+
+    fn secret_file(name: &str, content: &str, mode: u32) -> std::path::PathBuf {
+        let path = temp_path(&format!("libelp_secret_{}", name));
+        fs::write(&path, content).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        path
+    }
+
+    #[test]
+    fn test_read_secret_trims_content() {
+        let path = secret_file("trim", "  s3cret\n", 0o600);
+        assert_eq!(read_secret(&path, "database.password").unwrap(), "s3cret");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_secret_refuses_world_readable() {
+        let path = secret_file("open", "s3cret", 0o644);
+        match read_secret(&path, "database.password").unwrap_err() {
+            ConfigurationError::SecretError { message, path: p, field } => {
+                assert!(message.contains("world-readable"), "{}", message);
+                assert_eq!(p, path.display().to_string());
+                assert_eq!(field, "database.password");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_missing_secret_is_attributed_to_path() {
+        let err = read_secret(Path::new("/nonexistent/libelp/secret"), "redis.password").unwrap_err();
+        assert!(matches!(err, ConfigurationError::SecretError { ref path, .. } if path == "/nonexistent/libelp/secret"));
+        assert!(err.user_message().contains("redis.password"));
+    }
+
+    #[test]
+    fn test_from_file_reference_requires_single_key_table() {
+        let value: toml::Table = toml::from_str("a = { from_file = \"/x\" }\nb = { from_file = \"/x\", y = 1 }\nc = \"/x\"").unwrap();
+        assert_eq!(from_file_reference(&value["a"]), Some("/x"));
+        assert_eq!(from_file_reference(&value["b"]), None);
+        assert_eq!(from_file_reference(&value["c"]), None);
+    }
+
+    #[test]
+    fn test_env_secret_files_maps_names_to_key_paths() {
+        let vars = [
+            ("LIBELPSECRETTEST_TOKEN_FILE", "/b"),
+            ("LIBELPSECRETTEST_DATABASE__MAX_CONN_FILE", "/a"),
+            ("LIBELPSECRETTEST_TOKEN", "ignored"),
+            ("LIBELPSECRETTEST__FILE", "ignored"),
+            ("OTHER_TOKEN_FILE", "ignored"),
+        ];
+        let found = env_secret_files("LIBELPSECRETTEST", vars.map(|(k, v)| (k.to_string(), v.to_string())));
+        assert_eq!(
+            found,
+            vec![
+                ("LIBELPSECRETTEST_DATABASE__MAX_CONN_FILE".to_string(), "database.max_conn".to_string(), "/a".to_string()),
+                ("LIBELPSECRETTEST_TOKEN_FILE".to_string(), "token".to_string(), "/b".to_string()),
+            ]
+        );
+        assert_eq!(env_file_var("LIBELPSECRETTEST", "database.max_conn"), found[0].0);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::Configuration;
+use crate::config::loader::ConfigLoader;
+use crate::config::path::lookup;
+use crate::error::ConfigurationResult;
+use arc_swap::ArcSwap;
+use serde::Serialize;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+type Callback<T> = Arc<dyn Fn(&T) + Send + Sync>;
+
+/// Live configuration shared between readers and a reloader
+///
+/// [`SharedConfig::load`] hands out an `Arc` snapshot without taking a lock,
+/// so request handlers can call it on every request. [`SharedConfig::store`]
+/// validates a new configuration, swaps it in atomically and then notifies
+/// the subscribers of every section whose values changed. Clones share the
+/// same configuration.
+pub struct SharedConfig<T> {
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    current: ArcSwap<T>,
+    // Held while storing so subscribers see changes in order
+    subscribers: Mutex<Vec<(String, Callback<T>)>>,
+}
+
+impl<T: Configuration + Send + Sync + 'static> SharedConfig<T> {
+    /// Share an already loaded configuration
+    pub fn new(config: T) -> Self {
+        Self {
+            inner: Arc::new(Inner { current: ArcSwap::from_pointee(config), subscribers: Mutex::new(Vec::new()) }),
+        }
+    }
+
+    /// Snapshot of the current configuration
+    ///
+    /// The snapshot stays unchanged while the caller holds it, even if a new
+    /// configuration is stored in the meantime.
+    pub fn load(&self) -> Arc<T> {
+        self.inner.current.load_full()
+    }
+
+    /// Validate `config` and make it current
+    ///
+    /// Invalid configurations are rejected and the current one is kept.
+    /// Subscribers run on the calling thread before `store` returns and must
+    /// not call `store` themselves.
+    pub fn store(&self, config: T) -> ConfigurationResult<()> {
+        config.validate()?;
+        let subscribers = self.subscribers();
+        let new = Arc::new(config);
+        let old = self.inner.current.swap(Arc::clone(&new));
+
+        let (old_value, new_value) = (to_value(&*old), to_value(&*new));
+        for (section, callback) in subscribers.iter() {
+            if section_of(&old_value, section) != section_of(&new_value, section) {
+                callback(&new);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a fresh configuration with `loader` and store it
+    pub fn reload(&self, loader: &ConfigLoader) -> ConfigurationResult<()> {
+        self.store(loader.load()?)
+    }
+
+    /// Call `callback` with the new configuration whenever values under `section` change
+    ///
+    /// `section` is a dotted path such as `"database"` or `"database.pool"`;
+    /// an empty path subscribes to every change.
+    pub fn subscribe(&self, section: &str, callback: impl Fn(&T) + Send + Sync + 'static) {
+        self.subscribers().push((section.to_string(), Arc::new(callback)));
+    }
+
+    /// Handle to the part of the configuration selected by `project`
+    ///
+    /// ```rust,ignore
+    /// let database = shared.map(|c: &Config| &c.database);
+    /// let url = &database.load().url;
+    /// ```
+    pub fn map<U>(&self, project: fn(&T) -> &U) -> MappedConfig<T, U> {
+        MappedConfig { shared: self.clone(), project }
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, Vec<(String, Callback<T>)>> {
+        // A panicking subscriber leaves nothing half-updated behind
+        self.inner.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T> Clone for SharedConfig<T> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SharedConfig<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedConfig").field(&self.inner.current.load()).finish()
+    }
+}
+
+/// Handle to one part of a [`SharedConfig`], created by [`SharedConfig::map`]
+pub struct MappedConfig<T, U> {
+    shared: SharedConfig<T>,
+    project: fn(&T) -> &U,
+}
+
+impl<T: Configuration + Send + Sync + 'static, U: Serialize + 'static> MappedConfig<T, U> {
+    /// Snapshot of the current value of this part
+    pub fn load(&self) -> Snapshot<T, U> {
+        Snapshot { config: self.shared.load(), project: self.project }
+    }
+
+    /// Call `callback` with the new value whenever this part changes
+    pub fn subscribe(&self, callback: impl Fn(&U) + Send + Sync + 'static) {
+        let project = self.project;
+        let last = Mutex::new(to_value(project(&self.shared.load())));
+        self.shared.subscribe("", move |config| {
+            let value = to_value(project(config));
+            let mut last = last.lock().unwrap_or_else(|e| e.into_inner());
+            if *last != value {
+                *last = value;
+                callback(project(config));
+            }
+        });
+    }
+}
+
+impl<T, U> Clone for MappedConfig<T, U> {
+    fn clone(&self) -> Self {
+        Self { shared: self.shared.clone(), project: self.project }
+    }
+}
+
+/// Configuration snapshot viewed through a [`MappedConfig`] projection
+pub struct Snapshot<T, U> {
+    config: Arc<T>,
+    project: fn(&T) -> &U,
+}
+
+impl<T, U> Deref for Snapshot<T, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        (self.project)(&self.config)
+    }
+}
+
+impl<T, U: fmt::Debug> fmt::Debug for Snapshot<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// Values that fail to serialize compare as missing
+fn to_value<S: Serialize + ?Sized>(value: &S) -> Option<toml::Value> {
+    toml::Value::try_from(value).ok()
+}
+
+fn section_of<'a>(value: &'a Option<toml::Value>, path: &str) -> Option<&'a toml::Value> {
+    lookup(value.as_ref()?, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ConfigurationError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(libelp::Configuration, Debug, Clone)]
+    struct Database {
+        #[config(default = "postgres://localhost/eauth")]
+        url: String,
+        #[config(default = 10)]
+        pool: u32,
+    }
+
+    #[derive(libelp::Configuration, Debug, Clone)]
+    struct Server {
+        #[config(default = 8080)]
+        port: u16,
+    }
+
+    #[derive(libelp::Configuration, Debug, Clone)]
+    struct Config {
+        server: Server,
+        database: Database,
+    }
+
+    fn counter(shared: &SharedConfig<Config>, section: &str) -> Arc<AtomicUsize> {
+        let count = Arc::new(AtomicUsize::new(0));
+        let seen = Arc::clone(&count);
+        shared.subscribe(section, move |_| {
+            seen.fetch_add(1, Ordering::SeqCst);
+        });
+        count
+    }
+
+    #[test]
+    fn test_store_swaps_snapshot_and_notifies_changed_sections() {
+        let shared = SharedConfig::new(Config::new());
+        let before = shared.load();
+        let (server, database, pool, all) =
+            (counter(&shared, "server"), counter(&shared, "database"), counter(&shared, "database.pool"), counter(&shared, ""));
+
+        let mut next = Config::new();
+        next.database.url = "postgres://db/eauth".to_string();
+        shared.store(next.clone()).unwrap();
+        assert_eq!(before.database.url, "postgres://localhost/eauth");
+        assert_eq!(shared.clone().load().database.url, "postgres://db/eauth");
+
+        // Storing an equal configuration notifies nobody
+        shared.store(next).unwrap();
+        let counts: Vec<usize> = [server, database, pool, all].iter().map(|c| c.load(Ordering::SeqCst)).collect();
+        assert_eq!(counts, vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_store_rejects_invalid_config() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug)]
+        struct Limits {
+            workers: u32,
+        }
+        impl Configuration for Limits {
+            fn new() -> Self {
+                Self { workers: 1 }
+            }
+
+            fn validate(&self) -> ConfigurationResult<()> {
+                if self.workers == 0 {
+                    return Err(ConfigurationError::validation_error("Workers cannot be zero", Some("workers".to_string())));
+                }
+                Ok(())
+            }
+        }
+
+        let shared = SharedConfig::new(Limits::new());
+        let err = shared.store(Limits { workers: 0 }).unwrap_err();
+        assert!(matches!(err, ConfigurationError::ValidationError { .. }), "{:?}", err);
+        assert!(matches!(
+            shared.reload(&ConfigLoader::new().file("/nonexistent/eauth.toml")),
+            Err(ConfigurationError::FileError { .. })
+        ));
+        assert_eq!(shared.load().workers, 1);
+    }
+
+    #[test]
+    fn test_map_projects_and_subscribes_to_one_section() {
+        let shared = SharedConfig::new(Config::new());
+        let database = shared.map(|c: &Config| &c.database);
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&changes);
+        database.subscribe(move |db: &Database| seen.lock().unwrap().push(db.pool));
+
+        let mut next = Config::new();
+        next.server.port = 9090;
+        shared.store(next.clone()).unwrap();
+        next.database.pool = 20;
+        shared.store(next).unwrap();
+
+        assert_eq!(database.load().pool, 20);
+        assert_eq!(*changes.lock().unwrap(), vec![20]);
+    }
+}
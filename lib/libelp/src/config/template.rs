@@ -0,0 +1,646 @@
+use crate::Configuration;
+use crate::config::format::Format;
+use crate::config::meta::FieldMeta;
+use crate::config::secret::env_file_var;
+use crate::config::value::{comment, inline};
+use crate::error::{ConfigurationError, ConfigurationResult};
+use serde::Serialize;
+
+/// How much the derived `to_toml_with()` writes above each field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// One line with the note, type and default, as `to_toml()` writes
+    #[default]
+    Minimal,
+    /// A wrapped block listing the note, type, unit, default, example,
+    /// allowed values, constraints, environment variable and deprecation
+    Full,
+}
+
+/// Options of the derived `to_toml_with()`
+///
+/// ```rust,ignore
+/// let options = TemplateOptions::new().verbosity(Verbosity::Full).env_prefix("EAUTH");
+/// std::fs::write("eauth.toml", config.to_toml_with(&options))?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct TemplateOptions {
+    verbosity: Verbosity,
+    env_prefix: Option<String>,
+    width: usize,
+}
+
+impl Default for TemplateOptions {
+    fn default() -> Self {
+        Self { verbosity: Verbosity::Minimal, env_prefix: None, width: 80 }
+    }
+}
+
+impl TemplateOptions {
+    /// Minimal comments, wrapped at 80 columns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Choose between one-line and full comment blocks
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Name the `<PREFIX>_..._FILE` variable of each field in full blocks
+    pub fn env_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Column at which full blocks are wrapped
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+/// Comment lines the derived `to_toml_with()` writes above a field
+///
+/// `default` is the computed default and `path` the field's dotted key path.
+/// Required fields are marked `REQUIRED`.
+#[doc(hidden)]
+pub fn field_comment(
+    meta: &FieldMeta,
+    type_name: &str,
+    path: &str,
+    default: &toml::Value,
+    options: &TemplateOptions,
+) -> String {
+    // `default` of a field without one is only its placeholder
+    let default = (!meta.is_mandatory()).then(|| inline(default));
+    if options.verbosity == Verbosity::Minimal {
+        let mut parts: Vec<&str> = vec![type_name];
+        let unit = meta.unit.map(|unit| format!("unit: {}", unit));
+        parts.extend(unit.as_deref());
+        let default = default.map(|default| format!("default: {}", default));
+        parts.extend(default.as_deref());
+        if meta.required {
+            parts.push("REQUIRED");
+        }
+        return comment(&with_summary(meta.note, &parts.join(", ")));
+    }
+
+    let mut entries: Vec<String> = Vec::new();
+    if meta.required {
+        entries.push("REQUIRED: loading fails without this key".to_string());
+    }
+    entries.push(format!("Type: {}", type_name));
+    if let Some(unit) = meta.unit {
+        entries.push(format!("Unit: {}", unit));
+    }
+    if let Some(default) = default {
+        entries.push(format!("Default: {}", default));
+    }
+    if let Some(example) = meta.example {
+        entries.push(format!("Example: {}", example));
+    }
+    if let Some(allowed) = meta.allowed {
+        entries.push(format!("Allowed values: {}", allowed.join(", ")));
+    }
+    if !meta.constraints.is_empty() {
+        let constraints: Vec<String> = meta.constraints.iter().map(ToString::to_string).collect();
+        entries.push(format!("Constraints: {}", constraints.join(", ")));
+    }
+    if let Some(prefix) = &options.env_prefix {
+        entries.push(format!("Environment: {} (file holding the value)", env_file_var(prefix, path)));
+    }
+    if meta.secret {
+        entries.push("Secret: keep this file private".to_string());
+    }
+    if let Some(reason) = meta.deprecated {
+        entries.push(format!("Deprecated: {}", reason));
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    if let Some(note) = meta.note {
+        lines.extend(wrap(note, options.width, ""));
+        lines.push(String::new());
+    }
+    for entry in &entries {
+        lines.extend(wrap(entry, options.width, "  "));
+    }
+    comment(&lines.join("\n"))
+}
+
+/// Comment lines written above a section of type `type_name`, or above the whole document
+///
+/// Sections are always described; the document only when it has a note.
+#[doc(hidden)]
+pub fn section_comment(note: Option<&str>, type_name: Option<&str>, options: &TemplateOptions) -> Option<String> {
+    let text = match (options.verbosity, note, type_name) {
+        (_, None, None) => return None,
+        (Verbosity::Minimal, note, Some(type_name)) => with_summary(note, type_name),
+        (Verbosity::Minimal, Some(note), None) => note.to_string(),
+        (Verbosity::Full, note, type_name) => {
+            let mut lines = note.map(|note| wrap(note, options.width, "")).unwrap_or_default();
+            if let Some(type_name) = type_name {
+                if note.is_some() {
+                    lines.push(String::new());
+                }
+                lines.push(format!("Type: {} (section)", type_name));
+            }
+            lines.join("\n")
+        }
+    };
+    Some(comment(&text))
+}
+
+// A one-line note shares its line with `summary`, e.g. `port, u16`; after a
+// longer one the summary gets a line of its own
+fn with_summary(note: Option<&str>, summary: &str) -> String {
+    match note {
+        Some(note) if note.contains('\n') => format!("{}\n{}", note, summary),
+        Some(note) => format!("{}, {}", note, summary),
+        None => summary.to_string(),
+    }
+}
+
+/// A key as written in TOML: bare when possible, quoted otherwise
+#[doc(hidden)]
+pub fn toml_key(key: &str) -> String {
+    bare_or_quoted(key, is_toml_bare)
+}
+
+// Greedy word wrap leaving room for `# `; continuation lines start with `indent`
+//
+// As in rustdoc, blank lines separate paragraphs and other line breaks are reflowed.
+fn wrap(text: &str, width: usize, indent: &str) -> Vec<String> {
+    let width = width.saturating_sub(2).max(20);
+    let mut out = Vec::new();
+    for paragraph in text.split("\n\n") {
+        if !out.is_empty() {
+            out.push(String::new());
+        }
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.len() + 1 + word.len() > width {
+                out.push(std::mem::replace(&mut line, indent.to_string()));
+            }
+            if !line.trim().is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        out.push(line);
+    }
+    out
+}
+
+/// Render a commented configuration template in the given format
+///
+/// Every field gets a comment line with its `note`, value kind and default,
+/// followed by the value itself; values equal to the defaults
+/// are commented out, as in the derived `to_toml()`. Required fields are
+/// marked `REQUIRED` and always written. JSON has no comments, so its
+/// template is the plain serialized document.
+pub fn render_template<T: Configuration>(config: &T, format: Format) -> ConfigurationResult<String> {
+    if format == Format::Json || !format.is_enabled() {
+        return format.serialize(config);
+    }
+    let values = to_table(format, config)?;
+    let defaults = to_table(format, &T::__elp_new())?;
+    render(format, &values, Some(&defaults), &T::field_note, &T::metadata())
+}
+
+/// Serialize a value into the TOML table tree walked by the template writers
+pub(crate) fn to_table<T: Serialize>(format: Format, value: &T) -> ConfigurationResult<toml::Table> {
+    let error = |message: String| match format {
+        Format::Toml => ConfigurationError::toml_serialize_error(message),
+        _ => ConfigurationError::serialize_error(format, message),
+    };
+    match toml::Value::try_from(value).map_err(|e| error(e.to_string()))? {
+        toml::Value::Table(table) => Ok(table),
+        other => Err(error(format!("expected a table at the top level, found {}", other.type_str()))),
+    }
+}
+
+/// Write `table` in `format`; comments are emitted only when `defaults` is given
+///
+/// `fields` tells which keys are required.
+pub(crate) fn render(
+    format: Format,
+    table: &toml::Table,
+    defaults: Option<&toml::Table>,
+    notes: &dyn Fn(&str) -> Option<&'static str>,
+    fields: &[FieldMeta],
+) -> ConfigurationResult<String> {
+    let mut writer = Writer { format, defaults, notes, fields, lines: Vec::new() };
+    match format {
+        Format::Toml => writer.toml_table(table, &[]),
+        Format::Yaml => writer.yaml_table(table, &[]),
+        Format::Json5 => {
+            writer.lines.push("{".to_string());
+            writer.json5_table(table, &[]);
+            writer.trim_blank();
+            writer.lines.push("}".to_string());
+        }
+        Format::Ini => writer.ini(table)?,
+        Format::Json => return format.serialize(table),
+    }
+    let mut out = writer.lines.join("\n");
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    Ok(out)
+}
+
+struct Writer<'a> {
+    format: Format,
+    defaults: Option<&'a toml::Table>,
+    notes: &'a dyn Fn(&str) -> Option<&'static str>,
+    fields: &'a [FieldMeta],
+    lines: Vec<String>,
+}
+
+impl Writer<'_> {
+    /// Push one `key <sep> value` entry, annotated and commented out when it equals the default
+    fn entry(&mut self, path: &[&str], indent: &str, line: String, value: &toml::Value) {
+        let Some(defaults) = self.defaults else {
+            self.lines.push(format!("{}{}", indent, line));
+            return;
+        };
+        let prefix = self.format.comment_prefix().unwrap_or("#");
+        let dotted = path.join(".");
+        let field = self.fields.iter().find(|f| f.path == dotted);
+        let required = field.is_some_and(|f| f.required);
+        // The placeholder of a field without a default is no default
+        let default = lookup(defaults, path).filter(|_| !field.is_some_and(FieldMeta::is_mandatory));
+
+        let note = (self.notes)(&dotted).filter(|n| !n.is_empty());
+        let mut parts: Vec<String> = vec![kind(value).to_string()];
+        if let Some(default) = default {
+            parts.push(format!("default: {}", self.scalar(default)));
+        }
+        if required {
+            parts.push("REQUIRED".to_string());
+        }
+        for text in with_summary(note, &parts.join(", ")).lines() {
+            self.lines.push(format!("{}{} {}", indent, prefix, text).trim_end().to_string());
+        }
+
+        if default == Some(value) && !required {
+            self.lines.push(format!("{}{} {}", indent, prefix, line));
+        } else {
+            self.lines.push(format!("{}{}", indent, line));
+        }
+        self.lines.push(String::new());
+    }
+
+    fn toml_table(&mut self, table: &toml::Table, path: &[&str]) {
+        for (key, value) in table.iter().filter(|(_, v)| !v.is_table()) {
+            let full = [path, &[key.as_str()]].concat();
+            self.entry(&full, "", format!("{} = {}", bare_or_quoted(key, is_toml_bare), value), value);
+        }
+        for (key, value) in table.iter() {
+            if let toml::Value::Table(sub) = value {
+                let full = [path, &[key.as_str()]].concat();
+                let header: Vec<String> = full.iter().map(|k| bare_or_quoted(k, is_toml_bare)).collect();
+                if self.defaults.is_none() && !self.lines.is_empty() {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{}]", header.join(".")));
+                self.toml_table(sub, &full);
+                if self.defaults.is_some() {
+                    self.lines.push(String::new());
+                }
+            }
+        }
+    }
+
+    fn yaml_table(&mut self, table: &toml::Table, path: &[&str]) {
+        let indent = "  ".repeat(path.len());
+        for (key, value) in table.iter() {
+            let full = [path, &[key.as_str()]].concat();
+            let key_text = bare_or_quoted(key, is_toml_bare);
+            match value {
+                toml::Value::Table(sub) if !sub.is_empty() => {
+                    self.lines.push(format!("{}{}:", indent, key_text));
+                    self.yaml_table(sub, &full);
+                }
+                _ => {
+                    let line = format!("{}: {}", key_text, self.scalar(value));
+                    self.entry(&full, &indent, line, value);
+                }
+            }
+        }
+    }
+
+    fn json5_table(&mut self, table: &toml::Table, path: &[&str]) {
+        let indent = "  ".repeat(path.len() + 1);
+        for (key, value) in table.iter() {
+            let full = [path, &[key.as_str()]].concat();
+            let key_text = bare_or_quoted(key, is_json5_identifier);
+            match value {
+                toml::Value::Table(sub) => {
+                    self.lines.push(format!("{}{}: {{", indent, key_text));
+                    self.json5_table(sub, &full);
+                    self.trim_blank();
+                    self.lines.push(format!("{}}},", indent));
+                }
+                _ => {
+                    let line = format!("{}: {},", key_text, self.scalar(value));
+                    self.entry(&full, &indent, line, value);
+                }
+            }
+        }
+    }
+
+    fn ini(&mut self, table: &toml::Table) -> ConfigurationResult<()> {
+        let unsupported = |path: String| {
+            ConfigurationError::serialize_error(
+                Format::Ini,
+                format!("'{}': INI supports only scalar values in at most one level of sections", path),
+            )
+        };
+        for (key, value) in table.iter().filter(|(_, v)| !v.is_table()) {
+            if matches!(value, toml::Value::Array(_)) {
+                return Err(unsupported(key.clone()));
+            }
+            self.entry(&[key], "", format!("{} = {}", key, self.scalar(value)), value);
+        }
+        for (section, value) in table.iter() {
+            let toml::Value::Table(sub) = value else { continue };
+            if !self.lines.is_empty() && self.defaults.is_none() {
+                self.lines.push(String::new());
+            }
+            self.lines.push(format!("[{}]", section));
+            for (key, value) in sub.iter() {
+                if matches!(value, toml::Value::Table(_) | toml::Value::Array(_)) {
+                    return Err(unsupported(format!("{}.{}", section, key)));
+                }
+                self.entry(&[section, key], "", format!("{} = {}", key, self.scalar(value)), value);
+            }
+        }
+        Ok(())
+    }
+
+    fn trim_blank(&mut self) {
+        while self.lines.last().is_some_and(|l| l.is_empty()) {
+            self.lines.pop();
+        }
+    }
+
+    /// Inline representation of a value in the writer's format
+    fn scalar(&self, value: &toml::Value) -> String {
+        match self.format {
+            Format::Toml => value.to_string(),
+            _ => flow(self.format, value),
+        }
+    }
+}
+
+/// JSON-style flow representation, valid in YAML, JSON5 and our INI dialect
+fn flow(format: Format, value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => quote(s),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) if f.is_nan() => match format {
+            Format::Yaml => ".nan".to_string(),
+            Format::Json5 => "NaN".to_string(),
+            _ => "nan".to_string(),
+        },
+        toml::Value::Float(f) if f.is_infinite() => {
+            let sign = if *f < 0.0 { "-" } else { "" };
+            match format {
+                Format::Yaml => format!("{}.inf", sign),
+                Format::Json5 => format!("{}Infinity", sign),
+                _ => format!("{}inf", sign),
+            }
+        }
+        toml::Value::Float(f) => {
+            let s = f.to_string();
+            if s.contains(['.', 'e', 'E']) { s } else { format!("{}.0", s) }
+        }
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Datetime(d) => quote(&d.to_string()),
+        toml::Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(|v| flow(format, v)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        toml::Value::Table(table) => {
+            let items: Vec<String> = table.iter().map(|(k, v)| format!("{}: {}", quote(k), flow(format, v))).collect();
+            format!("{{{}}}", items.join(", "))
+        }
+    }
+}
+
+/// Double-quoted string with JSON escapes (also valid TOML basic string and YAML)
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn bare_or_quoted(key: &str, is_bare: fn(&str) -> bool) -> String {
+    if is_bare(key) { key.to_string() } else { quote(key) }
+}
+
+fn is_toml_bare(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn is_json5_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn lookup<'a>(table: &'a toml::Table, path: &[&str]) -> Option<&'a toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut current = table;
+    for key in parents {
+        current = current.get(*key)?.as_table()?;
+    }
+    current.get(*last)
+}
+
+fn kind(value: &toml::Value) -> &'static str {
+    match value {
+        toml::Value::String(_) => "string",
+        toml::Value::Integer(_) => "integer",
+        toml::Value::Float(_) => "float",
+        toml::Value::Boolean(_) => "boolean",
+        toml::Value::Datetime(_) => "datetime",
+        toml::Value::Array(_) => "array",
+        toml::Value::Table(_) => "table",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct Db {
+        #[config(default = "localhost", note = "db host")]
+        host: String,
+        #[config(default = 5432, note = "db port")]
+        port: u16,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct App {
+        #[config(default = true, note = "enable feature")]
+        feature: bool,
+        #[config(default = 0.5, note = "sampling ratio")]
+        ratio: f64,
+        database: Db,
+    }
+
+    fn customized() -> App {
+        let mut app = App::new();
+        app.database.port = 6000;
+        app
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Level {
+        Info,
+        Debug,
+    }
+
+    impl crate::ConfigValue for Level {
+        const ALLOWED_VALUES: Option<&'static [&'static str]> = Some(&["info", "debug"]);
+
+        fn type_name() -> &'static str {
+            "Level"
+        }
+
+        fn from_toml_value(value: &toml::Value) -> Result<Self, String> {
+            match value.as_str() {
+                Some("info") => Ok(Level::Info),
+                Some("debug") => Ok(Level::Debug),
+                _ => Err("expected info or debug".to_string()),
+            }
+        }
+
+        fn to_toml_value(&self) -> toml::Value {
+            toml::Value::String(if *self == Level::Info { "info" } else { "debug" }.to_string())
+        }
+    }
+
+    /// Upstream HTTP client
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Client {
+        /// Time to wait for the upstream before the request is failed and retried on the next
+        /// address
+        #[config(default = 500, unit = "ms", min = 10, example = 250)]
+        timeout: u64,
+        #[config(default = "", secret, deprecated = "use `token_file`")]
+        token: String,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Service {
+        #[config(default = "info", note = "log level")]
+        level: Level,
+        #[config(note = "calls to the billing API")]
+        client: Client,
+    }
+
+    #[test]
+    fn test_to_toml_with_full_verbosity() {
+        let minimal = Service::new().to_toml();
+        assert!(minimal.contains("# log level, Level, default: \"info\"\n# level = \"info\""), "{}", minimal);
+        assert!(minimal.contains("# calls to the billing API, Client\n[client]\n"), "{}", minimal);
+        assert!(minimal.contains("# address\n# u64, unit: ms, default: 500\n"), "{}", minimal);
+
+        let options = TemplateOptions::new().verbosity(Verbosity::Full).env_prefix("SVC").width(60);
+        let full = Service::new().to_toml_with(&options);
+        assert!(full.contains("# log level\n#\n# Type: Level\n# Default: \"info\"\n# Allowed values: info, debug\n"), "{}", full);
+        assert!(full.contains("# Environment: SVC_LEVEL_FILE (file holding the value)\n# level = \"info\""), "{}", full);
+        assert!(full.contains("# Type: u64\n# Unit: ms\n# Default: 500\n# Example: 250\n# Constraints: at least 10\n"), "{}", full);
+        assert!(full.contains("# Environment: SVC_CLIENT__TIMEOUT_FILE (file holding the\n#   value)"), "{}", full);
+        assert!(full.contains("# Secret: keep this file private\n# Deprecated: use `token_file`\n"), "{}", full);
+        assert!(!full.contains("Upstream HTTP client"), "{}", full);
+        assert!(full.lines().all(|line| line.len() <= 60), "{}", full);
+        assert!(full.contains("# Time to wait for the upstream before the request is failed\n# and retried on the next address\n#\n# Type: u64"), "{}", full);
+
+        assert_eq!(Client::new().to_toml_with(&options).lines().next(), Some("# Upstream HTTP client"));
+        assert_eq!(Service::FIELDS[0].allowed, Some(&["info", "debug"][..]));
+    }
+
+    #[test]
+    fn test_toml_template_comments_defaults_and_roundtrips() {
+        let out = render_template(&customized(), Format::Toml).unwrap();
+        assert!(out.contains("# enable feature, boolean, default: true\n# feature = true"), "{}", out);
+        assert!(out.contains("[database]"), "{}", out);
+        assert!(out.contains("# db port, integer, default: 5432\nport = 6000"), "{}", out);
+
+        let uncommented: String = out
+            .lines()
+            .map(|l| l.strip_prefix("# ").filter(|r| r.contains(" = ")).unwrap_or(l))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(App::from_toml_string(&uncommented).unwrap(), customized());
+    }
+
+    #[test]
+    fn test_plain_render_has_no_comments() {
+        let table = to_table(Format::Toml, &customized()).unwrap();
+        let out = render(Format::Toml, &table, None, &|_| None, &[]).unwrap();
+        assert!(!out.contains('#'), "{}", out);
+        assert_eq!(App::from_toml_string(&out).unwrap(), customized());
+    }
+
+    #[test]
+    fn test_flow_values_and_quoting() {
+        assert_eq!(quote("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+        assert_eq!(flow(Format::Yaml, &toml::Value::Float(1.0)), "1.0");
+        assert_eq!(flow(Format::Json5, &toml::Value::Float(f64::NEG_INFINITY)), "-Infinity");
+        let arr = toml::Value::Array(vec![toml::Value::Integer(1), toml::Value::String("x".into())]);
+        assert_eq!(flow(Format::Yaml, &arr), "[1, \"x\"]");
+        assert!(is_json5_identifier("max_conn"));
+        assert!(!is_json5_identifier("max-conn"));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_template_is_commented_and_parses() {
+        let out = render_template(&customized(), Format::Yaml).unwrap();
+        assert!(out.contains("database:\n"), "{}", out);
+        assert!(out.contains("  # db port, integer, default: 5432\n  port: 6000"), "{}", out);
+        assert!(out.contains("# feature: true"), "{}", out);
+    }
+
+    #[cfg(feature = "json5")]
+    #[test]
+    fn test_json5_template_uses_line_comments() {
+        let out = render_template(&customized(), Format::Json5).unwrap();
+        assert!(out.starts_with("{\n"), "{}", out);
+        assert!(out.contains("    // db port, integer, default: 5432\n    port: 6000,"), "{}", out);
+        assert!(out.contains("  // feature: true,"), "{}", out);
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn test_ini_template_and_nested_rejection() {
+        let out = render_template(&customized(), Format::Ini).unwrap();
+        assert!(out.contains("; db port, integer, default: 5432\nport = 6000"), "{}", out);
+
+        let mut nested = toml::Table::new();
+        let mut inner = toml::Table::new();
+        inner.insert("deep".into(), toml::Value::Table(toml::Table::new()));
+        nested.insert("section".into(), toml::Value::Table(inner));
+        let err = render(Format::Ini, &nested, None, &|_| None, &[]).unwrap_err();
+        assert!(matches!(err, ConfigurationError::SerializeError { format: Format::Ini, .. }));
+    }
+}
@@ -0,0 +1,27 @@
+//! Helpers shared by the unit tests
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Held by tests that change the process environment, so they run one at a time
+pub static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Path under the system temp directory that no other call returns
+///
+/// Nothing is created; add an extension with [`PathBuf::with_extension`].
+pub fn temp_path(prefix: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("{}_{}_{}", prefix, nanos, count))
+}
+
+/// Fresh empty directory under the system temp directory
+pub fn temp_dir(prefix: &str) -> PathBuf {
+    let dir = temp_path(prefix);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
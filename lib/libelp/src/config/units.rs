@@ -0,0 +1,303 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+// The grammar below is mirrored by `libelp-proc-internal` to check
+// `#[config(default = "...")]` values at compile time; keep both in sync.
+
+const DURATION_UNITS: &[(&str, u128)] = &[
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+const BYTE_UNITS: &[(&str, u64)] = &[
+    ("b", 1),
+    ("kb", 1_000),
+    ("mb", 1_000_000),
+    ("gb", 1_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+];
+
+/// Parse a human duration such as `30s`, `5m`, `1h30m` or `250ms`
+///
+/// Units are `d`, `h`, `m`, `s`, `ms`, `us` and `ns`; a bare `0` is also accepted.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    if text == "0" {
+        return Ok(Duration::ZERO);
+    }
+    if text.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    let mut total: u128 = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("expected a number at `{}`", rest));
+        }
+        let number: u128 = rest[..digits].parse().map_err(|e| format!("{}", e))?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest.find(|c: char| c.is_ascii_digit() || c.is_whitespace()).unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        let scale = DURATION_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, scale)| *scale)
+            .ok_or_else(|| {
+                if unit.is_empty() {
+                    format!("missing unit after `{}` (use d, h, m, s, ms, us or ns)", number)
+                } else {
+                    format!("unknown unit `{}` (use d, h, m, s, ms, us or ns)", unit)
+                }
+            })?;
+        total = number
+            .checked_mul(scale)
+            .and_then(|n| total.checked_add(n))
+            .ok_or_else(|| "duration is too large".to_string())?;
+        rest = rest[unit_len..].trim_start();
+    }
+    let secs = u64::try_from(total / 1_000_000_000).map_err(|_| "duration is too large".to_string())?;
+    Ok(Duration::new(secs, (total % 1_000_000_000) as u32))
+}
+
+/// Render a duration in the form accepted by [`parse_duration`], e.g. `1h30m`
+pub fn format_duration(duration: Duration) -> String {
+    let mut rest = duration.as_nanos();
+    if rest == 0 {
+        return "0s".to_string();
+    }
+    let mut out = String::new();
+    for (unit, scale) in DURATION_UNITS {
+        let count = rest / scale;
+        if count > 0 {
+            out.push_str(&format!("{}{}", count, unit));
+            rest %= scale;
+        }
+    }
+    out
+}
+
+/// Serde adapter for `std::time::Duration` fields using human strings
+///
+/// Used by the `ConfigValue` impl for `Duration` and usable with
+/// `#[serde(with = "libelp::config::units::duration")]`. Integers are
+/// accepted as seconds when reading.
+pub mod duration {
+    use super::*;
+    use std::borrow::Borrow;
+
+    /// Serialize as a human string such as `30s`
+    pub fn serialize<B, S>(value: &B, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        B: Borrow<Duration>,
+        S: Serializer,
+    {
+        serializer.serialize_str(&format_duration(*value.borrow()))
+    }
+
+    /// Deserialize from a human string or a number of seconds
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match HumanOrNumber::deserialize(deserializer)? {
+            HumanOrNumber::Text(text) => parse_duration(&text)
+                .map_err(|e| serde::de::Error::custom(format!("invalid duration `{}`: {}", text, e))),
+            HumanOrNumber::Number(secs) => Ok(Duration::from_secs(secs)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "a string with a unit or a non-negative integer")]
+enum HumanOrNumber {
+    Number(u64),
+    Text(String),
+}
+
+/// A size in bytes written with a unit, e.g. `10MiB` or `512KB`
+///
+/// Decimal (`KB`, `MB`, `GB`, `TB`) and binary (`KiB`, `MiB`, `GiB`, `TiB`)
+/// units are accepted case-insensitively; a bare integer is a number of bytes.
+/// Rendering picks the shortest exact form.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    /// Size in bytes
+    pub const fn bytes(self) -> u64 {
+        self.0
+    }
+
+    /// `n` kibibytes
+    pub const fn kib(n: u64) -> Self {
+        Self(n << 10)
+    }
+
+    /// `n` mebibytes
+    pub const fn mib(n: u64) -> Self {
+        Self(n << 20)
+    }
+
+    /// `n` gibibytes
+    pub const fn gib(n: u64) -> Self {
+        Self(n << 30)
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+        if digits == 0 {
+            return Err(format!("expected a number at `{}`", text));
+        }
+        let number: u64 = text[..digits].parse().map_err(|e| format!("{}", e))?;
+        let unit = text[digits..].trim();
+        let scale = if unit.is_empty() {
+            1
+        } else {
+            BYTE_UNITS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                .map(|(_, scale)| *scale)
+                .ok_or_else(|| format!("unknown unit `{}` (use B, KB, MB, GB, TB, KiB, MiB, GiB or TiB)", unit))?
+        };
+        number
+            .checked_mul(scale)
+            .map(ByteSize)
+            .ok_or_else(|| "size is too large".to_string())
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (count, unit) = BYTE_UNITS
+            .iter()
+            .filter(|(_, scale)| self.0.is_multiple_of(*scale))
+            .map(|(name, scale)| (self.0 / scale, *name))
+            .min_by_key(|(count, name)| (*count, name.len() == 2))
+            .unwrap_or((self.0, "b"));
+        let unit = match unit {
+            "b" => "B".to_string(),
+            other => {
+                let mut unit = other.to_uppercase();
+                if unit.len() == 3 {
+                    unit.replace_range(1..2, "i");
+                }
+                unit
+            }
+        };
+        write!(f, "{}{}", count, unit)
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match HumanOrNumber::deserialize(deserializer)? {
+            HumanOrNumber::Text(text) => text
+                .parse()
+                .map_err(|e| serde::de::Error::custom(format!("invalid byte size `{}`: {}", text, e))),
+            HumanOrNumber::Number(bytes) => Ok(ByteSize(bytes)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Configuration;
+
+    #[test]
+    fn test_parse_and_format_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("1h 30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert!(parse_duration("30").unwrap_err().contains("missing unit"));
+        assert!(parse_duration("3 weeks").unwrap_err().contains("unknown unit `weeks`"));
+        assert!(parse_duration("").is_err());
+
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1s500ms");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_secs(2 * 86_400)), "2d");
+    }
+
+    #[test]
+    fn test_parse_and_format_byte_size() {
+        assert_eq!("10MiB".parse::<ByteSize>().unwrap(), ByteSize::mib(10));
+        assert_eq!("512 kb".parse::<ByteSize>().unwrap(), ByteSize(512_000));
+        assert_eq!("4096".parse::<ByteSize>().unwrap(), ByteSize(4096));
+        assert!("10 MiBs".parse::<ByteSize>().is_err());
+
+        assert_eq!(ByteSize::mib(10).to_string(), "10MiB");
+        assert_eq!(ByteSize(2_000_000).to_string(), "2MB");
+        assert_eq!(ByteSize(1_024_000).to_string(), "1000KiB");
+        assert_eq!(ByteSize(1023).to_string(), "1023B");
+        assert_eq!(ByteSize(0).to_string(), "0B");
+    }
+
+    #[derive(libelp_proc::Configuration, Debug, PartialEq)]
+    struct Http {
+        #[config(default = "30s", note = "request timeout")]
+        timeout: Duration,
+        #[config(default = "10MiB", note = "largest accepted body")]
+        max_body: ByteSize,
+        #[config(default = 1024, note = "buffer size")]
+        buffer: ByteSize,
+        idle: Duration,
+    }
+
+    #[test]
+    fn test_derive_parses_defaults_and_human_values() {
+        let http = Http::new();
+        assert_eq!(http.timeout, Duration::from_secs(30));
+        assert_eq!(http.max_body, ByteSize::mib(10));
+        assert_eq!(http.buffer, ByteSize(1024));
+        assert_eq!(http.idle, Duration::ZERO);
+
+        let http = Http::from_toml_string("timeout = \"1m30s\"\nmax_body = \"2GiB\"\nbuffer = 64\nidle = 5\n").unwrap();
+        assert_eq!(http.timeout, Duration::from_secs(90));
+        assert_eq!(http.max_body, ByteSize::gib(2));
+        assert_eq!(http.buffer, ByteSize(64));
+        assert_eq!(http.idle, Duration::from_secs(5));
+
+        let err = Http::from_toml_string("timeout = \"soon\"\nmax_body = \"1B\"\nbuffer = 1\nidle = 1\n").unwrap_err();
+        assert!(err.to_string().contains("invalid duration `soon`"), "{}", err);
+    }
+
+    #[test]
+    fn test_to_toml_renders_human_form() {
+        let mut http = Http::new();
+        let out = http.to_toml();
+        assert!(out.contains("# request timeout, Duration, default: \"30s\""), "{}", out);
+        assert!(out.contains("# timeout = \"30s\""), "{}", out);
+        assert!(out.contains("# max_body = \"10MiB\""), "{}", out);
+
+        http.timeout = Duration::from_secs(300);
+        let out = http.to_toml();
+        assert!(out.contains("\ntimeout = \"5m\""), "{}", out);
+
+        assert!(http.to_toml_string().unwrap().contains("timeout = \"5m\""));
+    }
+}
@@ -0,0 +1,562 @@
+use crate::config::meta::FieldMeta;
+use crate::config::paths;
+use crate::config::template::TemplateOptions;
+use crate::config::units::{self, ByteSize};
+use crate::error::ConfigurationResult;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use toml::Value;
+
+/// A type that can be used as a single value field of a configuration struct
+///
+/// `#[derive(Configuration)]` reads, writes and documents every field
+/// through this trait, so implementing it is all a type needs to become a
+/// field. Structs deriving `Configuration` are nested sections instead and
+/// do not implement it.
+///
+/// ```rust,ignore
+/// use libelp::ConfigValue;
+///
+/// #[derive(Debug, Clone, PartialEq)]
+/// struct Currency([u8; 3]);
+///
+/// impl ConfigValue for Currency {
+///     fn type_name() -> &'static str {
+///         "Currency"
+///     }
+///
+///     fn from_toml_value(value: &toml::Value) -> Result<Self, String> {
+///         let code = value.as_str().ok_or("expected a currency code")?;
+///         match code.as_bytes() {
+///             [a, b, c] if code.chars().all(|c| c.is_ascii_uppercase()) => Ok(Currency([*a, *b, *c])),
+///             _ => Err(format!("`{}` is not an ISO 4217 code", code)),
+///         }
+///     }
+///
+///     fn to_toml_value(&self) -> toml::Value {
+///         toml::Value::String(String::from_utf8_lossy(&self.0).into_owned())
+///     }
+/// }
+///
+/// #[derive(libelp::Configuration)]
+/// struct Billing {
+///     #[config(default = "EUR", note = "invoice currency")]
+///     currency: Currency,
+/// }
+/// ```
+///
+/// Fields written as a primitive type (`u16`, `f64`, `bool`, `String`) take
+/// a literal default as it is written. For every other type, aliases
+/// included, the kind of the literal is checked at compile time against
+/// [`ConfigValue::DEFAULT_LITERALS`] and [`ConfigValue::DEFAULT_RANGE`];
+/// the value is then converted through [`ConfigValue::from_default_literal`]
+/// (strings) or [`ConfigValue::from_toml_value`] (numbers and booleans)
+/// when `new()` runs, which panics with the field name if it is rejected.
+pub trait ConfigValue: Sized {
+    /// Name of the type in generated comments, e.g. `u16` or `Duration`
+    fn type_name() -> &'static str;
+
+    /// Convert a value read from a configuration file
+    ///
+    /// The error message is reported against the field's key and location.
+    fn from_toml_value(value: &Value) -> Result<Self, String>;
+
+    /// Convert to the value written to configuration files
+    fn to_toml_value(&self) -> Value;
+
+    /// The complete list of accepted values, for enum-like types
+    ///
+    /// Listed in verbose templates and in `FIELDS`; it is documentation
+    /// only, [`ConfigValue::from_toml_value`] still does the checking.
+    const ALLOWED_VALUES: Option<&'static [&'static str]> = None;
+
+    /// Build the value from a `#[config(default = "...")]` string
+    ///
+    /// Defaults to reading the string as a TOML string value.
+    fn from_default_literal(literal: &str) -> Result<Self, String> {
+        Self::from_toml_value(&Value::String(literal.to_string()))
+    }
+
+    /// The `#[config(default = ...)]` literals the type accepts
+    ///
+    /// Checked at compile time; [`ConfigValue::from_default_literal`] and
+    /// [`ConfigValue::from_toml_value`] still convert the default when `new()` runs.
+    const DEFAULT_LITERALS: Literals = Literals::ANY;
+
+    /// The range of integer defaults, checked at compile time like [`ConfigValue::DEFAULT_LITERALS`]
+    const DEFAULT_RANGE: Option<(i128, i128)> = None;
+
+    // Hooks used by the derive through `ConfigField`
+
+    #[doc(hidden)]
+    fn __elp_deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Self::from_toml_value(&value).map_err(serde::de::Error::custom)
+    }
+
+    #[doc(hidden)]
+    fn __elp_serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_toml_value().serialize(serializer)
+    }
+
+    #[doc(hidden)]
+    fn __elp_resolve_paths(
+        &mut self,
+        _key: &str,
+        _base_dir: &dyn Fn(&str) -> Option<PathBuf>,
+    ) -> ConfigurationResult<()> {
+        Ok(())
+    }
+}
+
+/// What `#[derive(Configuration)]` needs from the type of a field
+///
+/// Implemented for every [`ConfigValue`], and by the derive for nested
+/// configuration structs, which are sections rather than values. Keeping
+/// the two apart by trait is what lets a builder set a value directly and
+/// edit a section through the section's own builder.
+#[doc(hidden)]
+pub trait ConfigField: Sized {
+    const __ELP_DEPTH: usize = 0;
+
+    const __ELP_FIELDS: Option<&'static [FieldMeta]> = None;
+
+    const __ELP_DOC: Option<&'static str> = None;
+
+    const __ELP_ALLOWED: Option<&'static [&'static str]> = None;
+
+    fn __elp_type_name() -> &'static str;
+
+    fn __elp_from_value(value: &Value) -> Result<Self, String>;
+
+    fn __elp_to_value(&self) -> Value;
+
+    fn __elp_deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+
+    fn __elp_serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+
+    fn __elp_from_toml_lenient(value: &Value) -> Option<Self> {
+        Self::__elp_from_value(value).ok()
+    }
+
+    fn __elp_section(&self, _depth: usize, _path: &str, _options: &TemplateOptions) -> Option<String> {
+        None
+    }
+
+    fn __elp_field_note(_path: &str) -> Option<&'static str> {
+        None
+    }
+
+    fn __elp_has_secrets() -> bool {
+        false
+    }
+
+    fn __elp_get_path(&self, _path: &str) -> Option<Value> {
+        None
+    }
+
+    fn __elp_set_path(&mut self, path: &str, _value: &Value) -> ConfigurationResult<()> {
+        Err(crate::ConfigurationError::unknown_key(path, Vec::new()))
+    }
+
+    fn __elp_validate(&self, _key: &str) -> ConfigurationResult<()> {
+        Ok(())
+    }
+
+    fn __elp_resolve_paths(
+        &mut self,
+        _key: &str,
+        _base_dir: &dyn Fn(&str) -> Option<PathBuf>,
+    ) -> ConfigurationResult<()> {
+        Ok(())
+    }
+}
+
+impl<T: ConfigValue> ConfigField for T {
+    const __ELP_ALLOWED: Option<&'static [&'static str]> = T::ALLOWED_VALUES;
+
+    fn __elp_type_name() -> &'static str {
+        T::type_name()
+    }
+
+    fn __elp_from_value(value: &Value) -> Result<Self, String> {
+        T::from_toml_value(value)
+    }
+
+    fn __elp_to_value(&self) -> Value {
+        self.to_toml_value()
+    }
+
+    fn __elp_deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <T as ConfigValue>::__elp_deserialize(deserializer)
+    }
+
+    fn __elp_serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        <T as ConfigValue>::__elp_serialize(self, serializer)
+    }
+
+    fn __elp_resolve_paths(
+        &mut self,
+        key: &str,
+        base_dir: &dyn Fn(&str) -> Option<PathBuf>,
+    ) -> ConfigurationResult<()> {
+        <T as ConfigValue>::__elp_resolve_paths(self, key, base_dir)
+    }
+}
+
+/// Kinds of `#[config(default = ...)]` literal, as a set
+///
+/// A type lists the kinds it accepts in [`ConfigValue::DEFAULT_LITERALS`];
+/// the derive describes each default by every kind it parses as, so a string
+/// default such as `"1m30s"` is both a `STRING` and a `DURATION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Literals(u32);
+
+impl Literals {
+    /// Any literal; the `ConfigValue` impl alone decides, when `new()` runs
+    pub const ANY: Literals = Literals(u32::MAX);
+    /// No literal, to start a set with [`Literals::or`]
+    pub const NONE: Literals = Literals(0);
+    pub const STRING: Literals = Literals(1);
+    pub const INTEGER: Literals = Literals(1 << 1);
+    pub const FLOAT: Literals = Literals(1 << 2);
+    pub const BOOL: Literals = Literals(1 << 3);
+    /// A string such as `"1m30s"`, see [`units::parse_duration`]
+    pub const DURATION: Literals = Literals(1 << 4);
+    /// A string such as `"10MiB"`, see [`ByteSize`]
+    pub const BYTE_SIZE: Literals = Literals(1 << 5);
+    pub const IP_ADDR: Literals = Literals(1 << 6);
+    pub const IPV4_ADDR: Literals = Literals(1 << 7);
+    pub const IPV6_ADDR: Literals = Literals(1 << 8);
+    pub const SOCKET_ADDR: Literals = Literals(1 << 9);
+    pub const URL: Literals = Literals(1 << 10);
+
+    /// Both sets of kinds
+    pub const fn or(self, other: Literals) -> Literals {
+        Literals(self.0 | other.0)
+    }
+
+    /// Whether the sets share a kind
+    pub const fn intersects(self, other: Literals) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+/// Fail compilation unless `T` accepts a default of the `kinds` described
+///
+/// `integer` is the value of an integer literal, checked against
+/// [`ConfigValue::DEFAULT_RANGE`].
+#[doc(hidden)]
+pub const fn check_default<T: ConfigValue>(kinds: Literals, integer: Option<i128>) {
+    assert!(T::DEFAULT_LITERALS.intersects(kinds), "the default does not fit the type of the field");
+    if let (Some(n), Some((min, max))) = (integer, T::DEFAULT_RANGE) {
+        assert!(min <= n && n <= max, "the default is out of range for the type of the field");
+    }
+}
+
+/// Render a value the way it appears on the right of `key = ...`
+pub fn inline(value: &Value) -> String {
+    value.to_string()
+}
+
+/// Turn `text` into TOML comment lines, one `# ` line per line of text
+pub fn comment(text: &str) -> String {
+    text.lines().map(|line| if line.is_empty() { "#".to_string() } else { format!("# {}", line) }).collect::<Vec<_>>().join("\n")
+}
+
+#[doc(hidden)]
+pub fn serialize_field<T: ConfigField, S: Serializer>(value: &&T, serializer: S) -> Result<S::Ok, S::Error> {
+    ConfigField::__elp_serialize(*value, serializer)
+}
+
+#[doc(hidden)]
+pub fn deserialize_field<'de, T: ConfigField, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    <T as ConfigField>::__elp_deserialize(deserializer)
+}
+
+#[doc(hidden)]
+pub fn default_from_literal<T: ConfigValue>(field: &str, literal: &str) -> T {
+    T::from_default_literal(literal)
+        .unwrap_or_else(|e| panic!("invalid default for `{}` ({}): {}", field, T::type_name(), e))
+}
+
+#[doc(hidden)]
+pub fn default_from_value<T: ConfigValue>(field: &str, value: Value) -> T {
+    T::from_toml_value(&value)
+        .unwrap_or_else(|e| panic!("invalid default for `{}` ({}): {}", field, T::type_name(), e))
+}
+
+/// Initial value of a `#[config(required)]` field without a default
+///
+/// The empty value of types with a `Default`; derived structs without
+/// defaults of their own use their placeholders instead.
+#[doc(hidden)]
+pub trait Placeholder {
+    fn __elp_placeholder() -> Self;
+}
+
+impl<T: Default> Placeholder for T {
+    fn __elp_placeholder() -> Self {
+        T::default()
+    }
+}
+
+// Types whose serde form is already the configuration form
+macro_rules! serde_value {
+    (@one $ty:ident, $literals:expr, $range:expr) => {
+        impl ConfigValue for $ty {
+            fn type_name() -> &'static str {
+                stringify!($ty)
+            }
+
+            fn from_toml_value(value: &Value) -> Result<Self, String> {
+                <$ty>::deserialize(value.clone()).map_err(|e| e.to_string())
+            }
+
+            fn to_toml_value(&self) -> Value {
+                Value::try_from(self).unwrap_or_else(|_| Value::String(self.to_string()))
+            }
+
+            const DEFAULT_LITERALS: Literals = $literals;
+
+            const DEFAULT_RANGE: Option<(i128, i128)> = $range;
+
+            fn __elp_deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <$ty>::deserialize(deserializer)
+            }
+
+            fn __elp_serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                Serialize::serialize(self, serializer)
+            }
+        }
+    };
+    (integers; $($ty:ident),* $(,)?) => {$(
+        serde_value!(@one $ty, Literals::INTEGER, Some((<$ty>::MIN as i128, clamp_to_i128(<$ty>::MAX as u128))));
+    )*};
+    ($literals:expr; $($ty:ident),* $(,)?) => {$(
+        serde_value!(@one $ty, $literals, None);
+    )*};
+}
+
+// `u128::MAX` does not fit; no literal reaches it anyway
+const fn clamp_to_i128(max: u128) -> i128 {
+    if max > i128::MAX as u128 { i128::MAX } else { max as i128 }
+}
+
+// Integers read from the file as numbers of seconds or bytes
+const UNSIGNED_64: Option<(i128, i128)> = Some((0, u64::MAX as i128));
+
+serde_value!(integers; i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+serde_value!(Literals::FLOAT.or(Literals::INTEGER); f32, f64);
+serde_value!(Literals::BOOL; bool);
+serde_value!(Literals::STRING; String);
+serde_value!(@one ByteSize, Literals::BYTE_SIZE.or(Literals::INTEGER), UNSIGNED_64);
+serde_value!(Literals::IP_ADDR; IpAddr);
+serde_value!(Literals::IPV4_ADDR; Ipv4Addr);
+serde_value!(Literals::IPV6_ADDR; Ipv6Addr);
+serde_value!(Literals::SOCKET_ADDR; SocketAddr);
+
+#[cfg(feature = "url")]
+use url::Url;
+#[cfg(feature = "url")]
+serde_value!(Literals::URL; Url);
+
+impl ConfigValue for Duration {
+    fn type_name() -> &'static str {
+        "Duration"
+    }
+
+    fn from_toml_value(value: &Value) -> Result<Self, String> {
+        units::duration::deserialize(value.clone()).map_err(|e| e.to_string())
+    }
+
+    fn to_toml_value(&self) -> Value {
+        Value::String(units::format_duration(*self))
+    }
+
+    const DEFAULT_LITERALS: Literals = Literals::DURATION.or(Literals::INTEGER);
+
+    const DEFAULT_RANGE: Option<(i128, i128)> = UNSIGNED_64;
+
+    fn __elp_deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        units::duration::deserialize(deserializer)
+    }
+}
+
+impl ConfigValue for PathBuf {
+    fn type_name() -> &'static str {
+        "PathBuf"
+    }
+
+    fn from_toml_value(value: &Value) -> Result<Self, String> {
+        PathBuf::deserialize(value.clone()).map_err(|e| e.to_string())
+    }
+
+    fn to_toml_value(&self) -> Value {
+        Value::String(self.to_string_lossy().into_owned())
+    }
+
+    const DEFAULT_LITERALS: Literals = Literals::STRING;
+
+    fn __elp_deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PathBuf::deserialize(deserializer)
+    }
+
+    fn __elp_resolve_paths(
+        &mut self,
+        key: &str,
+        base_dir: &dyn Fn(&str) -> Option<PathBuf>,
+    ) -> ConfigurationResult<()> {
+        paths::resolve_field(self, key, base_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_util::temp_path;
+    use crate::{ConfigLoader, Configuration, ConfigurationError};
+    use std::fs;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Currency([u8; 3]);
+
+    impl ConfigValue for Currency {
+        fn type_name() -> &'static str {
+            "Currency"
+        }
+
+        fn from_toml_value(value: &Value) -> Result<Self, String> {
+            let code = value.as_str().ok_or("expected a currency code")?;
+            match code.as_bytes() {
+                [a, b, c] if code.chars().all(|c| c.is_ascii_uppercase()) => Ok(Currency([*a, *b, *c])),
+                _ => Err(format!("`{}` is not an ISO 4217 code", code)),
+            }
+        }
+
+        fn to_toml_value(&self) -> Value {
+            Value::String(String::from_utf8_lossy(&self.0).into_owned())
+        }
+    }
+
+    // Stored in basis points, written as a percentage
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct TaxRate(u32);
+
+    impl ConfigValue for TaxRate {
+        fn type_name() -> &'static str {
+            "TaxRate"
+        }
+
+        fn from_toml_value(value: &Value) -> Result<Self, String> {
+            let percent = match value {
+                Value::Float(f) => *f,
+                Value::Integer(i) => *i as f64,
+                _ => return Err("expected a percentage".to_string()),
+            };
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("{}% is out of range", percent));
+            }
+            Ok(TaxRate((percent * 100.0).round() as u32))
+        }
+
+        fn to_toml_value(&self) -> Value {
+            Value::Float(f64::from(self.0) / 100.0)
+        }
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Invoice {
+        #[config(default = "EUR", note = "invoice currency")]
+        currency: Currency,
+        #[config(default = 19.5, note = "VAT percentage")]
+        vat: TaxRate,
+        #[config(default = 30, note = "reduced VAT percentage")]
+        reduced: TaxRate,
+    }
+
+    #[derive(libelp_proc::Configuration, Debug)]
+    struct Shop {
+        #[config(default = "shop", note = "shop name")]
+        name: String,
+        invoice: Invoice,
+    }
+
+    #[test]
+    fn test_builtin_values_round_trip() {
+        assert_eq!(u16::from_toml_value(&Value::Integer(8080)), Ok(8080));
+        assert!(u8::from_toml_value(&Value::Integer(300)).is_err());
+        assert_eq!(Duration::from_toml_value(&Value::String("1m".into())), Ok(Duration::from_secs(60)));
+        assert_eq!(Duration::from_secs(90).to_toml_value(), Value::String("1m30s".into()));
+        assert_eq!(ByteSize::mib(1).to_toml_value(), Value::String("1MiB".into()));
+        assert_eq!(u64::MAX.to_toml_value(), Value::String(u64::MAX.to_string()));
+        assert_eq!(inline(&"a\"b".to_string().to_toml_value()), "'a\"b'");
+        assert_eq!(<Ipv4Addr as ConfigValue>::type_name(), "Ipv4Addr");
+    }
+
+    #[test]
+    fn test_user_defined_values_as_fields() {
+        let shop = Shop::new();
+        assert_eq!(shop.invoice.currency, Currency(*b"EUR"));
+        assert_eq!(shop.invoice.vat, TaxRate(1950));
+        assert_eq!(shop.invoice.reduced, TaxRate(3000));
+
+        let out = shop.to_toml();
+        assert!(out.contains("# invoice currency, Currency, default: \"EUR\""), "{}", out);
+        assert!(out.contains("# vat = 19.5"), "{}", out);
+
+        let shop = Shop::from_toml_string("name = \"s\"\n[invoice]\ncurrency = \"USD\"\nvat = 7\nreduced = 2.5\n").unwrap();
+        assert_eq!(shop.invoice.currency, Currency(*b"USD"));
+        assert_eq!(shop.invoice.vat, TaxRate(700));
+        assert!(shop.to_toml().contains("\ncurrency = \"USD\""));
+        let back = Shop::from_toml_string(&shop.to_toml_string().unwrap()).unwrap();
+        assert_eq!(back.invoice.reduced, TaxRate(250));
+    }
+
+    #[test]
+    fn test_user_defined_value_errors_name_the_field() {
+        let path = temp_path("libelp_value").with_extension("toml");
+        fs::write(&path, "name = \"s\"\n[invoice]\ncurrency = \"usd\"\nvat = 7\nreduced = 2.5\n").unwrap();
+        let err = ConfigLoader::new().file(&path).load::<Shop>().unwrap_err();
+        let _ = fs::remove_file(&path);
+        assert!(err.to_string().contains("`usd` is not an ISO 4217 code"), "{}", err);
+        let ctx = err.parse_context().expect("expected parse context");
+        assert_eq!(ctx.field.as_deref(), Some("invoice.currency"));
+        assert_eq!(ctx.expected.as_deref(), Some("Currency"));
+        assert_eq!(ctx.note.as_deref(), Some("invoice currency"));
+        assert!(matches!(err, ConfigurationError::TomlParseError { line: Some(3), .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_default_check_accepts_what_the_type_parses() {
+        check_default::<Duration>(Literals::STRING.or(Literals::DURATION), None);
+        check_default::<Duration>(Literals::INTEGER, Some(30));
+        check_default::<f64>(Literals::INTEGER, Some(1));
+        check_default::<TaxRate>(Literals::FLOAT, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the default does not fit the type of the field")]
+    fn test_default_check_rejects_other_kinds() {
+        check_default::<Duration>(Literals::STRING, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the default is out of range for the type of the field")]
+    fn test_default_check_rejects_out_of_range_integers() {
+        check_default::<u8>(Literals::INTEGER, Some(300));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid default for `rate` (TaxRate): 150% is out of range")]
+    fn test_invalid_user_default_panics_with_field_name() {
+        #[derive(libelp_proc::Configuration, Debug)]
+        struct Bad {
+            #[config(default = 150, note = "rate")]
+            rate: TaxRate,
+        }
+        let _ = Bad::new();
+    }
+}
@@ -7,4 +7,18 @@ pub use config::*;
 
 // Re-export commonly used items at crate root for ergonomics
 pub use crate::config::config::Configuration;
-pub use crate::config::error::{ConfigurationError, ConfigurationResult, ErrorCategory};
+pub use crate::config::error::{ConfigurationError, ConfigurationResult, ErrorCategory, ParseContext};
+#[cfg(feature = "encryption")]
+pub use crate::config::encryption::EncryptionKey;
+pub use crate::config::format::Format;
+pub use crate::config::loader::ConfigLoader;
+pub use crate::config::meta::{Constraint, FieldMeta};
+pub use crate::config::permissions::PermissionPolicy;
+pub use crate::config::persist::SaveOptions;
+pub use crate::config::render::RenderStyle;
+pub use crate::config::shared::SharedConfig;
+pub use crate::config::template::{TemplateOptions, Verbosity};
+pub use crate::config::units::ByteSize;
+pub use crate::config::value::ConfigValue;
+#[cfg(feature = "url")]
+pub use url::Url;